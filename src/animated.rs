@@ -0,0 +1,111 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::io::Cursor;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::{load_from_memory_with_format, AnimationDecoder, Frame, ImageFormat, Rgba, RgbaImage};
+
+use gif::DecodeOptions as GifDecodeOptions;
+
+use crate::PngToWasm4SrcError;
+
+/// The frames of an animated image, composited on the full canvas
+///
+/// Durations are in milliseconds.
+pub(crate) struct DecodedAnimation {
+    pub(crate) frames: Vec<RgbaImage>,
+    pub(crate) durations: Vec<u32>,
+}
+
+impl DecodedAnimation {
+    /// Stack all frames vertically in a single image
+    pub(crate) fn to_sheet(&self) -> RgbaImage {
+        let (width, height) = self
+            .frames
+            .first()
+            .map(RgbaImage::dimensions)
+            .unwrap_or_default();
+        let mut sheet = RgbaImage::new(width, height * self.frames.len() as u32);
+        for (index, frame) in self.frames.iter().enumerate() {
+            for (x, y, pixel) in frame.enumerate_pixels() {
+                sheet.put_pixel(x, y + height * index as u32, *pixel);
+            }
+        }
+        sheet
+    }
+}
+
+/// Return whether a PNG image is animated
+pub(crate) fn is_animated_png(bytes: &[u8]) -> Result<bool, PngToWasm4SrcError> {
+    let decoder = PngDecoder::new(Cursor::new(bytes))?;
+    Ok(decoder.is_apng())
+}
+
+/// Decode all frames of an animated PNG image
+///
+/// A PNG image that is not animated is decoded as a single frame without a
+/// duration.
+pub(crate) fn decode_apng(bytes: &[u8]) -> Result<DecodedAnimation, PngToWasm4SrcError> {
+    let decoder = PngDecoder::new(Cursor::new(bytes))?;
+    if !decoder.is_apng() {
+        let frame = load_from_memory_with_format(bytes, ImageFormat::Png)?;
+        return Ok(DecodedAnimation {
+            frames: vec![frame.into_rgba8()],
+            durations: Vec::default(),
+        });
+    }
+    let frames = decoder.apng().into_frames().collect_frames()?;
+    Ok(animation_from_frames(frames))
+}
+
+/// Decode all frames of a GIF image
+pub(crate) fn decode_gif(bytes: &[u8]) -> Result<DecodedAnimation, PngToWasm4SrcError> {
+    let decoder = GifDecoder::new(Cursor::new(bytes))?;
+    let frames = decoder.into_frames().collect_frames()?;
+    Ok(animation_from_frames(frames))
+}
+
+/// Read the global palette of a GIF image, if any
+///
+/// The entry used as transparent colour by the first frame that has one is
+/// made fully transparent black, the same as the transparent pixels of
+/// decoded frames.
+pub(crate) fn read_gif_palette(bytes: &[u8]) -> Result<Option<Vec<Rgba<u8>>>, PngToWasm4SrcError> {
+    let mut decoder = GifDecodeOptions::new().read_info(Cursor::new(bytes))?;
+    let mut palette: Vec<Rgba<u8>> = match decoder.global_palette() {
+        Some(palette) => palette
+            .chunks(3)
+            .map(|rgb| Rgba([rgb[0], rgb[1], rgb[2], 0xff]))
+            .collect(),
+        None => return Ok(None),
+    };
+
+    let mut transparent_index = None;
+    while let Some(frame) = decoder.read_next_frame()? {
+        if frame.transparent.is_some() {
+            transparent_index = frame.transparent;
+            break;
+        }
+    }
+    if let Some(color) = transparent_index.and_then(|index| palette.get_mut(index as usize)) {
+        *color = Rgba([0, 0, 0, 0]);
+    }
+
+    Ok(Some(palette))
+}
+
+fn animation_from_frames(frames: Vec<Frame>) -> DecodedAnimation {
+    let durations = frames
+        .iter()
+        .map(|frame| {
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            (numerator + denominator / 2) / denominator.max(1)
+        })
+        .collect();
+    let frames = frames.into_iter().map(Frame::into_buffer).collect();
+    DecodedAnimation { frames, durations }
+}
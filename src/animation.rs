@@ -0,0 +1,216 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use crate::PngToWasm4SrcError;
+
+/// Number of WASM-4 ticks per second
+///
+/// WASM-4 calls the `update` function 60 times per second.
+const TICKS_PER_SECOND: u32 = 60;
+
+/// An animation over the frames of a sprite sheet
+///
+/// An animation is a sequence of frames, each displayed for a number of
+/// WASM-4 ticks (1/60 of a second).
+/// Animations loop by default, and can optionally play back and forth
+/// (ping-pong).
+///
+/// ```
+/// # use png2wasm4src::Animation;
+/// let animation = Animation::new("walk", vec![0, 1, 2, 1], vec![6, 6, 6, 6])
+///     .with_looping(true)
+///     .with_ping_pong(false);
+///
+/// assert_eq!(animation.frames(), [0, 1, 2, 1]);
+/// assert_eq!(animation.total_duration(), 24);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Animation {
+    name: String,
+    frames: Vec<usize>,
+    durations: Vec<u32>,
+    looping: bool,
+    ping_pong: bool,
+}
+
+impl Animation {
+    /// Create an animation from its name, its frame indices and their
+    /// durations in ticks
+    ///
+    /// The animation loops and does not play back and forth by default.
+    pub fn new(name: impl Into<String>, frames: Vec<usize>, durations: Vec<u32>) -> Self {
+        Self {
+            name: name.into(),
+            frames,
+            durations,
+            looping: true,
+            ping_pong: false,
+        }
+    }
+
+    /// Set whether the animation restarts after its last frame
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Set whether the animation plays back and forth
+    pub fn with_ping_pong(mut self, ping_pong: bool) -> Self {
+        self.ping_pong = ping_pong;
+        self
+    }
+
+    /// Return the animation name
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    /// Return the indices of the animation frames in the sprite sheet
+    pub fn frames(&self) -> &[usize] {
+        self.frames.as_ref()
+    }
+
+    /// Return the duration of each frame in ticks
+    pub fn durations(&self) -> &[u32] {
+        self.durations.as_ref()
+    }
+
+    /// Return the duration of the whole animation in ticks
+    pub fn total_duration(&self) -> u32 {
+        self.durations.iter().sum()
+    }
+
+    /// Return whether the animation restarts after its last frame
+    pub fn looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Return whether the animation plays back and forth
+    pub fn ping_pong(&self) -> bool {
+        self.ping_pong
+    }
+
+    /// Check that the animation only refers to existing frames
+    pub(crate) fn validate(&self, frame_count: usize) -> Result<(), PngToWasm4SrcError> {
+        if self.frames.len() != self.durations.len() {
+            return Err(PngToWasm4SrcError::InvalidAnimationDurations {
+                animation: self.name.clone(),
+            });
+        }
+        match self.frames.iter().find(|frame| **frame >= frame_count) {
+            Some(frame) => Err(PngToWasm4SrcError::InvalidAnimationFrame {
+                animation: self.name.clone(),
+                frame: *frame,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Playback direction of an Aseprite tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TagDirection {
+    Forward,
+    Reverse,
+    PingPong,
+    PingPongReverse,
+}
+
+/// Create an animation from an Aseprite tag over frames `from..=to`
+///
+/// Frame durations are in milliseconds, one for each frame of the sprite.
+pub(crate) fn animation_from_tag(
+    name: String,
+    from: usize,
+    to: usize,
+    direction: TagDirection,
+    looping: bool,
+    frame_durations: &[u32],
+) -> Result<Animation, PngToWasm4SrcError> {
+    if to >= frame_durations.len() {
+        return Err(PngToWasm4SrcError::InvalidAnimationFrame {
+            animation: name,
+            frame: to,
+        });
+    }
+    let mut frames: Vec<usize> = (from..=to).collect();
+    if matches!(
+        direction,
+        TagDirection::Reverse | TagDirection::PingPongReverse
+    ) {
+        frames.reverse();
+    }
+    let ping_pong = matches!(
+        direction,
+        TagDirection::PingPong | TagDirection::PingPongReverse
+    );
+    let durations = frames
+        .iter()
+        .map(|frame| millis_to_ticks(frame_durations[*frame]))
+        .collect();
+    Ok(Animation::new(name, frames, durations)
+        .with_looping(looping)
+        .with_ping_pong(ping_pong))
+}
+
+/// Convert a duration in milliseconds to WASM-4 ticks
+///
+/// The result is rounded to the nearest tick, and is at least one tick.
+pub(crate) fn millis_to_ticks(millis: u32) -> u32 {
+    let ticks = (u64::from(millis) * u64::from(TICKS_PER_SECOND) + 500) / 1000;
+    (ticks as u32).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_millis_to_ticks() {
+        assert_eq!(millis_to_ticks(100), 6);
+        assert_eq!(millis_to_ticks(1000), 60);
+        assert_eq!(millis_to_ticks(25), 2);
+        assert_eq!(millis_to_ticks(0), 1);
+    }
+
+    #[test]
+    fn animation_from_reverse_tag() -> Result<(), PngToWasm4SrcError> {
+        let animation = animation_from_tag(
+            "melt".into(),
+            1,
+            2,
+            TagDirection::PingPongReverse,
+            false,
+            &[100, 200, 100],
+        )?;
+
+        let expected = Animation::new("melt", vec![2, 1], vec![6, 12])
+            .with_looping(false)
+            .with_ping_pong(true);
+        assert_eq!(animation, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn validate_frames() {
+        let animation = Animation::new("walk", vec![0, 1, 4], vec![6, 6, 6]);
+
+        assert!(animation.validate(5).is_ok());
+        assert!(matches!(
+            animation.validate(4),
+            Err(PngToWasm4SrcError::InvalidAnimationFrame { frame: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn validate_durations() {
+        let animation = Animation::new("walk", vec![0, 1, 2], vec![6, 6]);
+
+        assert!(matches!(
+            animation.validate(5),
+            Err(PngToWasm4SrcError::InvalidAnimationDurations { .. })
+        ));
+    }
+}
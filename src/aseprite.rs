@@ -0,0 +1,554 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::convert::TryInto;
+
+use image::{Rgba, RgbaImage};
+
+use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+use crate::animation::{animation_from_tag, TagDirection};
+use crate::{Animation, PngToWasm4SrcError};
+
+/// Magic number identifying Aseprite files
+const FILE_MAGIC: u16 = 0xa5e0;
+
+/// Magic number identifying Aseprite frames
+const FRAME_MAGIC: u16 = 0xf1fa;
+
+/// Colour depth of indexed Aseprite files, in bits per pixel
+const INDEXED_COLOR_DEPTH: u16 = 8;
+
+const OLD_PALETTE_CHUNK: u16 = 0x0004;
+const LAYER_CHUNK: u16 = 0x2004;
+const CEL_CHUNK: u16 = 0x2005;
+const TAGS_CHUNK: u16 = 0x2018;
+const PALETTE_CHUNK: u16 = 0x2019;
+
+const LAYER_VISIBLE: u16 = 0x1;
+const LAYER_BACKGROUND: u16 = 0x2;
+
+/// Selection of the layers of an Aseprite file to flatten into a sprite
+///
+/// Layers are flattened from the bottom one to the top one, and each pixel
+/// takes the colour of the topmost layer where it is not transparent.
+/// Blend modes and opacity are ignored.
+///
+/// ```
+/// # use png2wasm4src::LayerSelection;
+/// let layers = LayerSelection::Named(vec!["body".into(), "outline".into()]);
+///
+/// assert_ne!(layers, LayerSelection::default());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayerSelection {
+    /// Flatten all visible layers
+    ///
+    /// Layers inside hidden groups are hidden as well.
+    Visible,
+
+    /// Flatten only the layers with the given names, even if hidden
+    ///
+    /// Naming a group selects all the layers inside it.
+    Named(Vec<String>),
+}
+
+impl Default for LayerSelection {
+    fn default() -> Self {
+        LayerSelection::Visible
+    }
+}
+
+/// An Aseprite file in indexed colour mode
+pub(crate) struct AsepriteFile {
+    width: u32,
+    height: u32,
+    transparent_index: u8,
+    palette: Vec<Rgba<u8>>,
+    layers: Vec<Layer>,
+    frames: Vec<Frame>,
+    tags: Vec<Tag>,
+}
+
+struct Layer {
+    name: String,
+    flags: u16,
+    group: bool,
+    ancestors: Vec<usize>,
+}
+
+struct Frame {
+    duration: u32,
+    cels: Vec<Cel>,
+}
+
+struct Cel {
+    layer: usize,
+    x: i32,
+    y: i32,
+    z_index: i32,
+    content: CelContent,
+}
+
+enum CelContent {
+    Image(CelImage),
+    Linked(usize),
+}
+
+struct CelImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+struct Tag {
+    name: String,
+    from: usize,
+    to: usize,
+    direction: TagDirection,
+    repeat: u16,
+}
+
+impl AsepriteFile {
+    /// Parse an Aseprite file
+    ///
+    /// Only files in indexed colour mode are accepted.
+    pub(crate) fn parse(bytes: &[u8]) -> Result<Self, PngToWasm4SrcError> {
+        let mut reader = Reader::new(bytes);
+
+        let _file_size = reader.u32()?;
+        if reader.u16()? != FILE_MAGIC {
+            return Err(PngToWasm4SrcError::InvalidAseprite("not an Aseprite file"));
+        }
+        let frame_count = reader.u16()?;
+        if frame_count == 0 {
+            return Err(PngToWasm4SrcError::InvalidAseprite("file has no frames"));
+        }
+        let width = reader.u16()? as u32;
+        let height = reader.u16()? as u32;
+        if reader.u16()? != INDEXED_COLOR_DEPTH {
+            return Err(PngToWasm4SrcError::NotIndexedAseprite);
+        }
+        reader.skip(14)?;
+        let transparent_index = reader.u8()?;
+        reader.skip(99)?;
+
+        let mut file = Self {
+            width,
+            height,
+            transparent_index,
+            palette: Vec::default(),
+            layers: Vec::default(),
+            frames: Vec::default(),
+            tags: Vec::default(),
+        };
+
+        let mut has_new_palette = false;
+        for _ in 0..frame_count {
+            let frame_start = reader.position;
+            let frame_size = reader.u32()? as usize;
+            if reader.u16()? != FRAME_MAGIC {
+                return Err(PngToWasm4SrcError::InvalidAseprite("invalid frame header"));
+            }
+            let old_chunk_count = reader.u16()? as u32;
+            let duration = reader.u16()? as u32;
+            reader.skip(2)?;
+            let chunk_count = match reader.u32()? {
+                0 => old_chunk_count,
+                chunk_count => chunk_count,
+            };
+
+            let mut frame = Frame {
+                duration,
+                cels: Vec::default(),
+            };
+            for _ in 0..chunk_count {
+                let chunk_size = reader.u32()? as usize;
+                let chunk_type = reader.u16()?;
+                let data_size = chunk_size
+                    .checked_sub(6)
+                    .ok_or(PngToWasm4SrcError::InvalidAseprite("invalid chunk size"))?;
+                let mut chunk = Reader::new(reader.bytes(data_size)?);
+                match chunk_type {
+                    LAYER_CHUNK => file.parse_layer(&mut chunk)?,
+                    CEL_CHUNK => frame.cels.push(parse_cel(&mut chunk)?),
+                    TAGS_CHUNK => file.tags = parse_tags(&mut chunk)?,
+                    PALETTE_CHUNK => {
+                        file.parse_palette(&mut chunk)?;
+                        has_new_palette = true;
+                    }
+                    OLD_PALETTE_CHUNK if !has_new_palette => file.parse_old_palette(&mut chunk)?,
+                    _ => {}
+                }
+            }
+            file.frames.push(frame);
+
+            reader.position = frame_start + frame_size;
+        }
+
+        Ok(file)
+    }
+
+    /// Return the number of frames
+    pub(crate) fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Return the animations defined by the tags
+    pub(crate) fn animations(&self) -> Result<Vec<Animation>, PngToWasm4SrcError> {
+        let frame_durations: Vec<u32> = self.frames.iter().map(|frame| frame.duration).collect();
+        self.tags
+            .iter()
+            .map(|tag| {
+                animation_from_tag(
+                    tag.name.clone(),
+                    tag.from,
+                    tag.to,
+                    tag.direction,
+                    tag.repeat == 0,
+                    &frame_durations,
+                )
+            })
+            .collect()
+    }
+
+    /// Flatten the first frames and stack them vertically in a single image
+    ///
+    /// Return the image and the palette.
+    /// If any pixel is transparent, the palette entry of the transparent
+    /// index is made fully transparent.
+    pub(crate) fn render_sheet(
+        &self,
+        layers: &LayerSelection,
+        frame_count: usize,
+    ) -> Result<(RgbaImage, Vec<Rgba<u8>>), PngToWasm4SrcError> {
+        let selected = self.select_layers(layers)?;
+
+        let frame_size = (self.width * self.height) as usize;
+        let mut indices = Vec::with_capacity(frame_size * frame_count);
+        for frame in 0..frame_count {
+            indices.extend(self.flatten_frame(frame, &selected)?);
+        }
+
+        // The transparent entry has the same colour as transparent pixels
+        let transparent = Rgba([0, 0, 0, 0]);
+        let mut palette = self.palette.clone();
+        let has_transparent_pixels = indices.iter().any(Option::is_none);
+        if has_transparent_pixels {
+            if let Some(color) = palette.get_mut(self.transparent_index as usize) {
+                *color = transparent;
+            }
+        }
+
+        let pixels =
+            indices
+                .into_iter()
+                .map(|index| match index {
+                    Some(index) => palette.get(index as usize).copied().ok_or(
+                        PngToWasm4SrcError::InvalidAseprite("pixel outside the palette"),
+                    ),
+                    None => Ok(transparent),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+        let mut image = RgbaImage::new(self.width, self.height * frame_count as u32);
+        for (pixel, color) in image.pixels_mut().zip(pixels) {
+            *pixel = color;
+        }
+
+        Ok((image, palette))
+    }
+
+    fn parse_layer(&mut self, chunk: &mut Reader) -> Result<(), PngToWasm4SrcError> {
+        let flags = chunk.u16()?;
+        let layer_type = chunk.u16()?;
+        let child_level = chunk.u16()? as usize;
+        chunk.skip(10)?;
+        let name = chunk.string()?;
+
+        let mut ancestors = match self.layers.last() {
+            Some(previous) => {
+                let mut ancestors = previous.ancestors.clone();
+                ancestors.push(self.layers.len() - 1);
+                ancestors
+            }
+            None => Vec::default(),
+        };
+        ancestors.truncate(child_level);
+
+        self.layers.push(Layer {
+            name,
+            flags,
+            group: layer_type == 1,
+            ancestors,
+        });
+        Ok(())
+    }
+
+    fn parse_palette(&mut self, chunk: &mut Reader) -> Result<(), PngToWasm4SrcError> {
+        let size = chunk.u32()? as usize;
+        let first = chunk.u32()? as usize;
+        let last = chunk.u32()? as usize;
+        chunk.skip(8)?;
+        if first > last || last >= size {
+            return Err(PngToWasm4SrcError::InvalidAseprite("invalid palette range"));
+        }
+
+        self.palette.resize(size, Rgba([0, 0, 0, 0xff]));
+        for color in &mut self.palette[first..=last] {
+            let flags = chunk.u16()?;
+            *color = Rgba([chunk.u8()?, chunk.u8()?, chunk.u8()?, chunk.u8()?]);
+            if flags & 0x1 != 0 {
+                let _name = chunk.string()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_old_palette(&mut self, chunk: &mut Reader) -> Result<(), PngToWasm4SrcError> {
+        let packet_count = chunk.u16()?;
+        let mut index = 0;
+        for _ in 0..packet_count {
+            index += chunk.u8()? as usize;
+            let color_count = match chunk.u8()? {
+                0 => 256,
+                color_count => color_count as usize,
+            };
+            if self.palette.len() < index + color_count {
+                self.palette
+                    .resize(index + color_count, Rgba([0, 0, 0, 0xff]));
+            }
+            for color in &mut self.palette[index..index + color_count] {
+                *color = Rgba([chunk.u8()?, chunk.u8()?, chunk.u8()?, 0xff]);
+            }
+            index += color_count;
+        }
+        Ok(())
+    }
+
+    /// Return whether each layer is selected
+    fn select_layers(&self, selection: &LayerSelection) -> Result<Vec<bool>, PngToWasm4SrcError> {
+        match selection {
+            LayerSelection::Visible => Ok(self
+                .layers
+                .iter()
+                .map(|layer| {
+                    layer.flags & LAYER_VISIBLE != 0
+                        && layer
+                            .ancestors
+                            .iter()
+                            .all(|ancestor| self.layers[*ancestor].flags & LAYER_VISIBLE != 0)
+                })
+                .collect()),
+            LayerSelection::Named(names) => {
+                if let Some(name) = names
+                    .iter()
+                    .find(|name| !self.layers.iter().any(|layer| &layer.name == *name))
+                {
+                    return Err(PngToWasm4SrcError::UnknownLayer(name.clone()));
+                }
+                let is_named = |index: usize| names.contains(&self.layers[index].name);
+                Ok((0..self.layers.len())
+                    .map(|index| {
+                        is_named(index)
+                            || self.layers[index]
+                                .ancestors
+                                .iter()
+                                .any(|ancestor| is_named(*ancestor))
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    /// Flatten the selected layers of a frame
+    ///
+    /// Transparent pixels are `None`.
+    fn flatten_frame(
+        &self,
+        frame: usize,
+        selected: &[bool],
+    ) -> Result<Vec<Option<u8>>, PngToWasm4SrcError> {
+        let mut cels: Vec<&Cel> = self
+            .frames
+            .get(frame)
+            .ok_or(PngToWasm4SrcError::InvalidAseprite("frame out of range"))?
+            .cels
+            .iter()
+            .filter(|cel| selected.get(cel.layer).copied().unwrap_or(false))
+            .filter(|cel| !self.layers[cel.layer].group)
+            .collect();
+        cels.sort_by_key(|cel| (cel.layer as i32 + cel.z_index, cel.z_index));
+
+        let mut indices = vec![None; (self.width * self.height) as usize];
+        for cel in cels {
+            let background = self.layers[cel.layer].flags & LAYER_BACKGROUND != 0;
+            let (cel, image) = self.resolve_cel(cel)?;
+            for cel_y in 0..image.height {
+                for cel_x in 0..image.width {
+                    let x = cel.x + cel_x as i32;
+                    let y = cel.y + cel_y as i32;
+                    if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+                        continue;
+                    }
+                    let index = image.pixels[(cel_y * image.width + cel_x) as usize];
+                    if background || index != self.transparent_index {
+                        indices[(y as u32 * self.width + x as u32) as usize] = Some(index);
+                    }
+                }
+            }
+        }
+        Ok(indices)
+    }
+
+    /// Return the cel holding the image of a cel, following links to other
+    /// frames
+    ///
+    /// Linked cels share both the image and the position of the linked cel.
+    fn resolve_cel<'a>(
+        &'a self,
+        cel: &'a Cel,
+    ) -> Result<(&'a Cel, &'a CelImage), PngToWasm4SrcError> {
+        let cel = match cel.content {
+            CelContent::Linked(frame) => self
+                .frames
+                .get(frame)
+                .and_then(|frame| frame.cels.iter().find(|linked| linked.layer == cel.layer))
+                .ok_or(PngToWasm4SrcError::InvalidAseprite("invalid linked cel"))?,
+            CelContent::Image(_) => cel,
+        };
+        match &cel.content {
+            CelContent::Image(image) => Ok((cel, image)),
+            CelContent::Linked(_) => Err(PngToWasm4SrcError::InvalidAseprite("invalid linked cel")),
+        }
+    }
+}
+
+fn parse_cel(chunk: &mut Reader) -> Result<Cel, PngToWasm4SrcError> {
+    let layer = chunk.u16()? as usize;
+    let x = chunk.i16()? as i32;
+    let y = chunk.i16()? as i32;
+    let _opacity = chunk.u8()?;
+    let cel_type = chunk.u16()?;
+    let z_index = chunk.i16()? as i32;
+    chunk.skip(5)?;
+
+    let content = match cel_type {
+        0 | 2 => {
+            let width = chunk.u16()? as u32;
+            let height = chunk.u16()? as u32;
+            let data = chunk.rest();
+            let pixels = if cel_type == 0 {
+                data.to_vec()
+            } else {
+                decompress_to_vec_zlib(data)
+                    .map_err(|_| PngToWasm4SrcError::InvalidAseprite("invalid compressed cel"))?
+            };
+            if pixels.len() < (width * height) as usize {
+                return Err(PngToWasm4SrcError::InvalidAseprite("cel is too short"));
+            }
+            CelContent::Image(CelImage {
+                width,
+                height,
+                pixels,
+            })
+        }
+        1 => CelContent::Linked(chunk.u16()? as usize),
+        _ => return Err(PngToWasm4SrcError::UnsupportedAsepriteTilemap),
+    };
+
+    Ok(Cel {
+        layer,
+        x,
+        y,
+        z_index,
+        content,
+    })
+}
+
+fn parse_tags(chunk: &mut Reader) -> Result<Vec<Tag>, PngToWasm4SrcError> {
+    let tag_count = chunk.u16()?;
+    chunk.skip(8)?;
+    (0..tag_count)
+        .map(|_| {
+            let from = chunk.u16()? as usize;
+            let to = chunk.u16()? as usize;
+            let direction = match chunk.u8()? {
+                1 => TagDirection::Reverse,
+                2 => TagDirection::PingPong,
+                3 => TagDirection::PingPongReverse,
+                _ => TagDirection::Forward,
+            };
+            let repeat = chunk.u16()?;
+            chunk.skip(10)?;
+            let name = chunk.string()?;
+            Ok(Tag {
+                name,
+                from,
+                to,
+                direction,
+                repeat,
+            })
+        })
+        .collect()
+}
+
+/// A reader of little-endian values from a byte slice
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], PngToWasm4SrcError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + length)
+            .ok_or(PngToWasm4SrcError::InvalidAseprite(
+                "unexpected end of file",
+            ))?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let bytes = self.bytes.get(self.position..).unwrap_or_default();
+        self.position = self.bytes.len();
+        bytes
+    }
+
+    fn skip(&mut self, length: usize) -> Result<(), PngToWasm4SrcError> {
+        self.bytes(length).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, PngToWasm4SrcError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, PngToWasm4SrcError> {
+        let bytes = self.bytes(2)?.try_into().expect("Slice must have length 2");
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn i16(&mut self) -> Result<i16, PngToWasm4SrcError> {
+        let bytes = self.bytes(2)?.try_into().expect("Slice must have length 2");
+        Ok(i16::from_le_bytes(bytes))
+    }
+
+    fn u32(&mut self) -> Result<u32, PngToWasm4SrcError> {
+        let bytes = self.bytes(4)?.try_into().expect("Slice must have length 4");
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn string(&mut self) -> Result<String, PngToWasm4SrcError> {
+        let length = self.u16()? as usize;
+        let bytes = self.bytes(length)?;
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
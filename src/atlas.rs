@@ -0,0 +1,153 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::collections::BTreeMap;
+
+use crate::rust::PackedSprite;
+use crate::sprite::IndexedImage;
+use crate::{Flags, RustAtlas, RustVariables};
+
+/// Width of atlases is a multiple of this number of pixels
+///
+/// Rows of the atlas start at byte boundaries for both bit depths.
+const ATLAS_WIDTH_ALIGNMENT: u32 = 8;
+
+/// Pack sprites in one atlas for each bit depth
+pub(crate) fn pack_atlases<S>(sprites: S) -> Vec<RustAtlas>
+where
+    S: IntoIterator<Item = RustVariables>,
+{
+    let mut groups: BTreeMap<Flags, Vec<RustVariables>> = BTreeMap::new();
+    for sprite in sprites {
+        groups.entry(sprite.flags()).or_default().push(sprite);
+    }
+
+    groups
+        .into_iter()
+        .map(|(flags, sprites)| pack_atlas(flags, sprites))
+        .collect()
+}
+
+fn pack_atlas(flags: Flags, mut sprites: Vec<RustVariables>) -> RustAtlas {
+    sprites.sort_by(|first, second| {
+        second
+            .height()
+            .cmp(&first.height())
+            .then(second.width().cmp(&first.width()))
+            .then(first.name().cmp(second.name()))
+    });
+
+    let sizes: Vec<(u32, u32)> = sprites
+        .iter()
+        .map(|sprite| (sprite.width(), sprite.height()))
+        .collect();
+    let (width, height, positions) = find_smallest_packing(&sizes);
+
+    let mut atlas = IndexedImage::blank(width, height, flags);
+    for (sprite, (x, y)) in sprites.iter().zip(&positions) {
+        atlas.paste(&IndexedImage::from_rust_variables(sprite), *x, *y);
+    }
+
+    let name = match flags {
+        Flags::OneBitPerPixel => "atlas_1bpp",
+        Flags::TwoBitsPerPixel => "atlas_2bpp",
+    };
+
+    let packed_sprites = sprites
+        .into_iter()
+        .zip(positions)
+        .map(|(sprite, (x, y))| PackedSprite::new(sprite, x, y))
+        .collect();
+
+    RustAtlas::new(atlas.to_rust_variables(name), packed_sprites)
+}
+
+/// Find the packing with the smallest area among all aligned atlas widths
+///
+/// Sprites are expected to be sorted by decreasing height.
+fn find_smallest_packing(sizes: &[(u32, u32)]) -> (u32, u32, Vec<(u32, u32)>) {
+    let max_width = sizes.iter().map(|(width, _)| *width).max().unwrap_or(0);
+    let total_width: u32 = sizes.iter().map(|(width, _)| *width).sum();
+
+    let min_width = align_width(max_width.max(1));
+    let max_width = align_width(total_width.max(1));
+
+    (min_width..=max_width)
+        .step_by(ATLAS_WIDTH_ALIGNMENT as usize)
+        .map(|width| {
+            let (height, positions) = pack_shelves(sizes, width);
+            (width, height, positions)
+        })
+        .min_by_key(|(width, height, _)| (width * height, *width))
+        .expect("There is always at least one candidate width")
+}
+
+/// Pack rectangles in horizontal shelves of a given width
+///
+/// Each rectangle is placed to the right of the previous one, or at the
+/// beginning of a new shelf if it does not fit.
+fn pack_shelves(sizes: &[(u32, u32)], width: u32) -> (u32, Vec<(u32, u32)>) {
+    let mut positions = Vec::with_capacity(sizes.len());
+    let mut shelf_x = 0;
+    let mut shelf_y = 0;
+    let mut shelf_height = 0;
+
+    for (sprite_width, sprite_height) in sizes {
+        if shelf_x + sprite_width > width {
+            shelf_x = 0;
+            shelf_y += shelf_height;
+            shelf_height = 0;
+        }
+        positions.push((shelf_x, shelf_y));
+        shelf_x += sprite_width;
+        shelf_height = shelf_height.max(*sprite_height);
+    }
+
+    (shelf_y + shelf_height, positions)
+}
+
+fn align_width(width: u32) -> u32 {
+    (width + ATLAS_WIDTH_ALIGNMENT - 1) / ATLAS_WIDTH_ALIGNMENT * ATLAS_WIDTH_ALIGNMENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shelves() {
+        let sizes = [(8, 8), (8, 4), (16, 4), (4, 2)];
+
+        let (height, positions) = pack_shelves(&sizes, 16);
+
+        assert_eq!(height, 14);
+        assert_eq!(positions, vec![(0, 0), (8, 0), (0, 8), (0, 12)]);
+    }
+
+    #[test]
+    fn smallest_packing() {
+        let sizes = [(8, 8), (8, 8), (8, 8), (8, 8)];
+
+        let (width, height, positions) = find_smallest_packing(&sizes);
+
+        assert_eq!((width, height), (8, 32));
+        assert_eq!(positions, vec![(0, 0), (0, 8), (0, 16), (0, 24)]);
+    }
+
+    #[test]
+    fn pack_sprites_by_bit_depth() {
+        let sprites = vec![
+            RustVariables::new("one", 8, 1, Flags::OneBitPerPixel, vec![0b1000_0001]),
+            RustVariables::new("two", 4, 1, Flags::TwoBitsPerPixel, vec![0b1110_0100]),
+            RustVariables::new("three", 8, 1, Flags::OneBitPerPixel, vec![0b0101_0101]),
+        ];
+
+        let atlases = pack_atlases(sprites);
+
+        assert_eq!(atlases.len(), 2);
+        assert_eq!(atlases[0].atlas().data(), [0b1000_0001, 0b0101_0101]);
+        assert_eq!(atlases[1].atlas().data(), [0b1110_0100, 0]);
+    }
+}
@@ -0,0 +1,629 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::fmt;
+use std::path::Path;
+
+use crate::check::{check_file, check_sprite_file};
+use crate::{
+    ByteFormat, Flags, ParsedModule, PngToWasm4SrcError, RustAtlas, RustFrames, RustPalette,
+    RustVariables,
+};
+
+/// An output backend generating source code in a programming language
+///
+/// Backends only decide how identifiers are named and how each constant is
+/// written, while the constants themselves are the same for all languages.
+/// The built-in backends are the [languages](crate::Language) supported by
+/// WASM-4, but any type implementing this trait can be passed to the
+/// `to_source()` functions, such as [`RustVariables::to_source()`].
+///
+/// ```
+/// # use std::fmt;
+/// # use png2wasm4src::{Backend, Flags, RustVariables, Value};
+/// struct Python;
+///
+/// impl Backend for Python {
+///     fn identifier(&self, name: &str, words: &[&str]) -> String {
+///         let mut identifier = name.to_string();
+///         for word in words {
+///             identifier.push('_');
+///             identifier.push_str(word);
+///         }
+///         identifier
+///     }
+///
+///     fn write_constant(
+///         &self,
+///         f: &mut fmt::Formatter,
+///         _depth: usize,
+///         identifier: &str,
+///         value: &Value,
+///     ) -> fmt::Result {
+///         match value {
+///             Value::Integer(value) => writeln!(f, "{} = {}", identifier, value),
+///             Value::Flags(flags) => writeln!(f, "{} = {}", identifier, flags.value()),
+///             Value::Bytes(data) => writeln!(f, "{} = bytes({:?})", identifier, data),
+///             _ => Ok(()),
+///         }
+///     }
+/// }
+///
+/// let variables = RustVariables::new("name", 4, 2, Flags::OneBitPerPixel, vec![0x0f]);
+///
+/// assert_eq!(
+///     format!("{}", variables.to_source(Python)),
+///     "name_width = 4
+/// name_height = 2
+/// name_flags = 0
+/// name = bytes([15])\n",
+/// );
+/// ```
+pub trait Backend {
+    /// Return the identifier of a constant
+    ///
+    /// The identifier is built from the sprite name, possibly prefixed by the
+    /// path of its module, and from a sequence of lowercase words, such as
+    /// `["src", "x"]`.
+    /// Words are empty for the sprite data.
+    fn identifier(&self, name: &str, words: &[&str]) -> String;
+
+    /// Write a constant, including its terminating newline
+    ///
+    /// The depth is the number of modules the constant is nested in, and it
+    /// is always zero for backends that do not [nest modules](Backend::nests_modules).
+    /// The alternate form of the formatter requests binary literals for bytes.
+    fn write_constant(
+        &self,
+        f: &mut fmt::Formatter,
+        depth: usize,
+        identifier: &str,
+        value: &Value,
+    ) -> fmt::Result;
+
+    /// Write the constants of a sprite, without its regions
+    ///
+    /// The name is the sprite name, possibly prefixed by the path of its
+    /// module.
+    /// By default, the width, the height, the flags, the transparent colour
+    /// index and the data are written as separate constants, the same as
+    /// `w4 png2src`, but a backend can group them in a single constant.
+    fn write_sprite(
+        &self,
+        f: &mut fmt::Formatter,
+        depth: usize,
+        name: &str,
+        variables: &RustVariables,
+    ) -> fmt::Result {
+        write_header(
+            self,
+            f,
+            depth,
+            name,
+            variables.width(),
+            variables.height(),
+            variables.flags(),
+            variables.transparent_index(),
+        )?;
+        let identifier = self.identifier(name, &[]);
+        self.write_constant(f, depth, &identifier, &Value::Bytes(variables.data()))
+    }
+
+    /// Return whether modules are nested in the generated code
+    ///
+    /// When modules are not nested, identifiers are prefixed with the path of
+    /// their module, such as `sprites_characters_player`.
+    fn nests_modules(&self) -> bool {
+        false
+    }
+
+    /// Write the beginning of a nested module
+    fn write_module_start(
+        &self,
+        _f: &mut fmt::Formatter,
+        _depth: usize,
+        _name: &str,
+    ) -> fmt::Result {
+        Ok(())
+    }
+
+    /// Write the end of a nested module
+    fn write_module_end(&self, _f: &mut fmt::Formatter, _depth: usize, _name: &str) -> fmt::Result {
+        Ok(())
+    }
+
+    /// Write code before all constants, such as include guards
+    fn write_prologue(&self, _f: &mut fmt::Formatter) -> fmt::Result {
+        Ok(())
+    }
+
+    /// Write code after all constants
+    fn write_epilogue(&self, _f: &mut fmt::Formatter) -> fmt::Result {
+        Ok(())
+    }
+}
+
+impl<B> Backend for &B
+where
+    B: Backend + ?Sized,
+{
+    fn identifier(&self, name: &str, words: &[&str]) -> String {
+        (**self).identifier(name, words)
+    }
+
+    fn write_constant(
+        &self,
+        f: &mut fmt::Formatter,
+        depth: usize,
+        identifier: &str,
+        value: &Value,
+    ) -> fmt::Result {
+        (**self).write_constant(f, depth, identifier, value)
+    }
+
+    fn write_sprite(
+        &self,
+        f: &mut fmt::Formatter,
+        depth: usize,
+        name: &str,
+        variables: &RustVariables,
+    ) -> fmt::Result {
+        (**self).write_sprite(f, depth, name, variables)
+    }
+
+    fn nests_modules(&self) -> bool {
+        (**self).nests_modules()
+    }
+
+    fn write_module_start(&self, f: &mut fmt::Formatter, depth: usize, name: &str) -> fmt::Result {
+        (**self).write_module_start(f, depth, name)
+    }
+
+    fn write_module_end(&self, f: &mut fmt::Formatter, depth: usize, name: &str) -> fmt::Result {
+        (**self).write_module_end(f, depth, name)
+    }
+
+    fn write_prologue(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).write_prologue(f)
+    }
+
+    fn write_epilogue(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).write_epilogue(f)
+    }
+}
+
+/// The value of a generated constant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value<'a> {
+    /// A size, a coordinate or a colour index
+    Integer(u32),
+
+    /// A frame index or a number of frames
+    Index(usize),
+
+    /// A boolean flag
+    Bool(bool),
+
+    /// The sprite flags
+    Flags(Flags),
+
+    /// The encoded data of a sprite
+    Bytes(&'a [u8]),
+
+    /// The encoded data of frames, all of the same size
+    Frames(&'a [Vec<u8>]),
+
+    /// A list of durations in ticks
+    Integers(&'a [u32]),
+
+    /// A list of frame indices
+    Indices(&'a [usize]),
+
+    /// A palette, as `0xRRGGBB` colours
+    Colors(&'a [u32; 4]),
+
+    /// The encoded data of an atlas, shared by all its packed sprites
+    ///
+    /// Backends should make sure the data is stored only once, such as with
+    /// a Rust `static`.
+    Atlas(&'a [u8]),
+}
+
+/// Source code generated by a backend
+///
+/// It is created by the `to_source()` functions, and the code is generated
+/// using the function [`std::string::ToString::to_string()`], or the macro
+/// [`format!`].
+#[derive(Debug)]
+pub struct SourceCode<'a, B, T> {
+    backend: B,
+    item: &'a T,
+}
+
+impl<'a, B, T> SourceCode<'a, B, T> {
+    pub(crate) fn new(backend: B, item: &'a T) -> Self {
+        Self { backend, item }
+    }
+}
+
+impl<B: Backend> SourceCode<'_, B, ParsedModule> {
+    /// Check that a file contains the code generated for the module
+    ///
+    /// This is useful for generated files committed to the repository
+    /// instead of being generated by a build script.
+    /// If the file content is different, or if the file does not exist,
+    /// [`PngToWasm4SrcError::OutdatedFile`] is returned, with a diff where
+    /// each group of changed lines is preceded by the sprite or module that
+    /// generated them, such as `sprite player in module sprites::characters`.
+    ///
+    /// ```no_run
+    /// # use png2wasm4src::{build_sprite_modules_tree, ByteFormat, Language};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let module = build_sprite_modules_tree("assets/sprites")?.parse()?;
+    /// module
+    ///     .to_source(Language::Zig)
+    ///     .check_file("src/sprites.zig", ByteFormat::Hexadecimal)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn check_file<P>(&self, path: P, format: ByteFormat) -> Result<(), PngToWasm4SrcError>
+    where
+        P: AsRef<Path>,
+    {
+        let code = format.apply(self);
+        check_file(path.as_ref(), &code, self.item, &self.backend)
+    }
+}
+
+impl<B: Backend> SourceCode<'_, B, RustVariables> {
+    /// Check that a file contains the code generated for the sprite
+    ///
+    /// Differences are reported as for a module, with changed lines preceded
+    /// by `sprite name`.
+    pub fn check_file<P>(&self, path: P, format: ByteFormat) -> Result<(), PngToWasm4SrcError>
+    where
+        P: AsRef<Path>,
+    {
+        let code = format.apply(self);
+        check_sprite_file(path.as_ref(), &code, self.item.name(), &self.backend)
+    }
+}
+
+impl<B: Backend> SourceCode<'_, B, RustFrames> {
+    /// Check that a file contains the code generated for the frames
+    ///
+    /// Differences are reported as for a module, with changed lines preceded
+    /// by `sprite name`.
+    pub fn check_file<P>(&self, path: P, format: ByteFormat) -> Result<(), PngToWasm4SrcError>
+    where
+        P: AsRef<Path>,
+    {
+        let code = format.apply(self);
+        check_sprite_file(path.as_ref(), &code, self.item.name(), &self.backend)
+    }
+}
+
+impl<B: Backend> fmt::Display for SourceCode<'_, B, RustVariables> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.backend.write_prologue(f)?;
+        write_variables(&self.backend, f, 0, "", self.item)?;
+        self.backend.write_epilogue(f)
+    }
+}
+
+impl<B: Backend> fmt::Display for SourceCode<'_, B, RustFrames> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.backend.write_prologue(f)?;
+        write_frames(&self.backend, f, 0, "", self.item)?;
+        self.backend.write_epilogue(f)
+    }
+}
+
+impl<B: Backend> fmt::Display for SourceCode<'_, B, RustAtlas> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.backend.write_prologue(f)?;
+        write_atlas(&self.backend, f, 0, "", self.item)?;
+        self.backend.write_epilogue(f)
+    }
+}
+
+impl<B: Backend> fmt::Display for SourceCode<'_, B, RustPalette> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.backend.write_prologue(f)?;
+        write_palette(&self.backend, f, 0, "", self.item)?;
+        self.backend.write_epilogue(f)
+    }
+}
+
+impl<B: Backend> fmt::Display for SourceCode<'_, B, ParsedModule> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.backend.write_prologue(f)?;
+        write_module(&self.backend, f, 0, "", self.item)?;
+        self.backend.write_epilogue(f)
+    }
+}
+
+fn write_variables<B: Backend>(
+    backend: &B,
+    f: &mut fmt::Formatter,
+    depth: usize,
+    prefix: &str,
+    variables: &RustVariables,
+) -> fmt::Result {
+    let name = qualified_name(prefix, variables.name());
+    backend.write_sprite(f, depth, &name, variables)?;
+    write_regions(backend, f, depth, &name, variables, 0, 0)
+}
+
+fn write_frames<B: Backend>(
+    backend: &B,
+    f: &mut fmt::Formatter,
+    depth: usize,
+    prefix: &str,
+    frames: &RustFrames,
+) -> fmt::Result {
+    let name = qualified_name(prefix, frames.name());
+    write_header(
+        backend,
+        f,
+        depth,
+        &name,
+        frames.width(),
+        frames.height(),
+        frames.flags(),
+        frames.transparent_index(),
+    )?;
+    for (index, frame_name) in frames.frame_names().iter().enumerate() {
+        let identifier = backend.identifier(&name, &["frame", frame_name]);
+        backend.write_constant(f, depth, &identifier, &Value::Index(index))?;
+    }
+    let identifier = backend.identifier(&name, &["frames"]);
+    backend.write_constant(f, depth, &identifier, &Value::Frames(frames.frames()))?;
+    if !frames.frame_durations().is_empty() {
+        let identifier = backend.identifier(&name, &["frame", "durations"]);
+        let value = Value::Integers(frames.frame_durations());
+        backend.write_constant(f, depth, &identifier, &value)?;
+    }
+    for animation in frames.animations() {
+        let constants: [(&[&str], Value); 5] = [
+            (&["frame", "count"], Value::Index(animation.frames().len())),
+            (&["frames"], Value::Indices(animation.frames())),
+            (&["durations"], Value::Integers(animation.durations())),
+            (&["loop"], Value::Bool(animation.looping())),
+            (&["ping", "pong"], Value::Bool(animation.ping_pong())),
+        ];
+        write_named_constants(backend, f, depth, &name, animation.name(), &constants)?;
+    }
+    Ok(())
+}
+
+fn write_atlas<B: Backend>(
+    backend: &B,
+    f: &mut fmt::Formatter,
+    depth: usize,
+    prefix: &str,
+    atlas: &RustAtlas,
+) -> fmt::Result {
+    let atlas_variables = atlas.atlas();
+    let atlas_name = qualified_name(prefix, atlas_variables.name());
+    write_header(
+        backend,
+        f,
+        depth,
+        &atlas_name,
+        atlas_variables.width(),
+        atlas_variables.height(),
+        atlas_variables.flags(),
+        atlas_variables.transparent_index(),
+    )?;
+    let identifier = backend.identifier(&atlas_name, &[]);
+    let value = Value::Atlas(atlas_variables.data());
+    backend.write_constant(f, depth, &identifier, &value)?;
+
+    // Packed sprites have no data constant of their own, so that calls to
+    // `blit` written for unpacked sprites fail to compile instead of drawing
+    // the wrong pixels
+    for sprite in atlas.sprites() {
+        let variables = sprite.variables();
+        let name = qualified_name(prefix, variables.name());
+        write_header(
+            backend,
+            f,
+            depth,
+            &name,
+            variables.width(),
+            variables.height(),
+            variables.flags(),
+            variables.transparent_index(),
+        )?;
+        let constants: [(&[&str], Value); 3] = [
+            (&["src", "x"], Value::Integer(sprite.x())),
+            (&["src", "y"], Value::Integer(sprite.y())),
+            (&["stride"], Value::Integer(atlas_variables.width())),
+        ];
+        write_constants(backend, f, depth, &name, &constants)?;
+        write_regions(backend, f, depth, &name, variables, sprite.x(), sprite.y())?;
+    }
+    Ok(())
+}
+
+fn write_palette<B: Backend>(
+    backend: &B,
+    f: &mut fmt::Formatter,
+    depth: usize,
+    prefix: &str,
+    palette: &RustPalette,
+) -> fmt::Result {
+    let name = qualified_name(prefix, palette.name());
+    let identifier = backend.identifier(&name, &["palette"]);
+    let colors = palette.colors();
+    backend.write_constant(f, depth, &identifier, &Value::Colors(&colors))
+}
+
+/// Write all items in a module and its submodules
+///
+/// Items are followed by an empty line, and their names are prefixed with
+/// the module path if the backend does not nest modules.
+fn write_module<B: Backend>(
+    backend: &B,
+    f: &mut fmt::Formatter,
+    depth: usize,
+    prefix: &str,
+    module: &ParsedModule,
+) -> fmt::Result {
+    let (depth, prefix) = if backend.nests_modules() {
+        backend.write_module_start(f, depth, module.name())?;
+        (depth + 1, String::default())
+    } else {
+        (depth, qualified_name(prefix, module.name()))
+    };
+
+    if let Some(palette) = module.palette() {
+        write_palette(backend, f, depth, &prefix, palette)?;
+        writeln!(f)?;
+    }
+    for variables in module.variables() {
+        write_variables(backend, f, depth, &prefix, variables)?;
+        writeln!(f)?;
+    }
+    for frames in module.frames() {
+        write_frames(backend, f, depth, &prefix, frames)?;
+        writeln!(f)?;
+    }
+    for atlas in module.atlases() {
+        write_atlas(backend, f, depth, &prefix, atlas)?;
+        writeln!(f)?;
+    }
+    for submodule in module.submodules() {
+        write_module(backend, f, depth, &prefix, submodule)?;
+    }
+
+    if backend.nests_modules() {
+        backend.write_module_end(f, depth - 1, module.name())?;
+    }
+    Ok(())
+}
+
+/// Write the constants for width, height, flags and transparent colour index
+#[allow(clippy::too_many_arguments)]
+fn write_header<B: Backend + ?Sized>(
+    backend: &B,
+    f: &mut fmt::Formatter,
+    depth: usize,
+    name: &str,
+    width: u32,
+    height: u32,
+    flags: Flags,
+    transparent_index: Option<u8>,
+) -> fmt::Result {
+    let mut constants: Vec<(&[&str], Value)> = vec![
+        (&["width"], Value::Integer(width)),
+        (&["height"], Value::Integer(height)),
+        (&["flags"], Value::Flags(flags)),
+    ];
+    if let Some(transparent_index) = transparent_index {
+        let value = Value::Integer(transparent_index.into());
+        constants.push((&["transparent", "index"], value));
+    }
+    write_constants(backend, f, depth, name, &constants)
+}
+
+/// Write the constants for atlas regions, shifted by an offset
+fn write_regions<B: Backend>(
+    backend: &B,
+    f: &mut fmt::Formatter,
+    depth: usize,
+    name: &str,
+    variables: &RustVariables,
+    x: u32,
+    y: u32,
+) -> fmt::Result {
+    for region in variables.regions() {
+        let constants: [(&[&str], Value); 4] = [
+            (&["src", "x"], Value::Integer(x + region.x())),
+            (&["src", "y"], Value::Integer(y + region.y())),
+            (&["width"], Value::Integer(region.width())),
+            (&["height"], Value::Integer(region.height())),
+        ];
+        write_named_constants(backend, f, depth, name, region.name(), &constants)?;
+    }
+    Ok(())
+}
+
+/// Write constants identified by the name and their words
+fn write_constants<B: Backend + ?Sized>(
+    backend: &B,
+    f: &mut fmt::Formatter,
+    depth: usize,
+    name: &str,
+    constants: &[(&[&str], Value)],
+) -> fmt::Result {
+    for (words, value) in constants {
+        let identifier = backend.identifier(name, words);
+        backend.write_constant(f, depth, &identifier, value)?;
+    }
+    Ok(())
+}
+
+/// Write constants of a named part of a sprite, such as a region or an
+/// animation, identified by the name, the part name and their words
+fn write_named_constants<B: Backend>(
+    backend: &B,
+    f: &mut fmt::Formatter,
+    depth: usize,
+    name: &str,
+    part_name: &str,
+    constants: &[(&[&str], Value)],
+) -> fmt::Result {
+    for (words, value) in constants {
+        let mut part_words = vec![part_name];
+        part_words.extend_from_slice(words);
+        let identifier = backend.identifier(name, &part_words);
+        backend.write_constant(f, depth, &identifier, value)?;
+    }
+    Ok(())
+}
+
+/// Return the name of an item prefixed by the path of its module
+pub(crate) fn qualified_name(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else if name.is_empty() {
+        prefix.to_string()
+    } else {
+        format!("{}_{}", prefix, name)
+    }
+}
+
+/// Write a byte literal, in binary format for the alternate form
+///
+/// All languages supported by WASM-4 accept the same syntax for hexadecimal
+/// literals, and the same syntax for binary literals except C, where they
+/// are only standard since C23.
+pub(crate) fn write_byte(f: &mut fmt::Formatter, byte: u8) -> fmt::Result {
+    if f.alternate() {
+        write!(f, "{:#010b}", byte)
+    } else {
+        write!(f, "{:#04x}", byte)
+    }
+}
+
+/// Write a list of items separated by commas
+pub(crate) fn write_separated<T, F>(
+    f: &mut fmt::Formatter,
+    items: &[T],
+    mut write: F,
+) -> fmt::Result
+where
+    F: FnMut(&mut fmt::Formatter, &T) -> fmt::Result,
+{
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write(f, item)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,322 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::env::args;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use png2wasm4src::{
+    parse_sprite_file, write_if_changed, Backend, Builder, ByteFormat, ConversionOptions, Language,
+    Naming, ParsedModule, ParsedSprite, PngToWasm4SrcError, RustBackend, Visibility,
+};
+
+const USAGE: &str = "Convert sprites to source code for WASM-4
+
+Usage: png2wasm4src [OPTIONS] <PATH>
+
+Arguments:
+  <PATH>  A sprite file, or a directory of sprites converted to modules
+
+Options:
+  -o, --output <FILE>          Write the code to a file instead of standard output
+  -l, --language <LANGUAGE>    Output language [default: rust]
+                               [values: assemblyscript, c, d, go, nim, odin, rust, zig]
+  -f, --format <FORMAT>        Format of bytes [default: hexadecimal]
+                               [values: hexadecimal, binary (not for C)]
+  -n, --naming <NAMING>        Naming convention of Rust constants [default: upper-snake-case]
+                               [values: upper-snake-case, snake-case, camel-case]
+  -v, --visibility <VISIBILITY>
+                               Visibility of Rust constants and modules [default: inherited]
+                               [values: inherited, public, crate]
+  -e, --extensions <LIST>      Comma-separated extensions of sprite files in directories
+                               [default: png,gif,ase,aseprite]
+  -q, --quantize               Accept non-indexed images with up to four colours
+      --check                  Check that the output file is up to date instead of writing it
+  -h, --help                   Print help
+  -V, --version                Print version";
+
+/// The parsed command-line arguments
+#[derive(Debug)]
+struct Arguments {
+    path: PathBuf,
+    output: Option<PathBuf>,
+    language: Language,
+    format: ByteFormat,
+    naming: Option<Naming>,
+    visibility: Option<Visibility>,
+    extensions: Option<Vec<String>>,
+    quantize: bool,
+    check: bool,
+}
+
+/// The action requested on the command line
+enum Command {
+    Help,
+    Version,
+    Convert(Arguments),
+}
+
+/// An invalid command line
+#[derive(Debug)]
+struct UsageError(String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\n\nFor more information, try '--help'.", self.0)
+    }
+}
+
+impl Error for UsageError {}
+
+fn main() {
+    match run() {
+        Ok(code) => exit(code),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            let mut source = error.source();
+            while let Some(error) = source {
+                eprintln!("  caused by: {}", error);
+                source = error.source();
+            }
+            exit(2);
+        }
+    }
+}
+
+fn run() -> Result<i32, Box<dyn Error>> {
+    let arguments = match parse_arguments(args().skip(1))? {
+        Command::Help => {
+            println!("{}", USAGE);
+            return Ok(0);
+        }
+        Command::Version => {
+            println!("png2wasm4src {}", env!("CARGO_PKG_VERSION"));
+            return Ok(0);
+        }
+        Command::Convert(arguments) => arguments,
+    };
+
+    if !arguments.path.exists() {
+        let message = format!("{} does not exist", arguments.path.display());
+        return Err(message.into());
+    }
+
+    match (&arguments.output, arguments.check) {
+        (Some(output), true) => match check(&arguments, output) {
+            Ok(()) => {
+                eprintln!("{} is up to date", output.display());
+                Ok(0)
+            }
+            Err(error @ PngToWasm4SrcError::OutdatedFile { .. }) => {
+                eprintln!("{}", error);
+                eprintln!("Run without --check to regenerate it");
+                Ok(1)
+            }
+            Err(error) => Err(error.into()),
+        },
+        (Some(output), false) => {
+            write_if_changed(output, &generate(&arguments)?)?;
+            Ok(0)
+        }
+        (None, _) => {
+            print!("{}", generate(&arguments)?);
+            Ok(0)
+        }
+    }
+}
+
+/// Generate the code for a sprite file or a directory
+fn generate(arguments: &Arguments) -> Result<String, PngToWasm4SrcError> {
+    let rust_backend = rust_backend(arguments);
+    let backend = backend(&arguments.language, &rust_backend);
+    let format = arguments.format;
+
+    if arguments.path.is_dir() {
+        let module = parse_directory(arguments)?;
+        Ok(format.apply(module.to_source(backend)))
+    } else {
+        let code = match parse_sprite_file(&arguments.path, &options(arguments))? {
+            ParsedSprite::Variables(variables) => format.apply(variables.to_source(backend)),
+            ParsedSprite::Frames(frames) => format.apply(frames.to_source(backend)),
+        };
+        Ok(code)
+    }
+}
+
+/// Check that the output file contains the code for a sprite file or a
+/// directory
+///
+/// Differences are reported with the sprite or module that drifted.
+fn check(arguments: &Arguments, output: &Path) -> Result<(), PngToWasm4SrcError> {
+    let rust_backend = rust_backend(arguments);
+    let backend = backend(&arguments.language, &rust_backend);
+    let format = arguments.format;
+
+    if arguments.path.is_dir() {
+        let module = parse_directory(arguments)?;
+        module.to_source(backend).check_file(output, format)
+    } else {
+        match parse_sprite_file(&arguments.path, &options(arguments))? {
+            ParsedSprite::Variables(variables) => {
+                variables.to_source(backend).check_file(output, format)
+            }
+            ParsedSprite::Frames(frames) => frames.to_source(backend).check_file(output, format),
+        }
+    }
+}
+
+fn parse_directory(arguments: &Arguments) -> Result<ParsedModule, PngToWasm4SrcError> {
+    let mut builder = Builder::new(&arguments.path).with_options(options(arguments));
+    if let Some(extensions) = &arguments.extensions {
+        builder = builder.with_extensions(extensions);
+    }
+    builder.parse()
+}
+
+fn options(arguments: &Arguments) -> ConversionOptions {
+    ConversionOptions::default().with_quantization(arguments.quantize)
+}
+
+fn rust_backend(arguments: &Arguments) -> RustBackend {
+    RustBackend::default()
+        .with_naming(arguments.naming.unwrap_or_default())
+        .with_visibility(arguments.visibility.unwrap_or_default())
+}
+
+fn backend<'a>(language: &'a Language, rust_backend: &'a RustBackend) -> &'a dyn Backend {
+    match language {
+        Language::Rust => rust_backend,
+        language => language,
+    }
+}
+
+fn parse_arguments<I>(args: I) -> Result<Command, UsageError>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut path = None;
+    let mut output = None;
+    let mut language = Language::Rust;
+    let mut format = ByteFormat::default();
+    let mut naming = None;
+    let mut visibility = None;
+    let mut extensions = None;
+    let mut quantize = false;
+    let mut check = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // Options can be given either as `--option value` or `--option=value`
+        let (option, inline_value) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => {
+                (option.to_string(), Some(value.to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| UsageError(format!("option '{}' requires a value", option)))
+        };
+
+        match option.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "-l" | "--language" => language = parse_language(&value()?)?,
+            "-f" | "--format" => format = parse_format(&value()?)?,
+            "-n" | "--naming" => naming = Some(parse_naming(&value()?)?),
+            "-v" | "--visibility" => visibility = Some(parse_visibility(&value()?)?),
+            "-e" | "--extensions" => {
+                let list = value()?;
+                extensions = Some(list.split(',').map(|e| e.trim().to_string()).collect());
+            }
+            "-q" | "--quantize" => quantize = true,
+            "--check" => check = true,
+            _ if option.starts_with('-') && option != "-" => {
+                return Err(UsageError(format!("unexpected option '{}'", option)));
+            }
+            _ if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => return Err(UsageError(format!("unexpected argument '{}'", arg))),
+        }
+    }
+
+    let path = path.ok_or_else(|| UsageError("missing argument <PATH>".to_string()))?;
+    if check && output.is_none() {
+        return Err(UsageError(
+            "option '--check' requires '--output'".to_string(),
+        ));
+    }
+    if language == Language::C && format == ByteFormat::Binary {
+        return Err(UsageError(
+            "option '--format binary' does not apply to C, where binary literals require C23"
+                .to_string(),
+        ));
+    }
+    if language != Language::Rust && (naming.is_some() || visibility.is_some()) {
+        return Err(UsageError(
+            "options '--naming' and '--visibility' only apply to Rust".to_string(),
+        ));
+    }
+
+    Ok(Command::Convert(Arguments {
+        path,
+        output,
+        language,
+        format,
+        naming,
+        visibility,
+        extensions,
+        quantize,
+        check,
+    }))
+}
+
+fn parse_language(value: &str) -> Result<Language, UsageError> {
+    match value {
+        "assemblyscript" => Ok(Language::AssemblyScript),
+        "c" => Ok(Language::C),
+        "d" => Ok(Language::D),
+        "go" => Ok(Language::Go),
+        "nim" => Ok(Language::Nim),
+        "odin" => Ok(Language::Odin),
+        "rust" => Ok(Language::Rust),
+        "zig" => Ok(Language::Zig),
+        _ => Err(invalid_value("--language", value)),
+    }
+}
+
+fn parse_format(value: &str) -> Result<ByteFormat, UsageError> {
+    match value {
+        "hexadecimal" | "hex" => Ok(ByteFormat::Hexadecimal),
+        "binary" | "bin" => Ok(ByteFormat::Binary),
+        _ => Err(invalid_value("--format", value)),
+    }
+}
+
+fn parse_naming(value: &str) -> Result<Naming, UsageError> {
+    match value {
+        "upper-snake-case" => Ok(Naming::UpperSnakeCase),
+        "snake-case" => Ok(Naming::SnakeCase),
+        "camel-case" => Ok(Naming::CamelCase),
+        _ => Err(invalid_value("--naming", value)),
+    }
+}
+
+fn parse_visibility(value: &str) -> Result<Visibility, UsageError> {
+    match value {
+        "inherited" => Ok(Visibility::Inherited),
+        "public" => Ok(Visibility::Public),
+        "crate" => Ok(Visibility::Crate),
+        _ => Err(invalid_value("--visibility", value)),
+    }
+}
+
+fn invalid_value(option: &str, value: &str) -> UsageError {
+    UsageError(format!("invalid value '{}' for option '{}'", value, option))
+}
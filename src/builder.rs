@@ -0,0 +1,263 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::env::var_os;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::lookup::{build_sprite_modules_tree_with_extensions, SPRITE_EXTENSIONS};
+use crate::{
+    write_if_changed, ConversionOptions, Module, Naming, ParsedModule, PngToWasm4SrcError,
+    RustBackend, Visibility,
+};
+
+#[cfg(doc)]
+use crate::build_sprite_modules_tree;
+
+/// A builder for the whole conversion pipeline, from a directory of sprites
+/// to Rust source code
+///
+/// It threads the same configuration through all steps: discovery of sprite
+/// files, their conversion, and the generation of Rust code.
+/// The default configuration generates the same code as
+/// [`build_sprite_modules_tree()`], [`Module::parse()`] and the [`Display`]
+/// implementation of [`ParsedModule`].
+///
+/// ```no_run
+/// # use png2wasm4src::{Builder, ByteFormat, ConversionOptions, Visibility};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// Builder::new("assets/sprites")
+///     .with_extensions(vec!["png"])
+///     .with_options(ConversionOptions::default().with_quantization(true))
+///     .with_visibility(Visibility::Crate)
+///     .with_format(ByteFormat::Binary)
+///     .write_to("src/sprites.rs")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Display`]: std::fmt::Display
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Builder {
+    directory: PathBuf,
+    extensions: Vec<String>,
+    options: ConversionOptions,
+    backend: RustBackend,
+    format: ByteFormat,
+}
+
+impl Builder {
+    /// Create a builder for the sprites in a directory
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            extensions: SPRITE_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+            options: ConversionOptions::default(),
+            backend: RustBackend::default(),
+            format: ByteFormat::default(),
+        }
+    }
+
+    /// Set the extensions of the sprite files to include
+    ///
+    /// Extensions are compared ignoring their case, and only supported
+    /// formats are converted: `png`, `gif`, `ase` and `aseprite`.
+    /// By default, all of them are included.
+    pub fn with_extensions<E, S>(mut self, extensions: E) -> Self
+    where
+        E: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.extensions = extensions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the options for the conversion of all sprites
+    pub fn with_options(mut self, options: ConversionOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the visibility of the generated constants and modules
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.backend = self.backend.with_visibility(visibility);
+        self
+    }
+
+    /// Set the naming convention of the generated constants
+    pub fn with_naming(mut self, naming: Naming) -> Self {
+        self.backend = self.backend.with_naming(naming);
+        self
+    }
+
+    /// Set the format of bytes in the generated code
+    pub fn with_format(mut self, format: ByteFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Return the directory containing the sprites
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Return the extensions of the sprite files to include
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    /// Return the options for the conversion of all sprites
+    pub fn options(&self) -> &ConversionOptions {
+        &self.options
+    }
+
+    /// Return the backend generating the Rust code
+    pub fn backend(&self) -> RustBackend {
+        self.backend
+    }
+
+    /// Return the format of bytes in the generated code
+    pub fn format(&self) -> ByteFormat {
+        self.format
+    }
+
+    /// Build the sprite module tree from the directory
+    pub fn build_tree(&self) -> Result<Module, PngToWasm4SrcError> {
+        build_sprite_modules_tree_with_extensions(&self.directory, &self.extensions)
+    }
+
+    /// Build the sprite module tree and parse all its sprites
+    pub fn parse(&self) -> Result<ParsedModule, PngToWasm4SrcError> {
+        self.build_tree()?.parse_with_options(&self.options)
+    }
+
+    /// Build the sprite module tree, parse it, and generate its Rust code
+    pub fn generate(&self) -> Result<String, PngToWasm4SrcError> {
+        self.generate_module(self.build_tree()?)
+    }
+
+    /// Generate the Rust code and write it to a file
+    ///
+    /// The file is created if it does not exist, replaced if its content is
+    /// different, and left untouched otherwise.
+    /// Return whether the file was written.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> Result<bool, PngToWasm4SrcError> {
+        let code = self.generate()?;
+        write_if_changed(path, &code)
+    }
+
+    /// Generate the Rust code from a build script, and write it to a file in
+    /// the output directory
+    ///
+    /// Cargo instructions are printed to standard output, so that the build
+    /// script is run again whenever a sprite, a sidecar metadata file or a
+    /// directory changes, including when sprites are added or removed.
+    /// The file is written to `${OUT_DIR}/<file_name>`, replacing any previous
+    /// content, unless the content is unchanged, so that crates including it
+    /// are not recompiled needlessly.
+    /// Return whether the file was written.
+    ///
+    /// ```no_run
+    /// # use png2wasm4src::Builder;
+    /// // build.rs
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     if Builder::new("assets/sprites").write_to_out_dir("sprites.rs")? {
+    ///         println!("cargo:warning=Sprites were regenerated");
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// The file can then be included in the crate.
+    ///
+    /// ```text
+    /// include!(concat!(env!("OUT_DIR"), "/sprites.rs"));
+    /// ```
+    pub fn write_to_out_dir(
+        &self,
+        file_name: impl AsRef<Path>,
+    ) -> Result<bool, PngToWasm4SrcError> {
+        let out_dir = var_os("OUT_DIR").ok_or(PngToWasm4SrcError::MissingOutDir)?;
+        let path = PathBuf::from(out_dir).join(file_name);
+
+        let module = self.build_tree()?;
+        let mut cargo_instructions = String::default();
+        module.generate_cargo_build_instructions(&mut cargo_instructions)?;
+        print!("{}", cargo_instructions);
+
+        let code = self.generate_module(module)?;
+        write_if_changed(path, &code)
+    }
+
+    /// Parse a sprite module tree and generate its Rust code
+    fn generate_module(&self, module: Module) -> Result<String, PngToWasm4SrcError> {
+        let module = module.parse_with_options(&self.options)?;
+        Ok(self.format.apply(module.to_source(self.backend)))
+    }
+
+    /// Check that a committed file contains the generated Rust code
+    ///
+    /// Instead of writing the file, the code is generated again and compared
+    /// with its content, for instance in continuous integration to catch
+    /// sprites modified without regenerating the file.
+    /// If they differ, [`PngToWasm4SrcError::OutdatedFile`] is returned,
+    /// with a diff naming the sprite or module that drifted.
+    ///
+    /// ```no_run
+    /// # use png2wasm4src::Builder;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// if let Err(error) = Builder::new("assets/sprites").check("src/sprites.rs") {
+    ///     eprintln!("{}", error);
+    ///     std::process::exit(1);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn check(&self, path: impl AsRef<Path>) -> Result<(), PngToWasm4SrcError> {
+        let module = self.parse()?;
+        module.to_source(self.backend).check_file(path, self.format)
+    }
+}
+
+/// Format of bytes in the generated code
+///
+/// This is the same as formatting the generated code with `{}` or with the
+/// alternate form `{:#}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteFormat {
+    /// Hexadecimal literals, such as `0x0f`
+    Hexadecimal,
+
+    /// Binary literals, such as `0b00001111`, which show the pixels of
+    /// sprites with one bit per pixel
+    Binary,
+}
+
+impl Default for ByteFormat {
+    fn default() -> Self {
+        ByteFormat::Hexadecimal
+    }
+}
+
+impl ByteFormat {
+    /// Format generated code, using the alternate form for binary literals
+    ///
+    /// ```
+    /// # use png2wasm4src::{convert_png_to_rust_variables, ByteFormat, Language};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let bytes = std::fs::read("tests/two-colors.png")?;
+    /// let sprite = convert_png_to_rust_variables("two_colors", &bytes)?;
+    /// let code = ByteFormat::Binary.apply(sprite.to_source(Language::Rust));
+    /// assert!(code.contains("0b11110000"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn apply(self, code: impl fmt::Display) -> String {
+        match self {
+            ByteFormat::Hexadecimal => code.to_string(),
+            ByteFormat::Binary => format!("{:#}", code),
+        }
+    }
+}
@@ -0,0 +1,309 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::fmt;
+
+use crate::backend::write_separated;
+use crate::language::{camel_case, frame_size, write_bytes, write_colors, write_numbers};
+use crate::{Backend, SourceCode, Value};
+
+#[cfg(doc)]
+use crate::{ParsedModule, RustAtlas, RustFrames, RustPalette, RustVariables};
+
+/// C source code generated from Rust variables or from a module
+///
+/// It is created by the `to_c()` functions of [`RustVariables`],
+/// [`RustFrames`], [`RustAtlas`], [`RustPalette`] and [`ParsedModule`], and by
+/// the functions [`ParsedModule::to_c_header()`] and
+/// [`ParsedModule::to_c_source()`].
+///
+/// The code is generated using the function
+/// [`std::string::ToString::to_string()`], or the macro [`format!`].
+/// Sizes and flags are generated as `#define`s, and data as `const` arrays,
+/// the same as the WASM-4 command `w4 png2src --c path/to/image`.
+///
+/// ```
+/// # use png2wasm4src::{Flags, RustVariables};
+/// let variables = RustVariables::new(
+///     "name", 16, 24, Flags::OneBitPerPixel,
+///     vec![0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0xff],
+/// );
+///
+/// assert_eq!(
+///     format!("{}", variables.to_c()),
+///     "#define nameWidth 16
+/// #define nameHeight 24
+/// #define nameFlags BLIT_1BPP
+/// const uint8_t name[10] = { 0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0xff };\n",
+/// );
+/// ```
+///
+/// Unlike Rust constants, names keep their case, and other constants are
+/// named by appending a suffix in camel case, such as `nameSrcX` for the
+/// position of a packed sprite, or `nameHeartSrcX` for the position of the
+/// region `heart`.
+///
+/// Like the Rust code, the alternate form `{:#}` typesets data in binary
+/// format, which requires a C23 compiler or a compiler supporting binary
+/// literals as an extension.
+pub type CCode<'a, T> = SourceCode<'a, CBackend, T>;
+
+/// The output backend for C
+///
+/// By default, both declarations and definitions are generated in a single
+/// file, like [`Language::C`](crate::Language::C).
+/// A header and source file pair is generated using the backends
+/// [`CBackend::header()`] and [`CBackend::source()`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CBackend {
+    part: CPart,
+}
+
+/// A part of the C code
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CPart {
+    /// Both declarations and definitions, in a single file
+    Complete,
+
+    /// Declarations only, in a header file with an include guard
+    Header { guard: String },
+
+    /// Definitions only, in a source file including its header
+    Source { header: String },
+}
+
+impl Default for CPart {
+    fn default() -> Self {
+        CPart::Complete
+    }
+}
+
+impl CBackend {
+    /// Create a backend for a header file with an include guard
+    ///
+    /// The header contains all `#define`s, while arrays are only declared as
+    /// `extern`.
+    pub fn header(guard: impl Into<String>) -> Self {
+        Self {
+            part: CPart::Header {
+                guard: guard.into(),
+            },
+        }
+    }
+
+    /// Create a backend for a source file including a header
+    ///
+    /// The source file only contains the definitions of arrays.
+    pub fn source(header: impl Into<String>) -> Self {
+        Self {
+            part: CPart::Source {
+                header: header.into(),
+            },
+        }
+    }
+
+    fn declarations(&self) -> bool {
+        !matches!(self.part, CPart::Source { .. })
+    }
+
+    fn definitions(&self) -> bool {
+        !matches!(self.part, CPart::Header { .. })
+    }
+
+    /// Write a define, which is part of the declarations
+    fn write_define(
+        &self,
+        f: &mut fmt::Formatter,
+        identifier: &str,
+        value: impl fmt::Display,
+    ) -> fmt::Result {
+        if self.declarations() {
+            writeln!(f, "#define {} {}", identifier, value)?;
+        }
+        Ok(())
+    }
+
+    /// Write a constant array, declared as `extern` in headers
+    fn write_array<F>(
+        &self,
+        f: &mut fmt::Formatter,
+        kind: &str,
+        identifier: &str,
+        dimensions: &str,
+        write: F,
+    ) -> fmt::Result
+    where
+        F: FnOnce(&mut fmt::Formatter) -> fmt::Result,
+    {
+        if self.definitions() {
+            write!(f, "const {} {}{} = ", kind, identifier, dimensions)?;
+            write(f)?;
+            writeln!(f, ";")?;
+        } else {
+            writeln!(f, "extern const {} {}{};", kind, identifier, dimensions)?;
+        }
+        Ok(())
+    }
+}
+
+impl Backend for CBackend {
+    fn identifier(&self, name: &str, words: &[&str]) -> String {
+        camel_case(name, words)
+    }
+
+    fn write_constant(
+        &self,
+        f: &mut fmt::Formatter,
+        _depth: usize,
+        identifier: &str,
+        value: &Value,
+    ) -> fmt::Result {
+        match value {
+            Value::Integer(value) => self.write_define(f, identifier, value),
+            Value::Index(value) => self.write_define(f, identifier, value),
+            Value::Bool(value) => self.write_define(f, identifier, *value as u8),
+            Value::Flags(flags) => self.write_define(f, identifier, flags.human_readable_value()),
+            Value::Bytes(data) | Value::Atlas(data) => {
+                let dimensions = format!("[{}]", data.len());
+                self.write_array(f, "uint8_t", identifier, &dimensions, |f| {
+                    write_bytes(f, data, "{ ", " }")
+                })
+            }
+            Value::Frames(frames) => {
+                let dimensions = format!("[{}][{}]", frames.len(), frame_size(frames));
+                self.write_array(f, "uint8_t", identifier, &dimensions, |f| {
+                    write!(f, "{{ ")?;
+                    write_separated(f, frames, |f, frame| write_bytes(f, frame, "{ ", " }"))?;
+                    write!(f, " }}")
+                })
+            }
+            Value::Integers(values) => {
+                let dimensions = format!("[{}]", values.len());
+                self.write_array(f, "uint32_t", identifier, &dimensions, |f| {
+                    write_numbers(f, values, "{ ", " }")
+                })
+            }
+            Value::Indices(values) => {
+                let dimensions = format!("[{}]", values.len());
+                self.write_array(f, "uint32_t", identifier, &dimensions, |f| {
+                    write_numbers(f, values, "{ ", " }")
+                })
+            }
+            Value::Colors(colors) => self.write_array(f, "uint32_t", identifier, "[4]", |f| {
+                write!(f, "{{ ")?;
+                write_colors(f, colors)?;
+                write!(f, " }}")
+            }),
+        }
+    }
+
+    fn write_prologue(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.part {
+            CPart::Complete => Ok(()),
+            CPart::Header { guard } => {
+                writeln!(f, "#ifndef {}", guard)?;
+                writeln!(f, "#define {}", guard)?;
+                writeln!(f)?;
+                writeln!(f, "#include <stdint.h>")?;
+                writeln!(f)
+            }
+            CPart::Source { header } => {
+                writeln!(f, "#include \"{}\"", header)?;
+                writeln!(f)
+            }
+        }
+    }
+
+    fn write_epilogue(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let CPart::Header { guard } = &self.part {
+            writeln!(f, "#endif // {}", guard)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Animation, Flags, Region, RustFrames, RustPalette, RustVariables};
+
+    #[test]
+    fn variables_to_c() {
+        let variables = RustVariables::new(
+            "some-name",
+            8,
+            4,
+            Flags::TwoBitsPerPixel,
+            vec![0x01, 0x02, 0x04, 0x1f],
+        )
+        .with_transparent_index(Some(0))
+        .with_regions(vec![Region::new("top", 0, 0, 8, 2)]);
+        let c_code = variables.to_c().to_string();
+
+        let expected = "#define some_nameWidth 8
+#define some_nameHeight 4
+#define some_nameFlags BLIT_2BPP
+#define some_nameTransparentIndex 0
+const uint8_t some_name[4] = { 0x01, 0x02, 0x04, 0x1f };
+#define some_nameTopSrcX 0
+#define some_nameTopSrcY 0
+#define some_nameTopWidth 8
+#define some_nameTopHeight 2\n";
+
+        assert_eq!(c_code, expected);
+    }
+
+    #[test]
+    fn variables_to_c_alternate() {
+        let variables = RustVariables::new("name", 4, 2, Flags::OneBitPerPixel, vec![0x0f]);
+        let c_code = format!("{:#}", variables.to_c());
+
+        let expected = "#define nameWidth 4
+#define nameHeight 2
+#define nameFlags BLIT_1BPP
+const uint8_t name[1] = { 0b00001111 };\n";
+
+        assert_eq!(c_code, expected);
+    }
+
+    #[test]
+    fn frames_to_c() {
+        let frames = RustFrames::new(
+            "name",
+            4,
+            2,
+            Flags::OneBitPerPixel,
+            vec![vec![0x0f], vec![0xf0]],
+        )
+        .with_frame_names(vec!["open", "closed"])
+        .with_frame_durations(vec![30, 6])
+        .with_animations(vec![Animation::new("blink", vec![0, 1], vec![30, 6])]);
+        let c_code = frames.to_c().to_string();
+
+        let expected = "#define nameWidth 4
+#define nameHeight 2
+#define nameFlags BLIT_1BPP
+#define nameFrameOpen 0
+#define nameFrameClosed 1
+const uint8_t nameFrames[2][1] = { { 0x0f }, { 0xf0 } };
+const uint32_t nameFrameDurations[2] = { 30, 6 };
+#define nameBlinkFrameCount 2
+const uint32_t nameBlinkFrames[2] = { 0, 1 };
+const uint32_t nameBlinkDurations[2] = { 30, 6 };
+#define nameBlinkLoop 1
+#define nameBlinkPingPong 0\n";
+
+        assert_eq!(c_code, expected);
+    }
+
+    #[test]
+    fn palette_to_c() {
+        let palette = RustPalette::new("", [0xfff6d3, 0xf9a875, 0xeb6b6f, 0x7c3f58]);
+        let c_code = palette.to_c().to_string();
+
+        let expected = "const uint32_t palette[4] = { 0xfff6d3, 0xf9a875, 0xeb6b6f, 0x7c3f58 };\n";
+
+        assert_eq!(c_code, expected);
+    }
+}
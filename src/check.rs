@@ -0,0 +1,404 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::fs::read_to_string;
+use std::io::ErrorKind as IoErrorKind;
+use std::path::Path;
+
+use crate::backend::qualified_name;
+use crate::{Backend, ParsedModule, PngToWasm4SrcError};
+
+/// Maximal number of cells in the table of the longest common subsequence
+///
+/// Larger differences are reported as all old lines removed and all new
+/// lines added.
+const MAX_DIFF_CELLS: usize = 1 << 22;
+
+/// Compare the code generated for a module with the content of a file
+///
+/// Return [`PngToWasm4SrcError::OutdatedFile`] if they differ, with a diff
+/// where each group of changed lines is attributed to the sprite or module
+/// that generated it.
+pub(crate) fn check_file<B>(
+    path: &Path,
+    code: &str,
+    module: &ParsedModule,
+    backend: &B,
+) -> Result<(), PngToWasm4SrcError>
+where
+    B: Backend + ?Sized,
+{
+    let mut items = Vec::default();
+    collect_items(module, "", "", backend, &mut items);
+    check_code(path, code, &items, backend.nests_modules())
+}
+
+/// Compare the code generated for a single sprite with the content of a file
+///
+/// Lines are attributed to the sprite, as in [`check_file()`].
+pub(crate) fn check_sprite_file<B>(
+    path: &Path,
+    code: &str,
+    name: &str,
+    backend: &B,
+) -> Result<(), PngToWasm4SrcError>
+where
+    B: Backend + ?Sized,
+{
+    let items = [Item {
+        module: String::default(),
+        scope: String::default(),
+        identifier: backend.identifier(name, &[]),
+        description: format!("sprite {}", name),
+    }];
+    check_code(path, code, &items, backend.nests_modules())
+}
+
+fn check_code(
+    path: &Path,
+    code: &str,
+    items: &[Item],
+    nests_modules: bool,
+) -> Result<(), PngToWasm4SrcError> {
+    let existing = match read_to_string(path) {
+        Ok(existing) => existing,
+        Err(error) if error.kind() == IoErrorKind::NotFound => {
+            return Err(PngToWasm4SrcError::OutdatedFile {
+                path: path.to_path_buf(),
+                diff: "file does not exist".to_string(),
+            });
+        }
+        Err(error) => return Err(error.into()),
+    };
+    if existing == code {
+        return Ok(());
+    }
+    if let Some(difference) = line_ending_difference(&existing, code) {
+        return Err(PngToWasm4SrcError::OutdatedFile {
+            path: path.to_path_buf(),
+            diff: difference,
+        });
+    }
+
+    let old_lines: Vec<&str> = existing.lines().collect();
+    let new_lines: Vec<&str> = code.lines().collect();
+    let old_scopes = scopes(&old_lines, nests_modules);
+    let new_scopes = scopes(&new_lines, nests_modules);
+
+    let mut diff = String::default();
+    let mut previous_label = None;
+    for change in diff_lines(&old_lines, &new_lines) {
+        let (sign, line, scope) = match change {
+            Change::Removed(index) => ('-', old_lines[index], &old_scopes[index]),
+            Change::Added(index) => ('+', new_lines[index], &new_scopes[index]),
+        };
+        let label = label(line, scope, items);
+        if previous_label.as_ref() != Some(&label) {
+            writeln!(diff, "{}", label)?;
+            previous_label = Some(label);
+        }
+        writeln!(diff, "{}{}", sign, line)?;
+    }
+
+    Err(PngToWasm4SrcError::OutdatedFile {
+        path: path.to_path_buf(),
+        diff: diff.trim_end().to_string(),
+    })
+}
+
+/// Describe the differences between two texts with the same lines
+///
+/// Line endings and newlines at the end of the file are not visible in a diff
+/// of lines, so they are described instead.
+/// Return `None` if the texts have different lines.
+fn line_ending_difference(existing: &str, code: &str) -> Option<String> {
+    let existing_lines = existing.replace("\r\n", "\n");
+    let code_lines = code.replace("\r\n", "\n");
+    if existing_lines.trim_end_matches('\n') != code_lines.trim_end_matches('\n') {
+        return None;
+    }
+
+    let line_ending = |text: &str| {
+        if text.contains("\r\n") {
+            "CRLF (\\r\\n)"
+        } else {
+            "LF (\\n)"
+        }
+    };
+    let final_newlines = |text: &str| text.len() - text.trim_end_matches('\n').len();
+
+    let mut differences = Vec::default();
+    if line_ending(existing) != line_ending(code) {
+        differences.push(format!(
+            "line endings differ: file uses {}, generated code uses {}",
+            line_ending(existing),
+            line_ending(code),
+        ));
+    }
+    match final_newlines(&existing_lines).cmp(&final_newlines(&code_lines)) {
+        Ordering::Less => differences.push("final newline is missing".to_string()),
+        Ordering::Greater => differences.push("file has extra newlines at the end".to_string()),
+        Ordering::Equal => {}
+    }
+    Some(differences.join("\n"))
+}
+
+/// An item of a module, such as a sprite, to attribute lines to
+struct Item {
+    /// The full path of the module, such as `sprites::characters`, which is
+    /// empty for a single sprite
+    module: String,
+
+    /// The scope of its lines, which is empty if modules are not nested
+    scope: String,
+
+    /// The identifier of the item, which prefixes all its constants
+    identifier: String,
+
+    /// The description of the item, such as `sprite player`
+    description: String,
+}
+
+fn collect_items<B>(
+    module: &ParsedModule,
+    parent_path: &str,
+    prefix: &str,
+    backend: &B,
+    items: &mut Vec<Item>,
+) where
+    B: Backend + ?Sized,
+{
+    let path = if parent_path.is_empty() {
+        module.name().to_string()
+    } else {
+        format!("{}::{}", parent_path, module.name())
+    };
+    let (scope, prefix) = if backend.nests_modules() {
+        (path.clone(), String::default())
+    } else {
+        (String::default(), qualified_name(prefix, module.name()))
+    };
+
+    let mut add_item = |name: &str, words: &[&str], description: String| {
+        items.push(Item {
+            module: path.clone(),
+            scope: scope.clone(),
+            identifier: backend.identifier(&qualified_name(&prefix, name), words),
+            description,
+        })
+    };
+
+    if let Some(palette) = module.palette() {
+        add_item(palette.name(), &["palette"], "palette".to_string());
+    }
+    for variables in module.variables() {
+        add_item(
+            variables.name(),
+            &[],
+            format!("sprite {}", variables.name()),
+        );
+    }
+    for frames in module.frames() {
+        add_item(frames.name(), &[], format!("sprite {}", frames.name()));
+    }
+    for atlas in module.atlases() {
+        let name = atlas.atlas().name();
+        add_item(name, &[], format!("atlas {}", name));
+        for sprite in atlas.sprites() {
+            let name = sprite.variables().name();
+            add_item(name, &[], format!("sprite {}", name));
+        }
+    }
+
+    for submodule in module.submodules() {
+        collect_items(submodule, &path, &prefix, backend, items);
+    }
+}
+
+/// Return the module path enclosing each line
+///
+/// Only nested Rust modules, such as `pub mod name {`, are recognized.
+fn scopes(lines: &[&str], nests_modules: bool) -> Vec<String> {
+    let mut stack: Vec<&str> = Vec::default();
+    let mut scopes = Vec::with_capacity(lines.len());
+    for line in lines {
+        scopes.push(stack.join("::"));
+        if !nests_modules {
+            continue;
+        }
+        let line = line.trim();
+        if line == "}" {
+            stack.pop();
+        } else if line.ends_with('{') {
+            let mut words = line.split_whitespace();
+            if words.any(|word| word == "mod") {
+                if let Some(name) = words.next() {
+                    stack.push(name);
+                }
+            }
+        }
+    }
+    scopes
+}
+
+/// Return the description of the item or module that generated a line
+///
+/// The item is the one whose identifier is the longest prefix of the first
+/// matching word in the line.
+fn label(line: &str, scope: &str, items: &[Item]) -> String {
+    let words = line.split(|c: char| !c.is_ascii_alphanumeric() && c != '_');
+    for word in words.filter(|word| !word.is_empty()) {
+        let item = items
+            .iter()
+            .filter(|item| item.scope == scope && word.starts_with(&item.identifier))
+            .max_by_key(|item| item.identifier.len());
+        match item {
+            Some(item) if item.module.is_empty() => return item.description.clone(),
+            Some(item) => return format!("{} in module {}", item.description, item.module),
+            None => {}
+        }
+    }
+    if scope.is_empty() {
+        "top level".to_string()
+    } else {
+        format!("module {}", scope)
+    }
+}
+
+/// A line removed from the old text or added from the new text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Removed(usize),
+    Added(usize),
+}
+
+/// Compute the changes from old lines to new lines
+///
+/// Lines are matched using their longest common subsequence, after skipping
+/// their common prefix and suffix.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Change> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    let (n, m) = (old_middle.len(), new_middle.len());
+
+    let mut changes = Vec::default();
+    if (n + 1) * (m + 1) > MAX_DIFF_CELLS {
+        changes.extend((prefix..prefix + n).map(Change::Removed));
+        changes.extend((prefix..prefix + m).map(Change::Added));
+        return changes;
+    }
+
+    // lengths[i][j] is the length of the longest common subsequence of
+    // old_middle[i..] and new_middle[j..]
+    let mut lengths = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if old_middle[i] == new_middle[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_middle[i] == new_middle[j] {
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            changes.push(Change::Removed(prefix + i));
+            i += 1;
+        } else {
+            changes.push(Change::Added(prefix + j));
+            j += 1;
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_replaced_line() {
+        let old = ["a", "b", "c", "d"];
+        let new = ["a", "x", "c", "d", "e"];
+
+        let changes = diff_lines(&old, &new);
+
+        let expected = vec![Change::Removed(1), Change::Added(1), Change::Added(4)];
+
+        assert_eq!(changes, expected);
+    }
+
+    #[test]
+    fn diff_identical_lines() {
+        let lines = ["a", "b"];
+
+        assert!(diff_lines(&lines, &lines).is_empty());
+    }
+
+    #[test]
+    fn difference_in_line_endings() {
+        let difference = line_ending_difference("a\r\nb\r\n", "a\nb\n");
+
+        let expected = "line endings differ: file uses CRLF (\\r\\n), generated code uses LF (\\n)";
+
+        assert_eq!(difference.as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn difference_in_final_newline() {
+        assert_eq!(
+            line_ending_difference("a\nb", "a\nb\n").as_deref(),
+            Some("final newline is missing")
+        );
+        assert_eq!(
+            line_ending_difference("a\nb\n\n", "a\nb\n").as_deref(),
+            Some("file has extra newlines at the end")
+        );
+    }
+
+    #[test]
+    fn difference_in_lines() {
+        assert_eq!(line_ending_difference("a\r\nb\r\n", "a\nc\n"), None);
+    }
+
+    #[test]
+    fn scopes_of_nested_modules() {
+        let lines = [
+            "pub mod sprites {",
+            "    pub mod tiles {",
+            "        pub const TOWN: [u8; 1] = [0x00];",
+            "    }",
+            "    pub const PLAYER: [u8; 1] = [0x00];",
+            "}",
+        ];
+
+        let scopes = scopes(&lines, true);
+
+        let expected = vec![
+            "",
+            "sprites",
+            "sprites::tiles",
+            "sprites::tiles",
+            "sprites",
+            "sprites",
+        ];
+
+        assert_eq!(scopes, expected);
+    }
+}
@@ -3,15 +3,26 @@
 // See accompanying file License.txt, or online at
 // https://opensource.org/licenses/MIT
 
+use std::error::Error as StdError;
+use std::fmt;
 use std::fmt::Error as FmtError;
 use std::io::Error as IoError;
+use std::path::PathBuf;
 
 use thiserror::Error;
 
 use image::ImageError;
 
+use gif::DecodingError as GifDecodingError;
+
 use png::DecodingError;
 
+use serde_json::Error as JsonError;
+
+use toml::de::Error as TomlError;
+
+use crate::ParsedModule;
+
 /// Error occurred when converting from PNG to WASM-4 Rust source code
 #[derive(Error, Debug)]
 pub enum PngToWasm4SrcError {
@@ -27,6 +38,10 @@ pub enum PngToWasm4SrcError {
     #[error("image is not encoded in PNG format")]
     PngDecoding(#[from] DecodingError),
 
+    /// The input image is not encoded in GIF format
+    #[error("image is not encoded in GIF format")]
+    GifDecoding(#[from] GifDecodingError),
+
     /// The image processing failed
     ///
     /// Further information are stored in the wrapped error.
@@ -34,15 +49,214 @@ pub enum PngToWasm4SrcError {
     Image(#[from] ImageError),
 
     /// The input PNG image is not indexed
+    ///
+    /// Grayscale images are always accepted, while truecolor and RGBA images
+    /// are only accepted when [quantization] is enabled.
+    ///
+    /// [quantization]: crate::ConversionOptions::with_quantization
     #[error("PNG image is not indexed")]
     NotIndexedPng,
 
     /// The input indexed PNG image has a palette of invalid size
     ///
     /// WASM-4 only supports sprites with 2-colours or 4-colours palettes.
+    /// Sprite palettes with fewer colours are rounded up, so sprites are only
+    /// rejected when their palette has more than four distinct colours, while
+    /// [palette images] need at least four entries.
+    ///
+    /// [palette images]: crate::convert_png_to_rust_palette
     #[error("palette has invalid size {0}")]
     InvalidPaletteSize(usize),
 
+    /// The input indexed PNG image has the same colour in multiple entries
+    ///
+    /// Only returned when duplicate colours are
+    /// [rejected](crate::DuplicateColors::Reject).
+    /// The colour is expressed as `0xRRGGBB`.
+    #[error(
+        "palette entry {index} has colour {color:#08x}, which is already in entry {first_index}"
+    )]
+    DuplicatePaletteColor {
+        /// The duplicate colour
+        color: u32,
+
+        /// The index of the duplicate entry
+        index: usize,
+
+        /// The index of the first entry with the same colour
+        first_index: usize,
+    },
+
+    /// The input non-indexed PNG image contains too many colours
+    ///
+    /// WASM-4 only supports sprites with up to four colours.
+    #[error("image contains too many colours {0}")]
+    TooManyColors(usize),
+
+    /// The requested index for the transparent colour is invalid
+    ///
+    /// WASM-4 sprites only support colour indices from 0 to 3.
+    #[error("transparent colour index {0} is invalid")]
+    InvalidTransparentIndex(u8),
+
+    /// A pixel has a colour that is not mapped to any WASM-4 colour index
+    ///
+    /// The colour is expressed as `0xRRGGBB`, and the pixel position as
+    /// `(x, y)` coordinates from the top-left corner.
+    #[error("pixel ({x}, {y}) has colour {color:#08x}, which is not in the palette")]
+    UnmappedColor {
+        /// The pixel colour
+        color: u32,
+
+        /// The pixel horizontal coordinate
+        x: u32,
+
+        /// The pixel vertical coordinate
+        y: u32,
+    },
+
+    /// The sprite sheet size is not a multiple of the frame size
+    #[error(
+        "sprite sheet size {width}x{height} is not a multiple of frame size {frame_width}x{frame_height}"
+    )]
+    InvalidFrameSize {
+        /// The frame width
+        frame_width: u32,
+
+        /// The frame height
+        frame_height: u32,
+
+        /// The sprite sheet width
+        width: u32,
+
+        /// The sprite sheet height
+        height: u32,
+    },
+
+    /// The number of frame names does not match the number of frames
+    #[error("sprite sheet has {frames} frames, but {names} frame names were given")]
+    InvalidFrameNames {
+        /// The number of frame names
+        names: usize,
+
+        /// The number of frames in the sprite sheet
+        frames: usize,
+    },
+
+    /// A frame lies outside the sprite sheet
+    #[error("frame {frame} lies outside the sprite sheet")]
+    FrameOutOfBounds {
+        /// The frame index
+        frame: usize,
+    },
+
+    /// The frames in an Aseprite data file do not all have the same size
+    #[error("frames do not all have the same size")]
+    IrregularFrames,
+
+    /// An atlas region lies outside the atlas
+    #[error("region {name} lies outside the atlas")]
+    RegionOutOfBounds {
+        /// The region name
+        name: String,
+    },
+
+    /// The sprite metadata file could not be parsed
+    #[error("could not parse sprite metadata")]
+    Metadata(#[from] TomlError),
+
+    /// The sprite metadata defines more than one layout
+    ///
+    /// A sprite can be either an atlas with a grid, an atlas with a list of
+    /// regions, or a sprite sheet with frames.
+    #[error("sprite metadata defines more than one of grid, regions and frames")]
+    ConflictingLayouts,
+
+    /// The sprite metadata defines animations, but no frames
+    #[error("sprite metadata defines animations, but no frames")]
+    AnimationsWithoutFrames,
+
+    /// An animation refers to a frame that does not exist
+    #[error("animation {animation} refers to frame {frame}, which does not exist")]
+    InvalidAnimationFrame {
+        /// The animation name
+        animation: String,
+
+        /// The frame index
+        frame: usize,
+    },
+
+    /// An animation does not have a duration for each frame
+    #[error("animation {animation} does not have a duration for each frame")]
+    InvalidAnimationDurations {
+        /// The animation name
+        animation: String,
+    },
+
+    /// The Aseprite data file could not be parsed
+    #[error("could not parse Aseprite data")]
+    AsepriteData(#[from] JsonError),
+
+    /// The input Aseprite file could not be parsed
+    #[error("Aseprite file is invalid: {0}")]
+    InvalidAseprite(&'static str),
+
+    /// The input Aseprite file is not in indexed colour mode
+    #[error("Aseprite file is not in indexed colour mode")]
+    NotIndexedAseprite,
+
+    /// The input Aseprite file contains tilemap layers, which are not supported
+    #[error("Aseprite tilemap layers are not supported")]
+    UnsupportedAsepriteTilemap,
+
+    /// A selected layer does not exist in the Aseprite file
+    #[error("layer {0} does not exist")]
+    UnknownLayer(String),
+
+    /// An output template could not be parsed
+    #[error("template is invalid: {0}")]
+    InvalidTemplate(String),
+
+    /// The output directory of the build script is not defined
+    ///
+    /// Cargo only sets the environment variable `OUT_DIR` when running build
+    /// scripts.
+    #[error("environment variable OUT_DIR is not set")]
+    MissingOutDir,
+
+    /// A file does not contain the code generated for its sprites
+    ///
+    /// The diff lists removed lines prefixed by `-` and added lines prefixed
+    /// by `+`, grouped by the sprite or module that generated them.
+    /// Files that only differ in line endings or final newlines are described
+    /// instead.
+    #[error("file {} is out of date\n{diff}", path.display())]
+    OutdatedFile {
+        /// The file path
+        path: PathBuf,
+
+        /// The differences between the file and the generated code
+        diff: String,
+    },
+
+    /// A sprite could not be converted
+    ///
+    /// The module is identified by its full path, such as
+    /// `sprites::characters::npcs`.
+    /// Further information are stored in the wrapped error.
+    #[error("could not convert sprite {} in module {module}", path.display())]
+    Sprite {
+        /// The sprite path
+        path: PathBuf,
+
+        /// The full path of the module containing the sprite
+        module: String,
+
+        /// The error that occurred while converting the sprite
+        #[source]
+        source: Box<PngToWasm4SrcError>,
+    },
+
     /// A file does not have a stem
     ///
     /// [File stem](std::path::Path::file_stem) is the part of file name
@@ -54,3 +268,66 @@ pub enum PngToWasm4SrcError {
     #[error("path is not valid UTF-8")]
     NonUtf8Path,
 }
+
+/// Errors occurred when parsing all sprites in a module
+///
+/// Returned by [`Module::parse_all()`](crate::Module::parse_all), it contains
+/// an error for each sprite that could not be converted, and the module
+/// containing all the sprites that were converted successfully.
+///
+/// When formatted, it lists each sprite path followed by the reason for the
+/// failure.
+#[derive(Debug)]
+pub struct ParseErrors {
+    partial_module: Box<ParsedModule>,
+    errors: Vec<PngToWasm4SrcError>,
+}
+
+impl ParseErrors {
+    pub(crate) fn new(partial_module: ParsedModule, errors: Vec<PngToWasm4SrcError>) -> Self {
+        Self {
+            partial_module: Box::new(partial_module),
+            errors,
+        }
+    }
+
+    /// Return the errors, one for each sprite that could not be converted
+    pub fn errors(&self) -> &[PngToWasm4SrcError] {
+        self.errors.as_ref()
+    }
+
+    /// Return the module containing only the successfully converted sprites
+    pub fn partial_module(&self) -> &ParsedModule {
+        &self.partial_module
+    }
+
+    /// Consume the errors and return the partial module
+    pub fn into_partial_module(self) -> ParsedModule {
+        *self.partial_module
+    }
+
+    /// Consume the errors and return the first one
+    pub(crate) fn into_first_error(self) -> PngToWasm4SrcError {
+        self.errors
+            .into_iter()
+            .next()
+            .expect("Parse errors must contain at least one error")
+    }
+}
+
+impl fmt::Display for ParseErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "could not convert {} sprite(s)", self.errors.len())?;
+        for error in &self.errors {
+            write!(f, "\n  {}", error)?;
+            let mut source = error.source();
+            while let Some(error) = source {
+                write!(f, ": {}", error)?;
+                source = error.source();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StdError for ParseErrors {}
@@ -0,0 +1,575 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::fmt;
+
+use crate::backend::{write_byte, write_separated};
+use crate::sanitization::sanitize_c_variable_name;
+use crate::{sanitize_variable_name, Backend, CBackend, Flags, RustBackend, Value};
+
+/// A programming language supported by WASM-4
+///
+/// Each language is a built-in [output backend](Backend), which generates the
+/// same code as the WASM-4 command `w4 png2src --<language>`, so that code
+/// using its output keeps compiling.
+///
+/// ```
+/// # use png2wasm4src::{Flags, Language, RustVariables};
+/// let variables = RustVariables::new("name", 8, 1, Flags::OneBitPerPixel, vec![0x0f]);
+///
+/// assert_eq!(
+///     format!("{}", variables.to_source(Language::Zig)),
+///     "const name_width = 8;
+/// const name_height = 1;
+/// const name_flags = 0; // BLIT_1BPP
+/// const name = [1]u8{ 0x0f };\n",
+/// );
+/// ```
+///
+/// Only Rust nests modules, while the other languages prefix identifiers with
+/// the path of their module.
+/// Rust identifiers are in upper snake case, Zig and Odin identifiers are in
+/// snake case, and identifiers in the other languages are in camel case, such
+/// as `nameWidth`.
+/// Sprite names keep their case in all languages except Rust.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    /// AssemblyScript (`w4 png2src --assemblyscript`)
+    ///
+    /// Frames are concatenated in a single static data segment.
+    AssemblyScript,
+
+    /// C (`w4 png2src --c`)
+    ///
+    /// Use [`CBackend`] to generate header and source files.
+    C,
+
+    /// D (`w4 png2src --d`)
+    D,
+
+    /// Go (`w4 png2src --go`)
+    Go,
+
+    /// Nim (`w4 png2src --nim`)
+    Nim,
+
+    /// Odin (`w4 png2src --odin`)
+    Odin,
+
+    /// Rust (`w4 png2src --rust`)
+    ///
+    /// Use [`RustBackend`] to change the visibility and naming of constants.
+    Rust,
+
+    /// Zig (`w4 png2src --zig`)
+    Zig,
+}
+
+impl Backend for Language {
+    fn identifier(&self, name: &str, words: &[&str]) -> String {
+        match self {
+            Language::Rust => RustBackend::default().identifier(name, words),
+            Language::Odin | Language::Zig => snake_case(name, words),
+            _ => camel_case(name, words),
+        }
+    }
+
+    fn write_constant(
+        &self,
+        f: &mut fmt::Formatter,
+        depth: usize,
+        identifier: &str,
+        value: &Value,
+    ) -> fmt::Result {
+        match self {
+            Language::AssemblyScript => write_assemblyscript_constant(f, identifier, value),
+            Language::C => CBackend::default().write_constant(f, depth, identifier, value),
+            Language::D => write_d_constant(f, identifier, value),
+            Language::Go => write_go_constant(f, identifier, value),
+            Language::Nim => write_nim_constant(f, identifier, value),
+            Language::Odin => write_odin_constant(f, identifier, value),
+            Language::Rust => RustBackend::default().write_constant(f, depth, identifier, value),
+            Language::Zig => write_zig_constant(f, identifier, value),
+        }
+    }
+
+    fn nests_modules(&self) -> bool {
+        *self == Language::Rust
+    }
+
+    fn write_module_start(&self, f: &mut fmt::Formatter, depth: usize, name: &str) -> fmt::Result {
+        RustBackend::default().write_module_start(f, depth, name)
+    }
+
+    fn write_module_end(&self, f: &mut fmt::Formatter, depth: usize, name: &str) -> fmt::Result {
+        RustBackend::default().write_module_end(f, depth, name)
+    }
+}
+
+/// Write a Rust constant with the given visibility qualifier, such as `pub `
+///
+/// Atlas data is written as a `static`, so that it is stored only once
+/// instead of being inlined wherever it is used.
+pub(crate) fn write_rust_constant(
+    f: &mut fmt::Formatter,
+    depth: usize,
+    visibility: &str,
+    identifier: &str,
+    value: &Value,
+) -> fmt::Result {
+    let keyword = match value {
+        Value::Atlas(_) => "static",
+        _ => "const",
+    };
+    write!(
+        f,
+        "{}{}{} {}: ",
+        indentation(depth),
+        visibility,
+        keyword,
+        identifier
+    )?;
+    match value {
+        Value::Integer(value) => write!(f, "u32 = {};", value)?,
+        Value::Index(value) => write!(f, "usize = {};", value)?,
+        Value::Bool(value) => write!(f, "bool = {};", value)?,
+        Value::Flags(flags) => write!(
+            f,
+            "u32 = {}; // {}",
+            flags.value(),
+            flags.human_readable_value()
+        )?,
+        Value::Bytes(data) | Value::Atlas(data) => {
+            write!(f, "[u8; {}] = ", data.len())?;
+            write_bytes(f, data, "[", "]")?;
+            write!(f, ";")?;
+        }
+        Value::Frames(frames) => {
+            write!(f, "[[u8; {}]; {}] = [", frame_size(frames), frames.len())?;
+            write_separated(f, frames, |f, frame| write_bytes(f, frame, "[", "]"))?;
+            write!(f, "];")?;
+        }
+        Value::Integers(values) => write!(f, "[u32; {}] = {:?};", values.len(), values)?,
+        Value::Indices(values) => write!(f, "[usize; {}] = {:?};", values.len(), values)?,
+        Value::Colors(colors) => {
+            write!(f, "[u32; 4] = [")?;
+            write_colors(f, colors)?;
+            write!(f, "];")?;
+        }
+    }
+    writeln!(f)
+}
+
+fn write_assemblyscript_constant(
+    f: &mut fmt::Formatter,
+    identifier: &str,
+    value: &Value,
+) -> fmt::Result {
+    write!(f, "const {} = ", identifier)?;
+    match value {
+        Value::Integer(value) => write!(f, "{};", value)?,
+        Value::Index(value) => write!(f, "{};", value)?,
+        Value::Bool(value) => write!(f, "{};", value)?,
+        Value::Flags(flags) => write_flags(f, *flags, ";", "//")?,
+        Value::Bytes(data) | Value::Atlas(data) => {
+            write_bytes(f, data, "memory.data<u8>([ ", " ])")?;
+            write!(f, ";")?;
+        }
+        Value::Frames(frames) => {
+            write!(f, "memory.data<u8>([ ")?;
+            write_separated(f, frames, |f, frame| {
+                write_separated(f, frame, |f, byte| write_byte(f, *byte))
+            })?;
+            write!(f, " ]);")?;
+        }
+        Value::Integers(values) => write!(f, "StaticArray.fromArray<u32>({:?});", values)?,
+        Value::Indices(values) => write!(f, "StaticArray.fromArray<i32>({:?});", values)?,
+        Value::Colors(colors) => {
+            write!(f, "StaticArray.fromArray<u32>([")?;
+            write_colors(f, colors)?;
+            write!(f, "]);")?;
+        }
+    }
+    writeln!(f)
+}
+
+fn write_d_constant(f: &mut fmt::Formatter, identifier: &str, value: &Value) -> fmt::Result {
+    match value {
+        Value::Integer(value) => write!(f, "enum {} = {};", identifier, value)?,
+        Value::Index(value) => write!(f, "enum {} = {};", identifier, value)?,
+        Value::Bool(value) => write!(f, "enum {} = {};", identifier, value)?,
+        Value::Flags(flags) => {
+            write!(f, "enum {} = ", identifier)?;
+            write_flags(f, *flags, ";", "//")?;
+        }
+        Value::Bytes(data) | Value::Atlas(data) => {
+            write!(f, "immutable ubyte[] {} = ", identifier)?;
+            write_bytes(f, data, "[ ", " ]")?;
+            write!(f, ";")?;
+        }
+        Value::Frames(frames) => {
+            write!(
+                f,
+                "immutable ubyte[{}][{}] {} = [ ",
+                frame_size(frames),
+                frames.len(),
+                identifier
+            )?;
+            write_separated(f, frames, |f, frame| write_bytes(f, frame, "[ ", " ]"))?;
+            write!(f, " ];")?;
+        }
+        Value::Integers(values) => write!(f, "immutable uint[] {} = {:?};", identifier, values)?,
+        Value::Indices(values) => write!(f, "immutable size_t[] {} = {:?};", identifier, values)?,
+        Value::Colors(colors) => {
+            write!(f, "immutable uint[] {} = [ ", identifier)?;
+            write_colors(f, colors)?;
+            write!(f, " ];")?;
+        }
+    }
+    writeln!(f)
+}
+
+fn write_go_constant(f: &mut fmt::Formatter, identifier: &str, value: &Value) -> fmt::Result {
+    match value {
+        Value::Integer(value) => write!(f, "const {} = {}", identifier, value)?,
+        Value::Index(value) => write!(f, "const {} = {}", identifier, value)?,
+        Value::Bool(value) => write!(f, "const {} = {}", identifier, value)?,
+        Value::Flags(flags) => {
+            write!(f, "const {} = ", identifier)?;
+            write_flags(f, *flags, "", "//")?;
+        }
+        Value::Bytes(data) | Value::Atlas(data) => {
+            write!(f, "var {} = [{}]byte ", identifier, data.len())?;
+            write_bytes(f, data, "{ ", " }")?;
+        }
+        Value::Frames(frames) => {
+            write!(
+                f,
+                "var {} = [{}][{}]byte {{ ",
+                identifier,
+                frames.len(),
+                frame_size(frames)
+            )?;
+            write_separated(f, frames, |f, frame| write_bytes(f, frame, "{ ", " }"))?;
+            write!(f, " }}")?;
+        }
+        Value::Integers(values) => {
+            write!(f, "var {} = [{}]uint32 ", identifier, values.len())?;
+            write_numbers(f, values, "{ ", " }")?;
+        }
+        Value::Indices(values) => {
+            write!(f, "var {} = [{}]int ", identifier, values.len())?;
+            write_numbers(f, values, "{ ", " }")?;
+        }
+        Value::Colors(colors) => {
+            write!(f, "var {} = [4]uint32 {{ ", identifier)?;
+            write_colors(f, colors)?;
+            write!(f, " }}")?;
+        }
+    }
+    writeln!(f)
+}
+
+fn write_nim_constant(f: &mut fmt::Formatter, identifier: &str, value: &Value) -> fmt::Result {
+    match value {
+        Value::Integer(value) => write!(f, "const {} = {}", identifier, value)?,
+        Value::Index(value) => write!(f, "const {} = {}", identifier, value)?,
+        Value::Bool(value) => write!(f, "const {} = {}", identifier, value)?,
+        Value::Flags(flags) => {
+            write!(f, "const {} = ", identifier)?;
+            write_flags(f, *flags, "", "#")?;
+        }
+        Value::Bytes(data) | Value::Atlas(data) => {
+            write!(f, "var {}: array[{}, uint8] = ", identifier, data.len())?;
+            write_nim_bytes(f, data)?;
+        }
+        Value::Frames(frames) => {
+            write!(
+                f,
+                "var {}: array[{}, array[{}, uint8]] = [",
+                identifier,
+                frames.len(),
+                frame_size(frames)
+            )?;
+            write_separated(f, frames, |f, frame| write_nim_bytes(f, frame))?;
+            write!(f, "]")?;
+        }
+        Value::Integers(values) => {
+            write!(
+                f,
+                "const {}: array[{}, uint32] = ",
+                identifier,
+                values.len()
+            )?;
+            write_numbers(f, values, "[", "]")?;
+        }
+        Value::Indices(values) => {
+            write!(f, "const {}: array[{}, int] = ", identifier, values.len())?;
+            write_numbers(f, values, "[", "]")?;
+        }
+        Value::Colors(colors) => {
+            write!(f, "const {}: array[4, uint32] = [", identifier)?;
+            write_colors(f, colors)?;
+            write!(f, "]")?;
+        }
+    }
+    writeln!(f)
+}
+
+fn write_odin_constant(f: &mut fmt::Formatter, identifier: &str, value: &Value) -> fmt::Result {
+    match value {
+        Value::Integer(value) => write!(f, "{} : u32 : {}", identifier, value)?,
+        Value::Index(value) => write!(f, "{} : int : {}", identifier, value)?,
+        Value::Bool(value) => write!(f, "{} :: {}", identifier, value)?,
+        Value::Flags(flags) => {
+            let flags = match flags {
+                Flags::OneBitPerPixel => "{}",
+                Flags::TwoBitsPerPixel => "{.USE_2BPP}",
+            };
+            write!(f, "{} : w4.Blit_Flags : {}", identifier, flags)?;
+        }
+        Value::Bytes(data) | Value::Atlas(data) => {
+            write!(f, "{} := [{}]u8", identifier, data.len())?;
+            write_bytes(f, data, "{ ", " }")?;
+        }
+        Value::Frames(frames) => {
+            write!(
+                f,
+                "{} := [{}][{}]u8{{ ",
+                identifier,
+                frames.len(),
+                frame_size(frames)
+            )?;
+            write_separated(f, frames, |f, frame| write_bytes(f, frame, "{ ", " }"))?;
+            write!(f, " }}")?;
+        }
+        Value::Integers(values) => {
+            write!(f, "{} := [{}]u32", identifier, values.len())?;
+            write_numbers(f, values, "{ ", " }")?;
+        }
+        Value::Indices(values) => {
+            write!(f, "{} := [{}]int", identifier, values.len())?;
+            write_numbers(f, values, "{ ", " }")?;
+        }
+        Value::Colors(colors) => {
+            write!(f, "{} := [4]u32{{ ", identifier)?;
+            write_colors(f, colors)?;
+            write!(f, " }}")?;
+        }
+    }
+    writeln!(f)
+}
+
+fn write_zig_constant(f: &mut fmt::Formatter, identifier: &str, value: &Value) -> fmt::Result {
+    write!(f, "const {} = ", identifier)?;
+    match value {
+        Value::Integer(value) => write!(f, "{};", value)?,
+        Value::Index(value) => write!(f, "{};", value)?,
+        Value::Bool(value) => write!(f, "{};", value)?,
+        Value::Flags(flags) => write_flags(f, *flags, ";", "//")?,
+        Value::Bytes(data) | Value::Atlas(data) => {
+            write!(f, "[{}]u8", data.len())?;
+            write_bytes(f, data, "{ ", " }")?;
+            write!(f, ";")?;
+        }
+        Value::Frames(frames) => {
+            write!(f, "[{}][{}]u8{{ ", frames.len(), frame_size(frames))?;
+            write_separated(f, frames, |f, frame| write_bytes(f, frame, ".{ ", " }"))?;
+            write!(f, " }};")?;
+        }
+        Value::Integers(values) => {
+            write!(f, "[{}]u32", values.len())?;
+            write_numbers(f, values, "{ ", " }")?;
+            write!(f, ";")?;
+        }
+        Value::Indices(values) => {
+            write!(f, "[{}]usize", values.len())?;
+            write_numbers(f, values, "{ ", " }")?;
+            write!(f, ";")?;
+        }
+        Value::Colors(colors) => {
+            write!(f, "[4]u32{{ ")?;
+            write_colors(f, colors)?;
+            write!(f, " }};")?;
+        }
+    }
+    writeln!(f)
+}
+
+/// Write the numeric value of flags, followed by their human-readable value
+/// in a comment
+fn write_flags(
+    f: &mut fmt::Formatter,
+    flags: Flags,
+    terminator: &str,
+    comment: &str,
+) -> fmt::Result {
+    write!(
+        f,
+        "{}{} {} {}",
+        flags.value(),
+        terminator,
+        comment,
+        flags.human_readable_value()
+    )
+}
+
+/// Write bytes between delimiters
+pub(crate) fn write_bytes(
+    f: &mut fmt::Formatter,
+    data: &[u8],
+    start: &str,
+    end: &str,
+) -> fmt::Result {
+    write!(f, "{}", start)?;
+    write_separated(f, data, |f, byte| write_byte(f, *byte))?;
+    write!(f, "{}", end)
+}
+
+/// Write bytes in a Nim array, where the first item sets the type
+fn write_nim_bytes(f: &mut fmt::Formatter, data: &[u8]) -> fmt::Result {
+    write!(f, "[")?;
+    for (index, byte) in data.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write_byte(f, *byte)?;
+        if index == 0 {
+            write!(f, "'u8")?;
+        }
+    }
+    write!(f, "]")
+}
+
+/// Write decimal numbers between delimiters
+pub(crate) fn write_numbers<T: fmt::Display>(
+    f: &mut fmt::Formatter,
+    values: &[T],
+    start: &str,
+    end: &str,
+) -> fmt::Result {
+    write!(f, "{}", start)?;
+    write_separated(f, values, |f, value| write!(f, "{}", value))?;
+    write!(f, "{}", end)
+}
+
+/// Write colours as `0xRRGGBB`, separated by commas
+pub(crate) fn write_colors(f: &mut fmt::Formatter, colors: &[u32; 4]) -> fmt::Result {
+    write_separated(f, colors, |f, color| write!(f, "{:#08x}", color))
+}
+
+/// Return the size of the first frame
+pub(crate) fn frame_size(frames: &[Vec<u8>]) -> usize {
+    frames.first().map(Vec::len).unwrap_or_default()
+}
+
+pub(crate) fn indentation(depth: usize) -> String {
+    " ".repeat(4 * depth)
+}
+
+/// Build an identifier such as `NAME_SRC_X`
+pub(crate) fn upper_snake_case(name: &str, words: &[&str]) -> String {
+    sanitize_variable_name(&join_words(name, words))
+}
+
+/// Build an identifier such as `name_src_x`
+pub(crate) fn snake_case(name: &str, words: &[&str]) -> String {
+    sanitize_c_variable_name(&join_words(name, words))
+}
+
+/// Join a name and words with underscores
+fn join_words(name: &str, words: &[&str]) -> String {
+    let mut identifier = name.to_string();
+    for word in words {
+        if !identifier.is_empty() {
+            identifier.push('_');
+        }
+        identifier.push_str(word);
+    }
+    identifier
+}
+
+/// Build an identifier such as `nameSrcX`
+pub(crate) fn camel_case(name: &str, words: &[&str]) -> String {
+    let mut identifier = name.to_string();
+    for word in words {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            if identifier.is_empty() {
+                identifier.push(first.to_ascii_lowercase());
+            } else {
+                identifier.push(first.to_ascii_uppercase());
+            }
+            identifier.extend(chars);
+        }
+    }
+    sanitize_c_variable_name(&identifier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::RustFrames;
+
+    #[test]
+    fn identifiers() {
+        let words = ["src", "x"];
+
+        assert_eq!(
+            Language::Rust.identifier("some-name", &words),
+            "SOME_NAME_SRC_X"
+        );
+        assert_eq!(
+            Language::Zig.identifier("some-name", &words),
+            "some_name_src_x"
+        );
+        assert_eq!(
+            Language::Go.identifier("some-name", &words),
+            "some_nameSrcX"
+        );
+        assert_eq!(Language::Go.identifier("", &["palette"]), "palette");
+    }
+
+    #[test]
+    fn frames_to_go() {
+        let frames = RustFrames::new(
+            "name",
+            4,
+            2,
+            Flags::TwoBitsPerPixel,
+            vec![vec![0x0f, 0x00], vec![0xf0, 0x00]],
+        )
+        .with_frame_durations(vec![30, 6]);
+        let code = frames.to_source(Language::Go).to_string();
+
+        let expected = "const nameWidth = 4
+const nameHeight = 2
+const nameFlags = 1 // BLIT_2BPP
+var nameFrames = [2][2]byte { { 0x0f, 0x00 }, { 0xf0, 0x00 } }
+var nameFrameDurations = [2]uint32 { 30, 6 }\n";
+
+        assert_eq!(code, expected);
+    }
+
+    #[test]
+    fn frames_to_nim() {
+        let frames = RustFrames::new(
+            "name",
+            4,
+            2,
+            Flags::OneBitPerPixel,
+            vec![vec![0x0f], vec![0xf0]],
+        );
+        let code = frames.to_source(Language::Nim).to_string();
+
+        let expected = "const nameWidth = 4
+const nameHeight = 2
+const nameFlags = 0 # BLIT_1BPP
+var nameFrames: array[2, array[1, uint8]] = [[0x0f'u8], [0xf0'u8]]\n";
+
+        assert_eq!(code, expected);
+    }
+}
@@ -5,22 +5,79 @@
 
 #![cfg_attr(not(doctest), doc = include_str!("../Readme.md"))]
 
+mod animated;
+
+mod animation;
+pub use animation::Animation;
+
+mod aseprite;
+pub use aseprite::LayerSelection;
+
+mod atlas;
+
+mod backend;
+pub use backend::{Backend, SourceCode, Value};
+
+mod builder;
+pub use builder::{Builder, ByteFormat};
+
+mod c;
+pub use c::{CBackend, CCode};
+
+mod check;
+
 mod error;
-pub use error::PngToWasm4SrcError;
+pub use error::{ParseErrors, PngToWasm4SrcError};
 
 mod flags;
 pub use flags::Flags;
 
+mod language;
+pub use language::Language;
+
 mod lookup;
 pub use lookup::build_sprite_modules_tree;
 pub use lookup::Module;
 pub use lookup::ParsedModule;
+pub use lookup::{parse_sprite_file, ParsedSprite};
+
+mod metadata;
+pub use metadata::SpriteMetadata;
+
+mod options;
+pub use options::{ColorOrder, ConversionOptions, DuplicateColors, PaletteCompaction};
+
+mod output;
+pub use output::write_if_changed;
+
+mod palette;
+pub use palette::{ColorMatching, MergedColor, ReferencePalette};
+
+mod region;
+pub use region::{AtlasLayout, Region};
 
 mod rust;
-pub use rust::RustVariables;
+pub use rust::{PackedSprite, RustAtlas, RustFrames, RustPalette, RustVariables};
+
+mod rust_backend;
+pub use rust_backend::{Naming, RustBackend, Visibility};
+
+mod rust_struct;
+pub use rust_struct::RustStructBackend;
 
 mod sanitization;
 use sanitization::sanitize_variable_name;
 
+mod sheet;
+pub use sheet::{FrameOrder, SpriteSheet};
+
+mod template;
+pub use template::Template;
+
 mod sprite;
-pub use sprite::convert_png_to_rust_variables;
+pub use sprite::{
+    convert_apng_to_rust_frames, convert_aseprite_to_rust_frames,
+    convert_aseprite_to_rust_variables, convert_gif_to_rust_frames, convert_png_to_rust_atlas,
+    convert_png_to_rust_frames, convert_png_to_rust_palette, convert_png_to_rust_variables,
+    convert_png_to_rust_variables_with_options,
+};
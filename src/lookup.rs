@@ -10,12 +10,26 @@ use std::io::Error as IoError;
 use std::io::ErrorKind as IoErrorKind;
 use std::path::{Path, PathBuf};
 
-use crate::{convert_png_to_rust_variables, PngToWasm4SrcError, RustVariables};
+use crate::animated::is_animated_png;
+use crate::aseprite::AsepriteFile;
+use crate::atlas::pack_atlases;
+use crate::metadata::sidecar_paths;
+use crate::sprite::{
+    convert_aseprite_file_to_rust_frames, convert_aseprite_file_to_rust_variables,
+    convert_png_to_rust_frames_with_layout,
+};
+use crate::{
+    convert_apng_to_rust_frames, convert_gif_to_rust_frames, convert_png_to_rust_atlas,
+    convert_png_to_rust_variables_with_options, write_if_changed, Backend, ByteFormat, CBackend,
+    CCode, ConversionOptions, Language, ParseErrors, PngToWasm4SrcError, RustAtlas, RustFrames,
+    RustPalette, RustVariables, SourceCode, SpriteMetadata,
+};
 
 /// A module containing sprites
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Module {
     name: String,
+    directory: Option<PathBuf>,
     sprite_paths: BTreeSet<PathBuf>,
     submodules: BTreeSet<Module>,
 }
@@ -24,7 +38,10 @@ pub struct Module {
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ParsedModule {
     name: String,
+    palette: Option<RustPalette>,
     variables: BTreeSet<RustVariables>,
+    frames: BTreeSet<RustFrames>,
+    atlases: Vec<RustAtlas>,
     submodules: BTreeSet<ParsedModule>,
 }
 
@@ -38,39 +55,115 @@ impl Module {
     {
         Self {
             name: name.into(),
+            directory: None,
             sprite_paths: sprite_paths.into_iter().collect(),
             submodules: submodules.into_iter().collect(),
         }
     }
 
+    /// Set the directory the module was built from
+    ///
+    /// Cargo build instructions also track changes to the directory, such as
+    /// sprites being added or removed.
+    pub fn with_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Return the directory the module was built from, if any
+    pub fn directory(&self) -> Option<&Path> {
+        self.directory.as_deref()
+    }
+
     /// Parse the sprites in the module
     ///
     /// Parse all the sprites in the module and generate their Rust variables.
+    ///
+    /// Errors are wrapped in [`PngToWasm4SrcError::Sprite`], which records the
+    /// sprite path and the full path of its module.
+    /// If multiple sprites cannot be converted, only the first error is
+    /// returned; use [`Module::parse_all()`] to collect all of them.
     pub fn parse(self) -> Result<ParsedModule, PngToWasm4SrcError> {
-        let variables = self
-            .sprite_paths
-            .into_iter()
-            .map(|path| {
-                let name = path
-                    .file_stem()
-                    .ok_or(PngToWasm4SrcError::FileWithoutStem)?
-                    .to_str()
-                    .ok_or(PngToWasm4SrcError::NonUtf8Path)?;
-                let bytes = read(&path)?;
-                let rust_variables = convert_png_to_rust_variables(name, &bytes)?;
-                Ok(rust_variables)
-            })
-            .collect::<Result<BTreeSet<RustVariables>, PngToWasm4SrcError>>()?;
+        self.parse_with_options(&ConversionOptions::default())
+    }
+
+    /// Parse the sprites in the module with custom conversion options
+    ///
+    /// The options apply to all sprites in the module and its submodules.
+    /// See [`Module::parse()`] for details.
+    pub fn parse_with_options(
+        self,
+        options: &ConversionOptions,
+    ) -> Result<ParsedModule, PngToWasm4SrcError> {
+        self.parse_all_with_options(options)
+            .map_err(ParseErrors::into_first_error)
+    }
+
+    /// Parse all the sprites in the module, collecting all errors
+    ///
+    /// Parse all the sprites in the module and generate their Rust variables,
+    /// without stopping at the first sprite that cannot be converted.
+    /// If any sprite fails, the returned [`ParseErrors`] contains an error for
+    /// each of them, as well as the partial module with all the sprites that
+    /// were converted successfully.
+    pub fn parse_all(self) -> Result<ParsedModule, ParseErrors> {
+        self.parse_all_with_options(&ConversionOptions::default())
+    }
 
-        let submodules = self
+    /// Parse all the sprites in the module with custom conversion options,
+    /// collecting all errors
+    ///
+    /// See [`Module::parse_all()`] for details.
+    pub fn parse_all_with_options(
+        self,
+        options: &ConversionOptions,
+    ) -> Result<ParsedModule, ParseErrors> {
+        let mut errors = Vec::default();
+        let parsed_module = self.parse_in_module_path(None, options, &mut errors);
+
+        if errors.is_empty() {
+            Ok(parsed_module)
+        } else {
+            Err(ParseErrors::new(parsed_module, errors))
+        }
+    }
+
+    fn parse_in_module_path(
+        self,
+        parent_path: Option<&str>,
+        options: &ConversionOptions,
+        errors: &mut Vec<PngToWasm4SrcError>,
+    ) -> ParsedModule {
+        let module_path = match parent_path {
+            Some(parent_path) => format!("{}::{}", parent_path, self.name),
+            None => self.name.clone(),
+        };
+
+        let mut variables = BTreeSet::default();
+        let mut frames = BTreeSet::default();
+        for path in self.sprite_paths {
+            match parse_sprite(&path, options) {
+                Ok(ParsedSprite::Variables(rust_variables)) => {
+                    variables.insert(rust_variables);
+                }
+                Ok(ParsedSprite::Frames(rust_frames)) => {
+                    frames.insert(rust_frames);
+                }
+                Err(error) => errors.push(PngToWasm4SrcError::Sprite {
+                    path,
+                    module: module_path.clone(),
+                    source: Box::new(error),
+                }),
+            }
+        }
+
+        let submodules: Vec<ParsedModule> = self
             .submodules
             .into_iter()
-            .map(|submodule| submodule.parse())
-            .collect::<Result<BTreeSet<ParsedModule>, PngToWasm4SrcError>>()?;
+            .map(|submodule| submodule.parse_in_module_path(Some(&module_path), options, errors))
+            .collect();
 
-        let parsed_module = ParsedModule::new(self.name, variables, submodules);
-
-        Ok(parsed_module)
+        ParsedModule::new(self.name, variables, submodules).with_frames(frames)
     }
 
     /// Flatten the module
@@ -87,6 +180,7 @@ impl Module {
 
         Self {
             name: self.name,
+            directory: self.directory,
             sprite_paths,
             submodules: BTreeSet::default(),
         }
@@ -97,13 +191,25 @@ impl Module {
     /// Build scripts communicate with cargo by printing instructions starting
     /// with `cargo:` to standard output.
     /// This function generates a list of instructions to force a rebuild when
-    /// source PNGs are modified.
+    /// source PNGs or their [sidecar metadata files](SpriteMetadata) are
+    /// modified.
+    /// If the module was built from a [directory](Module::with_directory),
+    /// the directory is tracked as well, so that adding or removing sprites
+    /// also forces a rebuild.
     pub fn generate_cargo_build_instructions<W>(&self, output: &mut W) -> Result<(), fmt::Error>
     where
         W: std::fmt::Write,
     {
+        if let Some(directory) = &self.directory {
+            writeln!(output, "cargo:rerun-if-changed={}", directory.display())?;
+        }
         for path in &self.sprite_paths {
             writeln!(output, "cargo:rerun-if-changed={}", path.display())?;
+            for sidecar_path in &sidecar_paths(path) {
+                if sidecar_path.is_file() {
+                    writeln!(output, "cargo:rerun-if-changed={}", sidecar_path.display())?;
+                }
+            }
         }
         for submodule in &self.submodules {
             submodule.generate_cargo_build_instructions(output)?;
@@ -122,24 +228,186 @@ impl ParsedModule {
     {
         Self {
             name: name.into(),
+            palette: None,
             variables: variables.into_iter().collect(),
+            frames: BTreeSet::default(),
+            atlases: Vec::default(),
             submodules: submodules.into_iter().collect(),
         }
     }
+
+    /// Include a palette constant in the module
+    ///
+    /// The palette constant is generated at the beginning of the module,
+    /// before all sprites.
+    pub fn with_palette(mut self, palette: RustPalette) -> Self {
+        self.palette = Some(palette);
+        self
+    }
+
+    /// Include sprite sheet frames in the module
+    ///
+    /// Frames are generated after all sprites.
+    pub fn with_frames<F>(mut self, frames: F) -> Self
+    where
+        F: IntoIterator<Item = RustFrames>,
+    {
+        self.frames.extend(frames);
+        self
+    }
+
+    /// Return the module name
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    /// Return the palette constant, if any
+    pub fn palette(&self) -> Option<&RustPalette> {
+        self.palette.as_ref()
+    }
+
+    /// Return the variables of the sprites in the module
+    pub fn variables(&self) -> impl Iterator<Item = &RustVariables> {
+        self.variables.iter()
+    }
+
+    /// Return the sprite sheet frames in the module
+    pub fn frames(&self) -> impl Iterator<Item = &RustFrames> {
+        self.frames.iter()
+    }
+
+    /// Return the atlases in the module
+    pub fn atlases(&self) -> &[RustAtlas] {
+        self.atlases.as_ref()
+    }
+
+    /// Return the submodules
+    pub fn submodules(&self) -> impl Iterator<Item = &ParsedModule> {
+        self.submodules.iter()
+    }
+
+    /// Generate source code for the module using an output backend
+    ///
+    /// Backends that do not [nest modules](Backend::nests_modules) prefix
+    /// all names with the path of their module.
+    /// See [`Backend`] and [`Language`] for details.
+    pub fn to_source<B: Backend>(&self, backend: B) -> SourceCode<'_, B, Self> {
+        SourceCode::new(backend, self)
+    }
+
+    /// Generate C source code for the module
+    ///
+    /// All items are generated in a single file, and modules are mapped to
+    /// name prefixes: sprite `player` in module `sprites::characters` is
+    /// generated as `sprites_characters_player`.
+    /// See [`CCode`] for the naming of the other constants.
+    pub fn to_c(&self) -> CCode<'_, Self> {
+        self.to_source(CBackend::default())
+    }
+
+    /// Generate a C header file for the module
+    ///
+    /// The header is wrapped in an include guard with the given name, and
+    /// contains all `#define`s, while arrays are only declared as `extern`.
+    /// They are defined in the [source file](ParsedModule::to_c_source).
+    pub fn to_c_header(&self, guard: impl Into<String>) -> CCode<'_, Self> {
+        self.to_source(CBackend::header(guard))
+    }
+
+    /// Generate a C source file for the module
+    ///
+    /// The source file includes the header with the given path, and defines
+    /// all the arrays declared in the [header file](ParsedModule::to_c_header).
+    pub fn to_c_source(&self, header: impl Into<String>) -> CCode<'_, Self> {
+        self.to_source(CBackend::source(header))
+    }
+
+    /// Write the Rust code of the module to a file, unless it is unchanged
+    ///
+    /// Return whether the file was written.
+    /// See [`write_if_changed()`] for details.
+    pub fn write_if_changed<P>(&self, path: P) -> Result<bool, PngToWasm4SrcError>
+    where
+        P: AsRef<Path>,
+    {
+        write_if_changed(path, &self.to_string())
+    }
+
+    /// Check that a file contains the Rust code of the module
+    ///
+    /// See [`SourceCode::check_file()`] for details.
+    pub fn check_file<P>(&self, path: P) -> Result<(), PngToWasm4SrcError>
+    where
+        P: AsRef<Path>,
+    {
+        self.to_source(Language::Rust)
+            .check_file(path, ByteFormat::Hexadecimal)
+    }
+
+    /// Pack the sprites in the module into atlases
+    ///
+    /// All sprites with the same bit depth are packed into a single atlas,
+    /// named `ATLAS_1BPP` or `ATLAS_2BPP`, which is generated only once.
+    /// This saves the overhead of many separate arrays.
+    /// Each submodule is packed separately, and sprite sheet frames are not
+    /// packed.
+    ///
+    /// The constants of each sprite are still generated, except its data
+    /// constant, and it has three additional constants `NAME_SRC_X`,
+    /// `NAME_SRC_Y` and `NAME_STRIDE`, so that it can be drawn from the atlas
+    /// data using the WASM-4 function `blit_sub`.
+    /// See [`RustAtlas`] for an example.
+    pub fn pack_atlases(self) -> Self {
+        let variables = self.variables;
+        let mut atlases = self.atlases;
+        atlases.extend(pack_atlases(variables));
+
+        Self {
+            name: self.name,
+            palette: self.palette,
+            variables: BTreeSet::default(),
+            frames: self.frames,
+            atlases,
+            submodules: self
+                .submodules
+                .into_iter()
+                .map(ParsedModule::pack_atlases)
+                .collect(),
+        }
+    }
 }
 
 impl fmt::Display for ParsedModule {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write_parsed_module_with_indentation(self, 0, f).map_err(|_| fmt::Error)
+        self.to_source(Language::Rust).fmt(f)
     }
 }
 
 /// Build a sprite module tree from a directory containing sprites
+///
+/// Sprites are PNG images (`.png`), GIF images (`.gif`) and Aseprite files
+/// (`.ase` or `.aseprite`).
+/// Aseprite files with a single frame are converted like PNG images, while
+/// Aseprite files with multiple frames are converted to frames, with an
+/// animation for each tag.
+/// Animated PNG images and GIF images are converted to frames, with the
+/// duration of each frame.
 pub fn build_sprite_modules_tree<P>(dir: P) -> Result<Module, PngToWasm4SrcError>
 where
     P: AsRef<Path>,
 {
-    let dir: &Path = dir.as_ref();
+    let extensions: Vec<String> = SPRITE_EXTENSIONS.iter().map(|e| e.to_string()).collect();
+    build_sprite_modules_tree_with_extensions(dir.as_ref(), &extensions)
+}
+
+/// The extensions of all supported sprite files
+pub(crate) const SPRITE_EXTENSIONS: [&str; 4] = ["png", "gif", "ase", "aseprite"];
+
+/// Build a sprite module tree only including files with the given extensions
+pub(crate) fn build_sprite_modules_tree_with_extensions(
+    dir: &Path,
+    extensions: &[String],
+) -> Result<Module, PngToWasm4SrcError> {
     if dir.is_dir() {
         let module_name = dir
             .file_name()
@@ -157,7 +425,7 @@ where
             .collect::<Result<Vec<PathBuf>, PngToWasm4SrcError>>()?
             .into_iter()
             .filter(|path| path.is_file())
-            .filter(|path| path.extension().map(|s| s == "png").unwrap_or(false));
+            .filter(|path| is_sprite(path, extensions));
 
         // Then recurse into directories
         let submodules = read_dir(dir)?
@@ -169,12 +437,12 @@ where
             .collect::<Result<Vec<PathBuf>, PngToWasm4SrcError>>()?
             .into_iter()
             .filter(|path| path.is_dir())
-            .map(|path| build_sprite_modules_tree(&path))
+            .map(|path| build_sprite_modules_tree_with_extensions(&path, extensions))
             .collect::<Result<Vec<Module>, PngToWasm4SrcError>>()?
             .into_iter()
             .filter(|submodule| !submodule.sprite_paths.is_empty());
 
-        let module = Module::new(module_name, files, submodules);
+        let module = Module::new(module_name, files, submodules).with_directory(dir);
         return Ok(module);
     }
 
@@ -184,35 +452,117 @@ where
     )))
 }
 
-fn write_parsed_module_with_indentation(
-    module: &ParsedModule,
-    level: usize,
-    f: &mut fmt::Formatter,
-) -> Result<(), PngToWasm4SrcError> {
-    let mod_prefix = vec![32_u8; 4 * level];
-    let mod_prefix = String::from_utf8(mod_prefix).expect("Cannot create string");
-    let prefix = vec![32_u8; 4 * (level + 1)];
-    let prefix = String::from_utf8(prefix).expect("Cannot create string");
-
-    writeln!(f, "{}pub mod {} {{", mod_prefix, module.name)?;
-
-    for rust_variables in &module.variables {
-        let rust_code = rust_variables.to_string();
-        for line in rust_code.split('\n') {
-            if !line.is_empty() {
-                writeln!(f, "{}pub {}", prefix, line)?;
-            }
+/// Return the extension of a file in lowercase
+///
+/// Extensions are compared ignoring their case, both when discovering sprite
+/// files and when choosing their format, such as `walk.GIF`.
+fn lowercase_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+}
+
+fn is_sprite(path: &Path, extensions: &[String]) -> bool {
+    match lowercase_extension(path) {
+        Some(extension) => extensions
+            .iter()
+            .any(|e| e.to_ascii_lowercase() == extension),
+        None => false,
+    }
+}
+
+fn is_aseprite(path: &Path) -> bool {
+    matches!(
+        lowercase_extension(path).as_deref(),
+        Some("ase") | Some("aseprite")
+    )
+}
+
+fn is_gif(path: &Path) -> bool {
+    lowercase_extension(path).as_deref() == Some("gif")
+}
+
+/// A sprite file converted to either plain variables or frames
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParsedSprite {
+    /// A sprite, possibly with atlas regions
+    Variables(RustVariables),
+
+    /// A sprite sheet, an animated image or an Aseprite file with multiple
+    /// frames
+    Frames(RustFrames),
+}
+
+/// Parse a single sprite file
+///
+/// The sprite is converted the same as in a [module](Module::parse), using
+/// its [sidecar metadata file](SpriteMetadata) if any, and it is named after
+/// the file stem.
+pub fn parse_sprite_file<P>(
+    path: P,
+    options: &ConversionOptions,
+) -> Result<ParsedSprite, PngToWasm4SrcError>
+where
+    P: AsRef<Path>,
+{
+    parse_sprite(path.as_ref(), options)
+}
+
+fn parse_sprite(
+    path: &Path,
+    options: &ConversionOptions,
+) -> Result<ParsedSprite, PngToWasm4SrcError> {
+    let name = path
+        .file_stem()
+        .ok_or(PngToWasm4SrcError::FileWithoutStem)?
+        .to_str()
+        .ok_or(PngToWasm4SrcError::NonUtf8Path)?;
+    let bytes = read(path)?;
+    let metadata = SpriteMetadata::read_sidecar(path)?.unwrap_or_default();
+
+    if is_aseprite(path) {
+        let file = AsepriteFile::parse(&bytes)?;
+        let layers = metadata.layers();
+        if file.frame_count() > 1 {
+            let rust_frames = convert_aseprite_file_to_rust_frames(name, &file, layers, options)?;
+            return Ok(ParsedSprite::Frames(rust_frames));
         }
-        writeln!(f)?;
+        let rust_variables = convert_aseprite_file_to_rust_variables(name, &file, layers, options)?;
+        let rust_variables = match metadata.layout() {
+            Some(layout) => {
+                let regions = layout.regions(rust_variables.width(), rust_variables.height())?;
+                rust_variables.with_regions(regions)
+            }
+            None => rust_variables,
+        };
+        return Ok(ParsedSprite::Variables(rust_variables));
     }
 
-    for submodule in &module.submodules {
-        write_parsed_module_with_indentation(submodule, level + 1, f)?;
+    if is_gif(path) {
+        let rust_frames = convert_gif_to_rust_frames(name, &bytes, options)?;
+        return Ok(ParsedSprite::Frames(rust_frames));
     }
 
-    writeln!(f, "{}}}\n", mod_prefix)?;
+    if is_animated_png(&bytes)? {
+        let rust_frames = convert_apng_to_rust_frames(name, &bytes, options)?;
+        return Ok(ParsedSprite::Frames(rust_frames));
+    }
+
+    if let Some(frame_layout) = metadata.frames() {
+        let rust_frames =
+            convert_png_to_rust_frames_with_layout(name, &bytes, frame_layout, options)?;
+        for animation in metadata.animations() {
+            animation.validate(rust_frames.frames().len())?;
+        }
+        let rust_frames = rust_frames.with_animations(metadata.animations().to_vec());
+        return Ok(ParsedSprite::Frames(rust_frames));
+    }
 
-    Ok(())
+    let rust_variables = match metadata.layout() {
+        Some(layout) => convert_png_to_rust_atlas(name, &bytes, layout, options)?,
+        None => convert_png_to_rust_variables_with_options(name, &bytes, options)?,
+    };
+    Ok(ParsedSprite::Variables(rust_variables))
 }
 
 #[cfg(test)]
@@ -269,4 +619,26 @@ cargo:rerun-if-changed=/two
 
         Ok(())
     }
+
+    #[test]
+    fn module_generate_cargo_build_instructions_with_directory() -> Result<()> {
+        let two = Module::new("two", vec![PathBuf::from("/one/two/b")], Vec::default())
+            .with_directory("/one/two");
+        let one =
+            Module::new("one", vec![PathBuf::from("/one/a")], vec![two]).with_directory("/one");
+
+        let mut buffer = String::default();
+
+        one.generate_cargo_build_instructions(&mut buffer)?;
+
+        let expected = "cargo:rerun-if-changed=/one
+cargo:rerun-if-changed=/one/a
+cargo:rerun-if-changed=/one/two
+cargo:rerun-if-changed=/one/two/b
+";
+
+        assert_eq!(buffer, expected);
+
+        Ok(())
+    }
 }
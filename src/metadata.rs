@@ -0,0 +1,590 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use serde_json::Value as JsonValue;
+
+use crate::animation::{animation_from_tag, millis_to_ticks, TagDirection};
+use crate::sheet::FrameLayout;
+use crate::{
+    Animation, AtlasLayout, FrameOrder, LayerSelection, PngToWasm4SrcError, Region, SpriteSheet,
+};
+
+/// Metadata of a sprite, stored in a sidecar file next to its PNG image
+///
+/// The sidecar file has the same stem as the image and extension `.toml`,
+/// such as `atlas.toml` for `atlas.png`.
+/// If there is no such file, a data file exported by Aseprite with extension
+/// `.json` is used instead, such as `player.json` for `player.png`.
+///
+///
+/// Atlas regions
+/// ----
+///
+/// Regions can be defined either as a regular grid, or listed explicitly.
+/// A sprite can only have one of grid, regions and frames.
+///
+/// ```toml
+/// [grid]
+/// width = 8
+/// height = 8
+/// order = "column-major" # optional, default "row-major"
+/// names = ["heart", "coin", "key", "door"] # optional
+/// ```
+///
+/// ```toml
+/// [[regions]]
+/// name = "heart"
+/// x = 0
+/// y = 0
+/// width = 8
+/// height = 8
+///
+/// [[regions]]
+/// name = "door"
+/// x = 8
+/// y = 0
+/// width = 8
+/// height = 16
+/// ```
+///
+///
+/// Frames and animations
+/// ----
+///
+/// A sprite sheet is cut into frames of the same size, which accept the same
+/// keys as the grid.
+/// Animations list the indices of their frames, and their durations in
+/// milliseconds, either for all frames or for each frame.
+/// Durations are converted to WASM-4 ticks (1/60 of a second).
+/// Animations loop by default, and can play back and forth.
+///
+/// ```toml
+/// [frames]
+/// width = 16
+/// height = 16
+///
+/// [[animations]]
+/// name = "idle"
+/// frames = [0, 1]
+/// duration = 500
+///
+/// [[animations]]
+/// name = "walk"
+/// frames = [2, 3, 4, 5]
+/// durations = [100, 50, 100, 50]
+/// loop = true # optional, default true
+/// ping_pong = true # optional, default false
+/// ```
+///
+/// In Aseprite data files, frames are read from the list of frames, which can
+/// be in either array or hash format, and must all have the same size.
+/// Animations are read from the frame tags, and their durations from the
+/// frames.
+/// Tags are looping unless they have a repeat count.
+///
+///
+/// Aseprite layers
+/// ----
+///
+/// Sprites stored as Aseprite files flatten all their visible layers, unless
+/// the names of the layers to flatten are listed.
+/// Their frames and animations are always read from the file itself, so only
+/// the grid and the regions are used from the sidecar file, and only for
+/// files with a single frame.
+///
+/// ```toml
+/// layers = ["body", "outline"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpriteMetadata {
+    layout: Option<AtlasLayout>,
+    frames: Option<FrameLayout>,
+    animations: Vec<Animation>,
+    layers: LayerSelection,
+}
+
+impl SpriteMetadata {
+    /// Parse sprite metadata from TOML text
+    pub fn from_toml(text: &str) -> Result<Self, PngToWasm4SrcError> {
+        let raw: RawSpriteMetadata = toml::from_str(text)?;
+        Self::from_raw(raw)
+    }
+
+    /// Parse sprite metadata from an Aseprite JSON data file
+    pub fn from_aseprite_json(text: &str) -> Result<Self, PngToWasm4SrcError> {
+        let raw: RawAsepriteData = serde_json::from_str(text)?;
+        Self::from_raw_aseprite(raw)
+    }
+
+    /// Read the sidecar metadata file of a sprite, if it exists
+    ///
+    /// A TOML file takes precedence over an Aseprite data file.
+    pub fn read_sidecar(sprite_path: &Path) -> Result<Option<Self>, PngToWasm4SrcError> {
+        let toml_path = sprite_path.with_extension("toml");
+        let json_path = sprite_path.with_extension("json");
+        if toml_path.is_file() {
+            let text = read_to_string(toml_path)?;
+            Self::from_toml(&text).map(Some)
+        } else if json_path.is_file() {
+            let text = read_to_string(json_path)?;
+            Self::from_aseprite_json(&text).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Return the atlas layout, if any
+    pub fn layout(&self) -> Option<&AtlasLayout> {
+        self.layout.as_ref()
+    }
+
+    /// Return the animations
+    pub fn animations(&self) -> &[Animation] {
+        self.animations.as_ref()
+    }
+
+    /// Return the layers to flatten in Aseprite files
+    pub fn layers(&self) -> &LayerSelection {
+        &self.layers
+    }
+
+    /// Return the position of frames, if the sprite is a sprite sheet
+    pub(crate) fn frames(&self) -> Option<&FrameLayout> {
+        self.frames.as_ref()
+    }
+}
+
+/// Return the paths of the possible sidecar metadata files of a sprite
+pub(crate) fn sidecar_paths(sprite_path: &Path) -> [PathBuf; 2] {
+    [
+        sprite_path.with_extension("toml"),
+        sprite_path.with_extension("json"),
+    ]
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSpriteMetadata {
+    grid: Option<RawGrid>,
+    regions: Option<Vec<RawRegion>>,
+    frames: Option<RawGrid>,
+    #[serde(default)]
+    animations: Vec<RawAnimation>,
+    layers: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawGrid {
+    width: u32,
+    height: u32,
+    #[serde(default)]
+    order: RawFrameOrder,
+    #[serde(default)]
+    names: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RawFrameOrder {
+    RowMajor,
+    ColumnMajor,
+}
+
+impl Default for RawFrameOrder {
+    fn default() -> Self {
+        RawFrameOrder::RowMajor
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRegion {
+    name: String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAnimation {
+    name: String,
+    frames: Vec<usize>,
+    duration: Option<u32>,
+    durations: Option<Vec<u32>>,
+    #[serde(default = "default_looping", rename = "loop")]
+    looping: bool,
+    #[serde(default)]
+    ping_pong: bool,
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct RawAsepriteData {
+    frames: JsonValue,
+    meta: RawAsepriteMeta,
+}
+
+#[derive(Deserialize)]
+struct RawAsepriteFrame {
+    frame: RawAsepriteRect,
+    duration: u32,
+}
+
+#[derive(Deserialize)]
+struct RawAsepriteRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct RawAsepriteMeta {
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<RawAsepriteTag>,
+}
+
+#[derive(Deserialize)]
+struct RawAsepriteTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: RawAsepriteDirection,
+    repeat: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RawAsepriteDirection {
+    Forward,
+    Reverse,
+    Pingpong,
+    PingpongReverse,
+}
+
+impl Default for RawAsepriteDirection {
+    fn default() -> Self {
+        RawAsepriteDirection::Forward
+    }
+}
+
+impl SpriteMetadata {
+    fn from_raw(raw: RawSpriteMetadata) -> Result<Self, PngToWasm4SrcError> {
+        let layouts = [
+            raw.grid.is_some(),
+            raw.regions.is_some(),
+            raw.frames.is_some(),
+        ];
+        if layouts.iter().filter(|defined| **defined).count() > 1 {
+            return Err(PngToWasm4SrcError::ConflictingLayouts);
+        }
+
+        if raw.frames.is_none() && !raw.animations.is_empty() {
+            return Err(PngToWasm4SrcError::AnimationsWithoutFrames);
+        }
+
+        let frames = raw
+            .frames
+            .map(|frames| FrameLayout::Sheet(sprite_sheet_from_raw(frames)));
+
+        let animations = raw
+            .animations
+            .into_iter()
+            .map(animation_from_raw)
+            .collect::<Result<_, _>>()?;
+
+        let layout = match (raw.grid, raw.regions) {
+            (Some(grid), _) => Some(AtlasLayout::Grid(sprite_sheet_from_raw(grid))),
+            (None, Some(regions)) => {
+                let regions = regions
+                    .into_iter()
+                    .map(|region| {
+                        Region::new(region.name, region.x, region.y, region.width, region.height)
+                    })
+                    .collect();
+                Some(AtlasLayout::Regions(regions))
+            }
+            (None, None) => None,
+        };
+
+        let layers = match raw.layers {
+            Some(names) => LayerSelection::Named(names),
+            None => LayerSelection::Visible,
+        };
+
+        Ok(Self {
+            layout,
+            frames,
+            animations,
+            layers,
+        })
+    }
+
+    fn from_raw_aseprite(raw: RawAsepriteData) -> Result<Self, PngToWasm4SrcError> {
+        let frames: Vec<JsonValue> = match raw.frames {
+            JsonValue::Array(frames) => frames,
+            JsonValue::Object(frames) => frames.into_iter().map(|(_, frame)| frame).collect(),
+            _ => Vec::default(),
+        };
+        let frames = frames
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<RawAsepriteFrame>, _>>()?;
+
+        let (frame_width, frame_height) = match frames.first() {
+            Some(frame) => (frame.frame.w, frame.frame.h),
+            None => return Ok(Self::default()),
+        };
+        if frames
+            .iter()
+            .any(|frame| frame.frame.w != frame_width || frame.frame.h != frame_height)
+        {
+            return Err(PngToWasm4SrcError::IrregularFrames);
+        }
+
+        let frame_durations: Vec<u32> = frames.iter().map(|frame| frame.duration).collect();
+        let animations = raw
+            .meta
+            .frame_tags
+            .into_iter()
+            .map(|tag| {
+                let direction = match tag.direction {
+                    RawAsepriteDirection::Forward => TagDirection::Forward,
+                    RawAsepriteDirection::Reverse => TagDirection::Reverse,
+                    RawAsepriteDirection::Pingpong => TagDirection::PingPong,
+                    RawAsepriteDirection::PingpongReverse => TagDirection::PingPongReverse,
+                };
+                let looping = matches!(tag.repeat.as_deref(), None | Some("0"));
+                animation_from_tag(
+                    tag.name,
+                    tag.from,
+                    tag.to,
+                    direction,
+                    looping,
+                    &frame_durations,
+                )
+            })
+            .collect::<Result<_, _>>()?;
+
+        let positions = frames
+            .iter()
+            .map(|frame| (frame.frame.x, frame.frame.y))
+            .collect();
+
+        Ok(Self {
+            layout: None,
+            frames: Some(FrameLayout::Explicit {
+                frame_width,
+                frame_height,
+                positions,
+            }),
+            animations,
+            layers: LayerSelection::default(),
+        })
+    }
+}
+
+fn sprite_sheet_from_raw(grid: RawGrid) -> SpriteSheet {
+    let order = match grid.order {
+        RawFrameOrder::RowMajor => FrameOrder::RowMajor,
+        RawFrameOrder::ColumnMajor => FrameOrder::ColumnMajor,
+    };
+    SpriteSheet::new(grid.width, grid.height)
+        .with_frame_order(order)
+        .with_frame_names(grid.names)
+}
+
+fn animation_from_raw(raw: RawAnimation) -> Result<Animation, PngToWasm4SrcError> {
+    let durations = match (raw.duration, raw.durations) {
+        (Some(duration), None) => vec![millis_to_ticks(duration); raw.frames.len()],
+        (None, Some(durations)) => durations.into_iter().map(millis_to_ticks).collect(),
+        _ => {
+            return Err(PngToWasm4SrcError::InvalidAnimationDurations {
+                animation: raw.name,
+            });
+        }
+    };
+    Ok(Animation::new(raw.name, raw.frames, durations)
+        .with_looping(raw.looping)
+        .with_ping_pong(raw.ping_pong))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_grid() -> Result<(), PngToWasm4SrcError> {
+        let text = "[grid]
+width = 8
+height = 4
+order = \"column-major\"
+names = [\"first\", \"second\"]
+";
+
+        let metadata = SpriteMetadata::from_toml(text)?;
+
+        let expected = SpriteSheet::new(8, 4)
+            .with_frame_order(FrameOrder::ColumnMajor)
+            .with_frame_names(vec!["first", "second"]);
+        assert_eq!(metadata.layout(), Some(&AtlasLayout::Grid(expected)));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_regions() -> Result<(), PngToWasm4SrcError> {
+        let text = "[[regions]]
+name = \"heart\"
+x = 0
+y = 0
+width = 8
+height = 8
+
+[[regions]]
+name = \"door\"
+x = 8
+y = 0
+width = 8
+height = 16
+";
+
+        let metadata = SpriteMetadata::from_toml(text)?;
+
+        let expected = vec![
+            Region::new("heart", 0, 0, 8, 8),
+            Region::new("door", 8, 0, 8, 16),
+        ];
+        assert_eq!(metadata.layout(), Some(&AtlasLayout::Regions(expected)));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_frames_and_animations() -> Result<(), PngToWasm4SrcError> {
+        let text = "[frames]
+width = 8
+height = 8
+
+[[animations]]
+name = \"idle\"
+frames = [0, 1]
+duration = 500
+
+[[animations]]
+name = \"walk\"
+frames = [2, 3]
+durations = [100, 50]
+loop = false
+ping_pong = true
+";
+
+        let metadata = SpriteMetadata::from_toml(text)?;
+
+        let expected = vec![
+            Animation::new("idle", vec![0, 1], vec![30, 30]),
+            Animation::new("walk", vec![2, 3], vec![6, 3])
+                .with_looping(false)
+                .with_ping_pong(true),
+        ];
+        assert_eq!(
+            metadata.frames(),
+            Some(&FrameLayout::Sheet(SpriteSheet::new(8, 8)))
+        );
+        assert_eq!(metadata.animations(), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_layers() -> Result<(), PngToWasm4SrcError> {
+        let text = "layers = [\"body\", \"outline\"]\n";
+
+        let metadata = SpriteMetadata::from_toml(text)?;
+
+        let expected = LayerSelection::Named(vec!["body".into(), "outline".into()]);
+        assert_eq!(metadata.layers(), &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_animations_without_frames() {
+        let text = "[[animations]]
+name = \"idle\"
+frames = [0, 1]
+duration = 500
+";
+
+        let result = SpriteMetadata::from_toml(text);
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::AnimationsWithoutFrames)
+        ));
+    }
+
+    #[test]
+    fn parse_aseprite_json() -> Result<(), PngToWasm4SrcError> {
+        let text = r#"{
+  "frames": {
+    "slime 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 4, "h": 2 }, "duration": 100 },
+    "slime 1.aseprite": { "frame": { "x": 4, "y": 0, "w": 4, "h": 2 }, "duration": 200 },
+    "slime 2.aseprite": { "frame": { "x": 0, "y": 2, "w": 4, "h": 2 }, "duration": 100 }
+  },
+  "meta": {
+    "frameTags": [
+      { "name": "bounce", "from": 0, "to": 2, "direction": "pingpong" },
+      { "name": "melt", "from": 1, "to": 2, "direction": "reverse", "repeat": "1" }
+    ]
+  }
+}"#;
+
+        let metadata = SpriteMetadata::from_aseprite_json(text)?;
+
+        let expected_frames = FrameLayout::Explicit {
+            frame_width: 4,
+            frame_height: 2,
+            positions: vec![(0, 0), (4, 0), (0, 2)],
+        };
+        let expected_animations = vec![
+            Animation::new("bounce", vec![0, 1, 2], vec![6, 12, 6]).with_ping_pong(true),
+            Animation::new("melt", vec![2, 1], vec![6, 12]).with_looping(false),
+        ];
+        assert_eq!(metadata.frames(), Some(&expected_frames));
+        assert_eq!(metadata.animations(), expected_animations);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_grid_and_regions() {
+        let text = "[grid]
+width = 8
+height = 4
+
+[[regions]]
+name = \"heart\"
+x = 0
+y = 0
+width = 8
+height = 8
+";
+
+        let result = SpriteMetadata::from_toml(text);
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::ConflictingLayouts)
+        ));
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use crate::ReferencePalette;
+
+/// Options controlling the conversion of PNG images to WASM-4 sprites
+///
+/// The default options reproduce the behaviour of the WASM-4 command
+/// `w4 png2src`, which only accepts indexed PNG images.
+///
+/// ```
+/// # use png2wasm4src::ConversionOptions;
+/// let options = ConversionOptions::default().with_quantization(true);
+///
+/// assert!(options.quantization());
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ConversionOptions {
+    quantization: bool,
+    color_order: ColorOrder,
+    duplicate_colors: DuplicateColors,
+    palette_compaction: PaletteCompaction,
+    transparent_index: Option<u8>,
+    reference_palette: Option<ReferencePalette>,
+}
+
+impl ConversionOptions {
+    /// Enable or disable colour quantization of non-indexed PNG images
+    ///
+    /// When enabled, truecolor and RGBA images are accepted, and their
+    /// palette is built from the distinct colours they contain, sorted by
+    /// luminance according to the [colour order](Self::with_color_order).
+    /// Images containing more than four distinct colours are still rejected.
+    pub fn with_quantization(mut self, quantization: bool) -> Self {
+        self.quantization = quantization;
+        self
+    }
+
+    /// Return whether non-indexed PNG images are quantized
+    pub fn quantization(&self) -> bool {
+        self.quantization
+    }
+
+    /// Set the order of colours in palettes built from the image content
+    ///
+    /// This order is used for grayscale images and for quantized images,
+    /// which do not have a palette.
+    pub fn with_color_order(mut self, color_order: ColorOrder) -> Self {
+        self.color_order = color_order;
+        self
+    }
+
+    /// Return the order of colours in palettes built from the image content
+    pub fn color_order(&self) -> ColorOrder {
+        self.color_order
+    }
+
+    /// Set how to handle colours appearing multiple times in a palette
+    pub fn with_duplicate_colors(mut self, duplicate_colors: DuplicateColors) -> Self {
+        self.duplicate_colors = duplicate_colors;
+        self
+    }
+
+    /// Return how colours appearing multiple times in a palette are handled
+    pub fn duplicate_colors(&self) -> DuplicateColors {
+        self.duplicate_colors
+    }
+
+    /// Set how palettes with an unsupported size are reduced to the colours
+    /// actually used by the pixels
+    pub fn with_palette_compaction(mut self, palette_compaction: PaletteCompaction) -> Self {
+        self.palette_compaction = palette_compaction;
+        self
+    }
+
+    /// Return how palettes with an unsupported size are reduced
+    pub fn palette_compaction(&self) -> PaletteCompaction {
+        self.palette_compaction
+    }
+
+    /// Set the WASM-4 colour index of the transparent colour
+    ///
+    /// If the image contains a fully transparent colour, it is assigned this
+    /// index, and the colour previously at this index takes its place.
+    /// All fully transparent colours are then merged into one, regardless of
+    /// their RGB components, while without a transparent index they are
+    /// distinct colours.
+    /// Sprites with one bit per pixel are converted to two bits per pixel if
+    /// the index is 2 or 3.
+    pub fn with_transparent_index(mut self, transparent_index: Option<u8>) -> Self {
+        self.transparent_index = transparent_index;
+        self
+    }
+
+    /// Return the WASM-4 colour index of the transparent colour
+    pub fn transparent_index(&self) -> Option<u8> {
+        self.transparent_index
+    }
+
+    /// Set a reference palette to map colours to WASM-4 colour indices
+    ///
+    /// When set, each pixel is assigned the index of its colour in the
+    /// reference palette, and the palette of the PNG image is ignored.
+    /// Any kind of PNG image is accepted, and pixels whose colours do not
+    /// match any reference colour are rejected.
+    pub fn with_reference_palette(mut self, reference_palette: Option<ReferencePalette>) -> Self {
+        self.reference_palette = reference_palette;
+        self
+    }
+
+    /// Return the reference palette, if any
+    pub fn reference_palette(&self) -> Option<&ReferencePalette> {
+        self.reference_palette.as_ref()
+    }
+}
+
+/// Order of colours in palettes built from the image content
+///
+/// Grayscale and quantized images do not have a palette, so their colours are
+/// assigned to WASM-4 colour indices according to their luminance.
+/// Luminance is computed from the RGB components using the ITU-R BT.601
+/// weights, so for grayscale images it matches the gray level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorOrder {
+    /// The darkest colour is assigned index 0, the lightest the highest index
+    DarkestFirst,
+
+    /// The lightest colour is assigned index 0, the darkest the highest index
+    LightestFirst,
+}
+
+impl Default for ColorOrder {
+    fn default() -> Self {
+        ColorOrder::DarkestFirst
+    }
+}
+
+/// Handling of colours appearing multiple times in a palette
+///
+/// Some image editors pad palettes by repeating some colours, typically black.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateColors {
+    /// Merge all entries of a colour into its first entry
+    ///
+    /// Pixels using any of the entries are assigned the same index, and the
+    /// remaining entries are assigned consecutive indices, so that the
+    /// palette has no gaps.
+    /// Duplicate entries do not count towards its size, so a palette of two
+    /// colours padded to four entries results in a sprite with one bit per
+    /// pixel, and a palette of four colours with an interior duplicate is
+    /// accepted.
+    /// Merged entries are reported by
+    /// [`RustVariables::merged_colors()`](crate::RustVariables::merged_colors).
+    Merge,
+
+    /// Reject palettes containing duplicate colours
+    ///
+    /// Conversion fails with [`PngToWasm4SrcError::DuplicatePaletteColor`]
+    /// reporting the first duplicate entry.
+    ///
+    /// [`PngToWasm4SrcError::DuplicatePaletteColor`]: crate::PngToWasm4SrcError::DuplicatePaletteColor
+    Reject,
+}
+
+impl Default for DuplicateColors {
+    fn default() -> Self {
+        DuplicateColors::Merge
+    }
+}
+
+/// Compaction of palettes with an unsupported size
+///
+/// Palettes are only compacted when their size is neither two nor four.
+/// The colours actually used by the pixels are collected, and if there are at
+/// most four of them, they are assigned new WASM-4 colour indices according
+/// to this rule.
+/// Up to two used colours result in a sprite with one bit per pixel, three or
+/// four in a sprite with two bits per pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteCompaction {
+    /// Do not compact palettes, and reject those with an unsupported size
+    Disabled,
+
+    /// Assign indices to the used colours following their palette order
+    ///
+    /// If the used colours are the first ones in the palette, they keep
+    /// their original indices.
+    PaletteOrder,
+
+    /// Assign indices to the used colours according to their luminance
+    ///
+    /// Colours are sorted following the
+    /// [colour order](ConversionOptions::with_color_order).
+    Luminance,
+}
+
+impl Default for PaletteCompaction {
+    fn default() -> Self {
+        PaletteCompaction::Disabled
+    }
+}
@@ -0,0 +1,73 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::fs::{read, write};
+use std::io::ErrorKind as IoErrorKind;
+use std::path::Path;
+
+use crate::PngToWasm4SrcError;
+
+/// Write generated code to a file, unless the file already contains it
+///
+/// Return whether the file was written.
+/// When the content is unchanged the file is left untouched, so its
+/// modification time does not change, and cargo does not recompile the
+/// crates including it.
+///
+/// ```no_run
+/// # use png2wasm4src::{build_sprite_modules_tree, write_if_changed};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let module = build_sprite_modules_tree("assets/sprites")?.parse()?;
+/// if write_if_changed("src/sprites.rs", &module.to_string())? {
+///     println!("Sprites were regenerated");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_if_changed<P>(path: P, contents: &str) -> Result<bool, PngToWasm4SrcError>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    match read(path) {
+        Ok(existing) if existing == contents.as_bytes() => return Ok(false),
+        Ok(_) => {}
+        Err(error) if error.kind() == IoErrorKind::NotFound => {}
+        Err(error) => return Err(error.into()),
+    }
+    write(path, contents)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, metadata, read_to_string, remove_file};
+
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn write_only_changed_content() -> Result<()> {
+        let directory = temp_dir().join("png2wasm4src-write-if-changed");
+        create_dir_all(&directory)?;
+        let path = directory.join("sprites.rs");
+        if path.exists() {
+            remove_file(&path)?;
+        }
+
+        assert!(write_if_changed(&path, "const A: u32 = 1;\n")?);
+        let modified = metadata(&path)?.modified()?;
+
+        assert!(!write_if_changed(&path, "const A: u32 = 1;\n")?);
+        assert_eq!(metadata(&path)?.modified()?, modified);
+
+        assert!(write_if_changed(&path, "const A: u32 = 2;\n")?);
+        assert_eq!(read_to_string(&path)?, "const A: u32 = 2;\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+/// A reference palette mapping colours to WASM-4 colour indices
+///
+/// A reference palette is an ordered list of up to four RGB colours, typically
+/// the same colours as the game's `PALETTE`.
+/// When converting a sprite using a reference palette, each pixel is assigned
+/// the index of the matching colour in the reference palette, regardless of
+/// the order of colours in the PNG file.
+///
+/// Colours are expressed as `0xRRGGBB`, the same format used by WASM-4.
+///
+/// ```
+/// # use png2wasm4src::{ColorMatching, ReferencePalette};
+/// let palette = ReferencePalette::new(vec![0xe0f8cf, 0x86c06c, 0x306850, 0x071821])
+///     .with_matching(ColorMatching::Nearest { max_distance: 16 });
+///
+/// assert_eq!(palette.find(0x86c06c), Some(1));
+/// assert_eq!(palette.find(0x88c06a), Some(1));
+/// assert_eq!(palette.find(0xff0000), None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferencePalette {
+    colors: Vec<u32>,
+    matching: ColorMatching,
+}
+
+/// Matching of sprite colours to reference palette colours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMatching {
+    /// Only match identical colours
+    Exact,
+
+    /// Match the nearest colour, if close enough
+    ///
+    /// The distance between two colours is the Euclidean distance of their
+    /// RGB components.
+    /// When two reference colours are at the same distance, the one with the
+    /// lowest index is chosen.
+    Nearest {
+        /// The maximal distance between matching colours
+        max_distance: u32,
+    },
+}
+
+impl Default for ColorMatching {
+    fn default() -> Self {
+        ColorMatching::Exact
+    }
+}
+
+impl ReferencePalette {
+    /// Create a reference palette from a list of `0xRRGGBB` colours
+    ///
+    /// Colours are matched exactly by default.
+    pub fn new<C>(colors: C) -> Self
+    where
+        C: IntoIterator<Item = u32>,
+    {
+        Self {
+            colors: colors.into_iter().collect(),
+            matching: ColorMatching::default(),
+        }
+    }
+
+    /// Set how sprite colours are matched to reference colours
+    pub fn with_matching(mut self, matching: ColorMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    /// Return the reference colours
+    pub fn colors(&self) -> &[u32] {
+        self.colors.as_ref()
+    }
+
+    /// Return how sprite colours are matched to reference colours
+    pub fn matching(&self) -> ColorMatching {
+        self.matching
+    }
+
+    /// Find the index of the reference colour matching a `0xRRGGBB` colour
+    pub fn find(&self, color: u32) -> Option<usize> {
+        match self.matching {
+            ColorMatching::Exact => self.colors.iter().position(|c| *c == color),
+            ColorMatching::Nearest { max_distance } => self
+                .colors
+                .iter()
+                .map(|c| squared_distance(*c, color))
+                .enumerate()
+                .filter(|(_, distance)| *distance <= max_distance.saturating_mul(max_distance))
+                .min_by_key(|(index, distance)| (*distance, *index))
+                .map(|(index, _)| index),
+        }
+    }
+}
+
+/// A palette entry merged into an earlier entry with the same colour
+///
+/// Merged entries are reported on the converted sprite, see
+/// [`RustVariables::merged_colors()`](crate::RustVariables::merged_colors).
+///
+/// ```
+/// # use png2wasm4src::MergedColor;
+/// let merged_color = MergedColor::new(0x000000, 1, 0);
+///
+/// assert_eq!(merged_color.color(), 0x000000);
+/// assert_eq!(merged_color.index(), 1);
+/// assert_eq!(merged_color.merged_index(), 0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MergedColor {
+    color: u32,
+    index: usize,
+    merged_index: usize,
+}
+
+impl MergedColor {
+    /// Create an instance
+    pub fn new(color: u32, index: usize, merged_index: usize) -> Self {
+        Self {
+            color,
+            index,
+            merged_index,
+        }
+    }
+
+    /// Return the colour of the entry, as `0xRRGGBB`
+    pub fn color(&self) -> u32 {
+        self.color
+    }
+
+    /// Return the index of the entry in the palette of the image
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Return the WASM-4 colour index assigned to the pixels using the entry
+    pub fn merged_index(&self) -> usize {
+        self.merged_index
+    }
+}
+
+fn squared_distance(first: u32, second: u32) -> u32 {
+    [16, 8, 0]
+        .iter()
+        .map(|shift| {
+            let a = (first >> shift) & 0xff;
+            let b = (second >> shift) & 0xff;
+            let difference = a.max(b) - a.min(b);
+            difference * difference
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_exact() {
+        let palette = ReferencePalette::new(vec![0x000000, 0x555555, 0xaaaaaa, 0xffffff]);
+
+        assert_eq!(palette.find(0x000000), Some(0));
+        assert_eq!(palette.find(0xaaaaaa), Some(2));
+        assert_eq!(palette.find(0xababab), None);
+    }
+
+    #[test]
+    fn find_nearest() {
+        let palette = ReferencePalette::new(vec![0x000000, 0x555555, 0xaaaaaa, 0xffffff])
+            .with_matching(ColorMatching::Nearest { max_distance: 8 });
+
+        assert_eq!(palette.find(0x000000), Some(0));
+        assert_eq!(palette.find(0xababab), Some(2));
+        assert_eq!(palette.find(0x575653), Some(1));
+        assert_eq!(palette.find(0x808080), None);
+    }
+
+    #[test]
+    fn find_nearest_tie() {
+        let palette = ReferencePalette::new(vec![0x000000, 0x000004])
+            .with_matching(ColorMatching::Nearest { max_distance: 8 });
+
+        assert_eq!(palette.find(0x000002), Some(0));
+    }
+}
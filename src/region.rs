@@ -0,0 +1,154 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use crate::{PngToWasm4SrcError, SpriteSheet};
+
+/// A named rectangular region of a sprite atlas
+///
+/// Regions are drawn with the WASM-4 function `blit_sub`, which takes the
+/// position of the top-left corner of the region in the atlas, and the region
+/// size.
+///
+/// ```
+/// # use png2wasm4src::Region;
+/// let region = Region::new("heart", 16, 0, 8, 8);
+///
+/// assert_eq!(region.name(), "heart");
+/// assert_eq!(region.x(), 16);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Region {
+    name: String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Region {
+    /// Create a region from its name, its top-left corner and its size
+    pub fn new(name: impl Into<String>, x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            name: name.into(),
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Return the region name
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    /// Return the horizontal coordinate of the top-left corner
+    pub fn x(&self) -> u32 {
+        self.x
+    }
+
+    /// Return the vertical coordinate of the top-left corner
+    pub fn y(&self) -> u32 {
+        self.y
+    }
+
+    /// Return the region width
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Return the region height
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+/// The layout of regions in a sprite atlas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AtlasLayout {
+    /// Regions are the cells of a regular grid
+    ///
+    /// Cells are named after the [frame names](SpriteSheet::with_frame_names)
+    /// if any, otherwise after their index.
+    Grid(SpriteSheet),
+
+    /// Regions are listed explicitly
+    Regions(Vec<Region>),
+}
+
+impl AtlasLayout {
+    /// Return the regions in an atlas of a given size
+    ///
+    /// All regions must lie within the atlas.
+    pub(crate) fn regions(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<Region>, PngToWasm4SrcError> {
+        let regions = match self {
+            AtlasLayout::Grid(sheet) => {
+                let positions = sheet.frame_positions(width, height)?;
+                let names: Vec<String> = if sheet.frame_names().is_empty() {
+                    (0..positions.len())
+                        .map(|index| index.to_string())
+                        .collect()
+                } else {
+                    sheet.frame_names().to_vec()
+                };
+                names
+                    .into_iter()
+                    .zip(positions)
+                    .map(|(name, (x, y))| {
+                        Region::new(name, x, y, sheet.frame_width(), sheet.frame_height())
+                    })
+                    .collect()
+            }
+            AtlasLayout::Regions(regions) => regions.clone(),
+        };
+
+        for region in &regions {
+            if region.x.saturating_add(region.width) > width
+                || region.y.saturating_add(region.height) > height
+            {
+                return Err(PngToWasm4SrcError::RegionOutOfBounds {
+                    name: region.name.clone(),
+                });
+            }
+        }
+
+        Ok(regions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_regions() -> Result<(), PngToWasm4SrcError> {
+        let layout = AtlasLayout::Grid(SpriteSheet::new(8, 4));
+
+        let regions = layout.regions(16, 4)?;
+
+        let expected = vec![Region::new("0", 0, 0, 8, 4), Region::new("1", 8, 0, 8, 4)];
+        assert_eq!(regions, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn region_out_of_bounds() {
+        let layout = AtlasLayout::Regions(vec![
+            Region::new("inside", 0, 0, 8, 8),
+            Region::new("outside", 4, 4, 8, 8),
+        ]);
+
+        let result = layout.regions(8, 8);
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::RegionOutOfBounds { name }) if name == "outside"
+        ));
+    }
+}
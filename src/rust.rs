@@ -5,7 +5,9 @@
 
 use std::fmt;
 
-use crate::{sanitize_variable_name, Flags};
+use crate::{
+    Animation, Backend, CBackend, CCode, Flags, Language, MergedColor, Region, SourceCode,
+};
 
 /// A group of Rust variables defining a WASM-4 sprite
 ///
@@ -32,6 +34,35 @@ use crate::{sanitize_variable_name, Flags};
 /// );
 /// ```
 ///
+/// When the sprite has a [transparent colour index](RustVariables::transparent_index),
+/// an additional constant `NAME_TRANSPARENT_INDEX` is generated after the
+/// flags.
+///
+/// When the sprite is an atlas with [regions](RustVariables::regions), four
+/// additional constants are generated after the data for each region, to be
+/// passed to the WASM-4 function `blit_sub`.
+///
+/// ```
+/// # use png2wasm4src::{Flags, Region, RustVariables};
+/// let variables = RustVariables::new(
+///     "name", 16, 8, Flags::OneBitPerPixel,
+///     vec![0x00; 16],
+/// )
+/// .with_regions(vec![Region::new("heart", 8, 0, 8, 8)]);
+///
+/// assert_eq!(
+///     format!("{}", variables),
+///     "const NAME_WIDTH: u32 = 16;
+/// const NAME_HEIGHT: u32 = 8;
+/// const NAME_FLAGS: u32 = 0; // BLIT_1BPP
+/// const NAME: [u8; 16] = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+/// const NAME_HEART_SRC_X: u32 = 8;
+/// const NAME_HEART_SRC_Y: u32 = 0;
+/// const NAME_HEART_WIDTH: u32 = 8;
+/// const NAME_HEART_HEIGHT: u32 = 8;\n",
+/// );
+/// ```
+///
 /// ### Alternate Form
 ///
 /// Passing the `#` flag to the `format!` macro will typeset the image data in
@@ -59,6 +90,9 @@ pub struct RustVariables {
     height: u32,
     flags: Flags,
     data: Vec<u8>,
+    transparent_index: Option<u8>,
+    merged_colors: Vec<MergedColor>,
+    regions: Vec<Region>,
 }
 
 impl RustVariables {
@@ -76,9 +110,30 @@ impl RustVariables {
             height,
             flags,
             data,
+            transparent_index: None,
+            merged_colors: Vec::default(),
+            regions: Vec::default(),
         }
     }
 
+    /// Set the colour index of the transparent colour
+    pub fn with_transparent_index(mut self, transparent_index: Option<u8>) -> Self {
+        self.transparent_index = transparent_index;
+        self
+    }
+
+    /// Set the palette entries merged into other entries
+    pub fn with_merged_colors(mut self, merged_colors: Vec<MergedColor>) -> Self {
+        self.merged_colors = merged_colors;
+        self
+    }
+
+    /// Set the atlas regions
+    pub fn with_regions(mut self, regions: Vec<Region>) -> Self {
+        self.regions = regions;
+        self
+    }
+
     /// Return the variables prefix
     pub fn name(&self) -> &str {
         self.name.as_ref()
@@ -103,32 +158,449 @@ impl RustVariables {
     pub fn data(&self) -> &[u8] {
         self.data.as_ref()
     }
+
+    /// Return the colour index of the transparent colour, if any
+    ///
+    /// It is only set when the conversion was requested to place the
+    /// transparent colour at a [fixed index](crate::ConversionOptions::with_transparent_index),
+    /// and the sprite actually contains transparent pixels.
+    pub fn transparent_index(&self) -> Option<u8> {
+        self.transparent_index
+    }
+
+    /// Return the palette entries merged into other entries with the same
+    /// colour
+    ///
+    /// Entries are only merged when [duplicate colours](crate::DuplicateColors::Merge)
+    /// are merged, and entries removed by [palette compaction](crate::PaletteCompaction)
+    /// are not reported.
+    pub fn merged_colors(&self) -> &[MergedColor] {
+        self.merged_colors.as_ref()
+    }
+
+    /// Return the atlas regions
+    pub fn regions(&self) -> &[Region] {
+        self.regions.as_ref()
+    }
+
+    /// Generate source code using an output backend
+    ///
+    /// See [`Backend`] and [`Language`] for details.
+    pub fn to_source<B: Backend>(&self, backend: B) -> SourceCode<'_, B, Self> {
+        SourceCode::new(backend, self)
+    }
+
+    /// Generate C source code, the same as `w4 png2src --c`
+    ///
+    /// See [`CCode`] for details.
+    pub fn to_c(&self) -> CCode<'_, Self> {
+        self.to_source(CBackend::default())
+    }
 }
 
 impl fmt::Display for RustVariables {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let name = sanitize_variable_name(&self.name);
-        writeln!(f, "const {}_WIDTH: u32 = {};", name, self.width)?;
-        writeln!(f, "const {}_HEIGHT: u32 = {};", name, self.height)?;
-        write!(f, "const {}_FLAGS: u32 = {};", name, self.flags.value())?;
-        writeln!(f, " // {}", self.flags.human_readable_value())?;
-        write!(f, "const {}: [u8; {}] = [", name, self.data.len())?;
-        if let Some(byte) = self.data.first() {
-            if f.alternate() {
-                write!(f, "{:#010b}", byte)?;
-            } else {
-                write!(f, "{:#04x}", byte)?;
-            }
+        self.to_source(Language::Rust).fmt(f)
+    }
+}
+
+/// A group of Rust variables defining the frames of a WASM-4 sprite sheet
+///
+/// All frames share the same size and flags, and their data are stored in a
+/// single array of arrays.
+///
+/// Generating Rust source code
+/// ----
+///
+/// The group can be converted to actual Rust source code using the function
+/// [`std::string::ToString::to_string()`], or the macro [`format!`].
+/// A constant with the frame index is generated for each named frame.
+///
+/// ```
+/// # use png2wasm4src::{Flags, RustFrames};
+/// let frames = RustFrames::new(
+///     "name", 4, 2, Flags::OneBitPerPixel,
+///     vec![vec![0x0f], vec![0xf0]],
+/// )
+/// .with_frame_names(vec!["open", "closed"]);
+///
+/// assert_eq!(
+///     format!("{}", frames),
+///     "const NAME_WIDTH: u32 = 4;
+/// const NAME_HEIGHT: u32 = 2;
+/// const NAME_FLAGS: u32 = 0; // BLIT_1BPP
+/// const NAME_FRAME_OPEN: usize = 0;
+/// const NAME_FRAME_CLOSED: usize = 1;
+/// const NAME_FRAMES: [[u8; 1]; 2] = [[0x0f], [0xf0]];\n",
+/// );
+/// ```
+///
+/// Like [`RustVariables`], the alternate form `{:#}` typesets the frames data
+/// in binary format.
+///
+/// For each [animation](RustFrames::animations), constants are generated after
+/// the frames data for the number of frames, the frame indices, their
+/// durations in ticks, and the looping and ping-pong flags.
+///
+/// ```
+/// # use png2wasm4src::{Animation, Flags, RustFrames};
+/// let frames = RustFrames::new(
+///     "name", 4, 2, Flags::OneBitPerPixel,
+///     vec![vec![0x0f], vec![0xf0]],
+/// )
+/// .with_animations(vec![Animation::new("blink", vec![0, 1], vec![30, 6])]);
+///
+/// assert_eq!(
+///     format!("{}", frames),
+///     "const NAME_WIDTH: u32 = 4;
+/// const NAME_HEIGHT: u32 = 2;
+/// const NAME_FLAGS: u32 = 0; // BLIT_1BPP
+/// const NAME_FRAMES: [[u8; 1]; 2] = [[0x0f], [0xf0]];
+/// const NAME_BLINK_FRAME_COUNT: usize = 2;
+/// const NAME_BLINK_FRAMES: [usize; 2] = [0, 1];
+/// const NAME_BLINK_DURATIONS: [u32; 2] = [30, 6];
+/// const NAME_BLINK_LOOP: bool = true;
+/// const NAME_BLINK_PING_PONG: bool = false;\n",
+/// );
+/// ```
+///
+/// If [frame durations](RustFrames::frame_durations) are set, such as for
+/// frames decoded from animated images, they are generated after the frames
+/// data.
+///
+/// ```
+/// # use png2wasm4src::{Flags, RustFrames};
+/// let frames = RustFrames::new(
+///     "name", 4, 2, Flags::OneBitPerPixel,
+///     vec![vec![0x0f], vec![0xf0]],
+/// )
+/// .with_frame_durations(vec![30, 6]);
+///
+/// assert_eq!(
+///     format!("{}", frames),
+///     "const NAME_WIDTH: u32 = 4;
+/// const NAME_HEIGHT: u32 = 2;
+/// const NAME_FLAGS: u32 = 0; // BLIT_1BPP
+/// const NAME_FRAMES: [[u8; 1]; 2] = [[0x0f], [0xf0]];
+/// const NAME_FRAME_DURATIONS: [u32; 2] = [30, 6];\n",
+/// );
+/// ```
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RustFrames {
+    name: String,
+    width: u32,
+    height: u32,
+    flags: Flags,
+    frames: Vec<Vec<u8>>,
+    frame_names: Vec<String>,
+    transparent_index: Option<u8>,
+    merged_colors: Vec<MergedColor>,
+    frame_durations: Vec<u32>,
+    animations: Vec<Animation>,
+}
+
+impl RustFrames {
+    /// Create an instance
+    ///
+    /// All frames must have the same encoded size.
+    pub fn new(
+        name: impl Into<String>,
+        width: u32,
+        height: u32,
+        flags: Flags,
+        frames: Vec<Vec<u8>>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            width,
+            height,
+            flags,
+            frames,
+            frame_names: Vec::default(),
+            transparent_index: None,
+            merged_colors: Vec::default(),
+            frame_durations: Vec::default(),
+            animations: Vec::default(),
         }
-        for byte in self.data.iter().skip(1) {
-            if f.alternate() {
-                write!(f, ", {:#010b}", byte)?;
-            } else {
-                write!(f, ", {:#04x}", byte)?;
-            }
+    }
+
+    /// Set the names of frames
+    pub fn with_frame_names<N, S>(mut self, frame_names: N) -> Self
+    where
+        N: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.frame_names = frame_names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the colour index of the transparent colour
+    pub fn with_transparent_index(mut self, transparent_index: Option<u8>) -> Self {
+        self.transparent_index = transparent_index;
+        self
+    }
+
+    /// Set the palette entries merged into other entries
+    pub fn with_merged_colors(mut self, merged_colors: Vec<MergedColor>) -> Self {
+        self.merged_colors = merged_colors;
+        self
+    }
+
+    /// Set the duration of each frame in ticks
+    pub fn with_frame_durations(mut self, frame_durations: Vec<u32>) -> Self {
+        self.frame_durations = frame_durations;
+        self
+    }
+
+    /// Set the animations
+    pub fn with_animations(mut self, animations: Vec<Animation>) -> Self {
+        self.animations = animations;
+        self
+    }
+
+    /// Return the variables prefix
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    /// Return the frame width
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Return the frame height
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Return the frames flags (only for bit depth)
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    /// Return the encoded data of each frame
+    pub fn frames(&self) -> &[Vec<u8>] {
+        self.frames.as_ref()
+    }
+
+    /// Return the names of frames
+    pub fn frame_names(&self) -> &[String] {
+        self.frame_names.as_ref()
+    }
+
+    /// Return the colour index of the transparent colour, if any
+    pub fn transparent_index(&self) -> Option<u8> {
+        self.transparent_index
+    }
+
+    /// Return the palette entries merged into other entries with the same
+    /// colour
+    ///
+    /// See [`RustVariables::merged_colors()`] for details.
+    pub fn merged_colors(&self) -> &[MergedColor] {
+        self.merged_colors.as_ref()
+    }
+
+    /// Return the duration of each frame in ticks, if set
+    pub fn frame_durations(&self) -> &[u32] {
+        self.frame_durations.as_ref()
+    }
+
+    /// Return the animations
+    pub fn animations(&self) -> &[Animation] {
+        self.animations.as_ref()
+    }
+
+    /// Generate source code using an output backend
+    ///
+    /// See [`Backend`] and [`Language`] for details.
+    pub fn to_source<B: Backend>(&self, backend: B) -> SourceCode<'_, B, Self> {
+        SourceCode::new(backend, self)
+    }
+
+    /// Generate C source code, the same as `w4 png2src --c`
+    ///
+    /// See [`CCode`] for details.
+    pub fn to_c(&self) -> CCode<'_, Self> {
+        self.to_source(CBackend::default())
+    }
+}
+
+impl fmt::Display for RustFrames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_source(Language::Rust).fmt(f)
+    }
+}
+
+/// A group of Rust variables defining an atlas of packed WASM-4 sprites
+///
+/// Atlases are created by [packing](crate::ParsedModule::pack_atlases) all
+/// sprites with the same bit depth in a module.
+///
+/// Generating Rust source code
+/// ----
+///
+/// The atlas can be converted to actual Rust source code using the function
+/// [`std::string::ToString::to_string()`], or the macro [`format!`].
+/// The atlas data is generated once, as a `static`, and each sprite keeps its
+/// constants for width, height and flags, but it has no data constant.
+/// Instead, three additional constants are generated for each sprite, to be
+/// passed to the WASM-4 function `blit_sub` together with the atlas data: the
+/// position of the sprite in the atlas (`NAME_SRC_X` and `NAME_SRC_Y`), and
+/// the atlas width (`NAME_STRIDE`).
+/// Calls to `blit` written for the unpacked sprite do not compile anymore,
+/// since drawing the atlas data with `blit` would show the wrong pixels.
+///
+/// ```plain
+/// const ATLAS_2BPP_WIDTH: u32 = 8;
+/// const ATLAS_2BPP_HEIGHT: u32 = 4;
+/// const ATLAS_2BPP_FLAGS: u32 = 1; // BLIT_2BPP
+/// static ATLAS_2BPP: [u8; 8] = [0x5a, 0x00, 0x5a, 0x00, 0xf0, 0x00, 0xf0, 0x00];
+/// const PLAYER_WIDTH: u32 = 4;
+/// const PLAYER_HEIGHT: u32 = 4;
+/// const PLAYER_FLAGS: u32 = 1; // BLIT_2BPP
+/// const PLAYER_SRC_X: u32 = 0;
+/// const PLAYER_SRC_Y: u32 = 0;
+/// const PLAYER_STRIDE: u32 = 8;
+/// ```
+///
+/// A sprite is then drawn with
+/// `blit_sub(&ATLAS_2BPP, x, y, PLAYER_WIDTH, PLAYER_HEIGHT, PLAYER_SRC_X, PLAYER_SRC_Y, PLAYER_STRIDE, PLAYER_FLAGS)`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RustAtlas {
+    atlas: RustVariables,
+    sprites: Vec<PackedSprite>,
+}
+
+/// A sprite packed in an atlas
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PackedSprite {
+    variables: RustVariables,
+    x: u32,
+    y: u32,
+}
+
+impl RustAtlas {
+    pub(crate) fn new(atlas: RustVariables, sprites: Vec<PackedSprite>) -> Self {
+        Self { atlas, sprites }
+    }
+
+    /// Return the variables of the whole atlas
+    pub fn atlas(&self) -> &RustVariables {
+        &self.atlas
+    }
+
+    /// Return the sprites packed in the atlas
+    pub fn sprites(&self) -> &[PackedSprite] {
+        self.sprites.as_ref()
+    }
+
+    /// Generate source code using an output backend
+    ///
+    /// See [`Backend`] and [`Language`] for details.
+    pub fn to_source<B: Backend>(&self, backend: B) -> SourceCode<'_, B, Self> {
+        SourceCode::new(backend, self)
+    }
+
+    /// Generate C source code, the same as `w4 png2src --c`
+    ///
+    /// See [`CCode`] for details.
+    pub fn to_c(&self) -> CCode<'_, Self> {
+        self.to_source(CBackend::default())
+    }
+}
+
+impl PackedSprite {
+    pub(crate) fn new(variables: RustVariables, x: u32, y: u32) -> Self {
+        Self { variables, x, y }
+    }
+
+    /// Return the original variables of the sprite
+    pub fn variables(&self) -> &RustVariables {
+        &self.variables
+    }
+
+    /// Return the horizontal coordinate of the sprite in the atlas
+    pub fn x(&self) -> u32 {
+        self.x
+    }
+
+    /// Return the vertical coordinate of the sprite in the atlas
+    pub fn y(&self) -> u32 {
+        self.y
+    }
+}
+
+impl fmt::Display for RustAtlas {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_source(Language::Rust).fmt(f)
+    }
+}
+
+/// A Rust constant defining a WASM-4 palette
+///
+/// Generating Rust source code
+/// ----
+///
+/// The palette can be converted to actual Rust source code using the function
+/// [`std::string::ToString::to_string()`], or the macro [`format!`].
+/// Colours are expressed as `0xRRGGBB`, the same format used by the WASM-4
+/// `PALETTE` register.
+///
+/// ```
+/// # use png2wasm4src::RustPalette;
+/// let palette = RustPalette::new("name", [0xe0f8cf, 0x86c06c, 0x306850, 0x071821]);
+///
+/// assert_eq!(
+///     format!("{}", palette),
+///     "const NAME_PALETTE: [u32; 4] = [0xe0f8cf, 0x86c06c, 0x306850, 0x071821];\n",
+/// );
+/// ```
+///
+/// An empty name generates a constant named `PALETTE`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RustPalette {
+    name: String,
+    colors: [u32; 4],
+}
+
+impl RustPalette {
+    /// Create an instance
+    pub fn new(name: impl Into<String>, colors: [u32; 4]) -> Self {
+        Self {
+            name: name.into(),
+            colors,
         }
-        writeln!(f, "];")?;
-        Ok(())
+    }
+
+    /// Return the constant prefix
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    /// Return the palette colours
+    pub fn colors(&self) -> [u32; 4] {
+        self.colors
+    }
+
+    /// Generate source code using an output backend
+    ///
+    /// See [`Backend`] and [`Language`] for details.
+    pub fn to_source<B: Backend>(&self, backend: B) -> SourceCode<'_, B, Self> {
+        SourceCode::new(backend, self)
+    }
+
+    /// Generate C source code, the same as `w4 png2src --c`
+    ///
+    /// See [`CCode`] for details.
+    pub fn to_c(&self) -> CCode<'_, Self> {
+        self.to_source(CBackend::default())
+    }
+}
+
+impl fmt::Display for RustPalette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_source(Language::Rust).fmt(f)
     }
 }
 
@@ -213,4 +685,157 @@ const SOME_NAME: [u8; 4] = [0b00000001, 0b00000010, 0b00000100, 0b00011111];\n";
 
         assert_eq!(rust_code, expected);
     }
+
+    #[test]
+    fn to_string_transparent_index() {
+        let rust_variables = RustVariables::new(
+            "some_name",
+            10,
+            12,
+            Flags::TwoBitsPerPixel,
+            vec![0x01, 0x02, 0x04, 0x1f],
+        )
+        .with_transparent_index(Some(0));
+        let rust_code = rust_variables.to_string();
+
+        let expected = "const SOME_NAME_WIDTH: u32 = 10;
+const SOME_NAME_HEIGHT: u32 = 12;
+const SOME_NAME_FLAGS: u32 = 1; // BLIT_2BPP
+const SOME_NAME_TRANSPARENT_INDEX: u32 = 0;
+const SOME_NAME: [u8; 4] = [0x01, 0x02, 0x04, 0x1f];\n";
+
+        assert_eq!(rust_code, expected);
+    }
+
+    #[test]
+    fn to_string_regions() {
+        let rust_variables = RustVariables::new(
+            "some_name",
+            8,
+            4,
+            Flags::OneBitPerPixel,
+            vec![0x01, 0x02, 0x04, 0x1f],
+        )
+        .with_regions(vec![
+            Region::new("top", 0, 0, 8, 2),
+            Region::new("bottom-left", 0, 2, 4, 2),
+        ]);
+        let rust_code = rust_variables.to_string();
+
+        let expected = "const SOME_NAME_WIDTH: u32 = 8;
+const SOME_NAME_HEIGHT: u32 = 4;
+const SOME_NAME_FLAGS: u32 = 0; // BLIT_1BPP
+const SOME_NAME: [u8; 4] = [0x01, 0x02, 0x04, 0x1f];
+const SOME_NAME_TOP_SRC_X: u32 = 0;
+const SOME_NAME_TOP_SRC_Y: u32 = 0;
+const SOME_NAME_TOP_WIDTH: u32 = 8;
+const SOME_NAME_TOP_HEIGHT: u32 = 2;
+const SOME_NAME_BOTTOM_LEFT_SRC_X: u32 = 0;
+const SOME_NAME_BOTTOM_LEFT_SRC_Y: u32 = 2;
+const SOME_NAME_BOTTOM_LEFT_WIDTH: u32 = 4;
+const SOME_NAME_BOTTOM_LEFT_HEIGHT: u32 = 2;\n";
+
+        assert_eq!(rust_code, expected);
+    }
+
+    #[test]
+    fn frames_to_string() {
+        let rust_frames = RustFrames::new(
+            "some_name",
+            8,
+            2,
+            Flags::TwoBitsPerPixel,
+            vec![vec![0x01, 0x02, 0x04, 0x1f], vec![0x1f, 0x04, 0x02, 0x01]],
+        )
+        .with_transparent_index(Some(0));
+        let rust_code = rust_frames.to_string();
+
+        let expected = "const SOME_NAME_WIDTH: u32 = 8;
+const SOME_NAME_HEIGHT: u32 = 2;
+const SOME_NAME_FLAGS: u32 = 1; // BLIT_2BPP
+const SOME_NAME_TRANSPARENT_INDEX: u32 = 0;
+const SOME_NAME_FRAMES: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x1f], [0x1f, 0x04, 0x02, 0x01]];\n";
+
+        assert_eq!(rust_code, expected);
+    }
+
+    #[test]
+    fn frames_format_alternate() {
+        let rust_frames = RustFrames::new(
+            "some_name",
+            4,
+            2,
+            Flags::OneBitPerPixel,
+            vec![vec![0x0f], vec![0xf0]],
+        )
+        .with_frame_names(vec!["first-frame", "second frame"]);
+        let rust_code = format!("{:#}", rust_frames);
+
+        let expected = "const SOME_NAME_WIDTH: u32 = 4;
+const SOME_NAME_HEIGHT: u32 = 2;
+const SOME_NAME_FLAGS: u32 = 0; // BLIT_1BPP
+const SOME_NAME_FRAME_FIRST_FRAME: usize = 0;
+const SOME_NAME_FRAME_SECONDFRAME: usize = 1;
+const SOME_NAME_FRAMES: [[u8; 1]; 2] = [[0b00001111], [0b11110000]];\n";
+
+        assert_eq!(rust_code, expected);
+    }
+
+    #[test]
+    fn atlas_to_string() {
+        let atlas = RustAtlas::new(
+            RustVariables::new(
+                "atlas_1bpp",
+                16,
+                2,
+                Flags::OneBitPerPixel,
+                vec![0x00, 0xff, 0x00, 0xff],
+            ),
+            vec![PackedSprite::new(
+                RustVariables::new("sprite", 8, 2, Flags::OneBitPerPixel, vec![0xff, 0xff])
+                    .with_regions(vec![Region::new("right", 4, 0, 4, 2)]),
+                8,
+                0,
+            )],
+        );
+        let rust_code = atlas.to_string();
+
+        let expected = "const ATLAS_1BPP_WIDTH: u32 = 16;
+const ATLAS_1BPP_HEIGHT: u32 = 2;
+const ATLAS_1BPP_FLAGS: u32 = 0; // BLIT_1BPP
+static ATLAS_1BPP: [u8; 4] = [0x00, 0xff, 0x00, 0xff];
+const SPRITE_WIDTH: u32 = 8;
+const SPRITE_HEIGHT: u32 = 2;
+const SPRITE_FLAGS: u32 = 0; // BLIT_1BPP
+const SPRITE_SRC_X: u32 = 8;
+const SPRITE_SRC_Y: u32 = 0;
+const SPRITE_STRIDE: u32 = 16;
+const SPRITE_RIGHT_SRC_X: u32 = 12;
+const SPRITE_RIGHT_SRC_Y: u32 = 0;
+const SPRITE_RIGHT_WIDTH: u32 = 4;
+const SPRITE_RIGHT_HEIGHT: u32 = 2;\n";
+
+        assert_eq!(rust_code, expected);
+    }
+
+    #[test]
+    fn palette_to_string() {
+        let palette = RustPalette::new("some_name", [0xfff6d3, 0xf9a875, 0xeb6b6f, 0x7c3f58]);
+        let rust_code = palette.to_string();
+
+        let expected =
+            "const SOME_NAME_PALETTE: [u32; 4] = [0xfff6d3, 0xf9a875, 0xeb6b6f, 0x7c3f58];\n";
+
+        assert_eq!(rust_code, expected);
+    }
+
+    #[test]
+    fn palette_without_name_to_string() {
+        let palette = RustPalette::new("", [0x000000, 0x000001, 0x000010, 0x000100]);
+        let rust_code = palette.to_string();
+
+        let expected = "const PALETTE: [u32; 4] = [0x000000, 0x000001, 0x000010, 0x000100];\n";
+
+        assert_eq!(rust_code, expected);
+    }
 }
@@ -0,0 +1,214 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::fmt;
+
+use crate::language::{camel_case, indentation, snake_case, upper_snake_case, write_rust_constant};
+use crate::{Backend, Value};
+
+#[cfg(doc)]
+use crate::Language;
+
+/// The output backend for Rust, with configurable visibility and naming
+///
+/// By default, it generates the same code as [`Language::Rust`].
+///
+/// ```
+/// # use png2wasm4src::{Flags, Naming, RustBackend, RustVariables, Visibility};
+/// let variables = RustVariables::new("name", 8, 1, Flags::OneBitPerPixel, vec![0x0f]);
+/// let backend = RustBackend::default()
+///     .with_visibility(Visibility::Crate)
+///     .with_naming(Naming::SnakeCase);
+///
+/// assert_eq!(
+///     format!("{}", variables.to_source(backend)),
+///     "pub(crate) const name_width: u32 = 8;
+/// pub(crate) const name_height: u32 = 1;
+/// pub(crate) const name_flags: u32 = 0; // BLIT_1BPP
+/// pub(crate) const name: [u8; 1] = [0x0f];\n",
+/// );
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RustBackend {
+    visibility: Visibility,
+    naming: Naming,
+}
+
+impl RustBackend {
+    /// Set the visibility of constants and modules
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Return the visibility of constants and modules
+    pub fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+
+    /// Set the naming convention of constants
+    pub fn with_naming(mut self, naming: Naming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Return the naming convention of constants
+    pub fn naming(&self) -> Naming {
+        self.naming
+    }
+}
+
+impl Backend for RustBackend {
+    fn identifier(&self, name: &str, words: &[&str]) -> String {
+        match self.naming {
+            Naming::UpperSnakeCase => upper_snake_case(name, words),
+            Naming::SnakeCase => snake_case(name, words),
+            Naming::CamelCase => camel_case(name, words),
+        }
+    }
+
+    fn write_constant(
+        &self,
+        f: &mut fmt::Formatter,
+        depth: usize,
+        identifier: &str,
+        value: &Value,
+    ) -> fmt::Result {
+        let visibility = self.visibility.qualifier(depth);
+        write_rust_constant(f, depth, visibility, identifier, value)
+    }
+
+    fn nests_modules(&self) -> bool {
+        true
+    }
+
+    fn write_module_start(&self, f: &mut fmt::Formatter, depth: usize, name: &str) -> fmt::Result {
+        // A module has the same visibility as the items it contains
+        let visibility = self.visibility.qualifier(depth + 1);
+        writeln!(f, "{}{}mod {} {{", indentation(depth), visibility, name)
+    }
+
+    fn write_module_end(&self, f: &mut fmt::Formatter, depth: usize, _name: &str) -> fmt::Result {
+        writeln!(f, "{}}}\n", indentation(depth))
+    }
+}
+
+/// Visibility of generated Rust constants and modules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    /// Items in modules are public, while top-level items are private
+    ///
+    /// Top-level items have no visibility qualifier, so they are only visible
+    /// in the module including the generated code, the same as
+    /// `w4 png2src --rust`.
+    Inherited,
+
+    /// All items are public (`pub`)
+    Public,
+
+    /// All items are visible in the whole crate (`pub(crate)`)
+    Crate,
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility::Inherited
+    }
+}
+
+impl Visibility {
+    /// Return the qualifier of an item at the given depth, such as `pub `
+    pub(crate) fn qualifier(self, depth: usize) -> &'static str {
+        match self {
+            Visibility::Inherited if depth == 0 => "",
+            Visibility::Inherited | Visibility::Public => "pub ",
+            Visibility::Crate => "pub(crate) ",
+        }
+    }
+}
+
+/// Naming convention of generated Rust constants
+///
+/// Names are sanitized in all conventions, so that they are valid
+/// identifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Naming {
+    /// Upper snake case, such as `NAME_SRC_X`, the same as `w4 png2src --rust`
+    UpperSnakeCase,
+
+    /// Snake case, such as `name_src_x`, preserving the case of sprite names
+    SnakeCase,
+
+    /// Camel case, such as `nameSrcX`, preserving the case of sprite names
+    CamelCase,
+}
+
+impl Default for Naming {
+    fn default() -> Self {
+        Naming::UpperSnakeCase
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Flags, Naming, ParsedModule, Region, RustBackend, RustPalette, RustVariables, Visibility,
+    };
+
+    #[test]
+    fn public_camel_case_module() {
+        let variables = RustVariables::new("player", 4, 1, Flags::OneBitPerPixel, vec![0x0f])
+            .with_regions(vec![Region::new("head", 0, 0, 4, 1)]);
+        let module = ParsedModule::new("sprites", vec![variables], Vec::default()).with_palette(
+            RustPalette::new("", [0x000000, 0x555555, 0xaaaaaa, 0xffffff]),
+        );
+        let backend = RustBackend::default()
+            .with_visibility(Visibility::Public)
+            .with_naming(Naming::CamelCase);
+        let rust_code = module.to_source(backend).to_string();
+
+        let expected = "pub mod sprites {
+    pub const palette: [u32; 4] = [0x000000, 0x555555, 0xaaaaaa, 0xffffff];
+
+    pub const playerWidth: u32 = 4;
+    pub const playerHeight: u32 = 1;
+    pub const playerFlags: u32 = 0; // BLIT_1BPP
+    pub const player: [u8; 1] = [0x0f];
+    pub const playerHeadSrcX: u32 = 0;
+    pub const playerHeadSrcY: u32 = 0;
+    pub const playerHeadWidth: u32 = 4;
+    pub const playerHeadHeight: u32 = 1;
+
+}
+
+";
+
+        assert_eq!(rust_code, expected);
+    }
+
+    #[test]
+    fn crate_visibility_module() {
+        let variables = RustVariables::new("player", 4, 1, Flags::OneBitPerPixel, vec![0x0f]);
+        let submodule = ParsedModule::new("characters", vec![variables], Vec::default());
+        let module = ParsedModule::new("sprites", Vec::default(), vec![submodule]);
+        let backend = RustBackend::default().with_visibility(Visibility::Crate);
+        let rust_code = module.to_source(backend).to_string();
+
+        let expected = "pub(crate) mod sprites {
+    pub(crate) mod characters {
+        pub(crate) const PLAYER_WIDTH: u32 = 4;
+        pub(crate) const PLAYER_HEIGHT: u32 = 1;
+        pub(crate) const PLAYER_FLAGS: u32 = 0; // BLIT_1BPP
+        pub(crate) const PLAYER: [u8; 1] = [0x0f];
+
+    }
+
+}
+
+";
+
+        assert_eq!(rust_code, expected);
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::fmt;
+
+use crate::language::{indentation, write_bytes};
+use crate::{Backend, Naming, RustBackend, RustVariables, Value, Visibility};
+
+#[cfg(doc)]
+use crate::ParsedModule;
+
+/// The output backend for Rust sprites grouped in a typed struct
+///
+/// Instead of four separate constants for the data, width, height and flags,
+/// each sprite is generated as a single constant of type `Sprite`, so that
+/// call sites only need to refer to one name.
+/// All other constants, such as regions, frames and palettes, are the same
+/// as [`RustBackend`], whose visibility and naming convention apply to all
+/// constants, including sprites.
+///
+/// ```
+/// # use png2wasm4src::{Flags, RustStructBackend, RustVariables};
+/// let variables = RustVariables::new("name", 8, 1, Flags::OneBitPerPixel, vec![0x0f]);
+///
+/// assert_eq!(
+///     format!("{}", variables.to_source(RustStructBackend::default().with_sprite_type("crate::Sprite"))),
+///     "const NAME: crate::Sprite = crate::Sprite {
+///     data: &[0x0f],
+///     width: 8,
+///     height: 1,
+///     flags: 0, // BLIT_1BPP
+/// };\n",
+/// );
+/// ```
+///
+/// By default, the definition of `Sprite` is generated once before all
+/// constants, at the root of the output of a [`ParsedModule`], and constants
+/// in nested modules refer to it through `super::`.
+/// It is public, or visible in the crate with [`Visibility::Crate`].
+/// Otherwise, the path of an existing type with the same public fields can be
+/// specified with [`RustStructBackend::with_sprite_type()`].
+///
+/// [Atlases](crate::RustAtlas) and their packed sprites are the same as [`RustBackend`],
+/// since packed sprites can only be drawn with `blit_sub` from the atlas
+/// data.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RustStructBackend {
+    backend: RustBackend,
+    sprite_type: Option<String>,
+}
+
+impl RustStructBackend {
+    /// Set the visibility of constants and modules
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.backend = self.backend.with_visibility(visibility);
+        self
+    }
+
+    /// Return the visibility of constants and modules
+    pub fn visibility(&self) -> Visibility {
+        self.backend.visibility()
+    }
+
+    /// Set the naming convention of constants
+    pub fn with_naming(mut self, naming: Naming) -> Self {
+        self.backend = self.backend.with_naming(naming);
+        self
+    }
+
+    /// Return the naming convention of constants
+    pub fn naming(&self) -> Naming {
+        self.backend.naming()
+    }
+
+    /// Refer to an existing sprite type instead of generating its definition
+    ///
+    /// The path is used as is in all modules, so it should be absolute, such
+    /// as `crate::graphics::Sprite`.
+    pub fn with_sprite_type(mut self, sprite_type: impl Into<String>) -> Self {
+        self.sprite_type = Some(sprite_type.into());
+        self
+    }
+
+    /// Return the path of the sprite type from a module at the given depth
+    fn sprite_type(&self, depth: usize) -> String {
+        match &self.sprite_type {
+            Some(sprite_type) => sprite_type.clone(),
+            None => format!("{}Sprite", "super::".repeat(depth)),
+        }
+    }
+}
+
+impl Backend for RustStructBackend {
+    fn identifier(&self, name: &str, words: &[&str]) -> String {
+        self.backend.identifier(name, words)
+    }
+
+    fn write_constant(
+        &self,
+        f: &mut fmt::Formatter,
+        depth: usize,
+        identifier: &str,
+        value: &Value,
+    ) -> fmt::Result {
+        self.backend.write_constant(f, depth, identifier, value)
+    }
+
+    fn write_sprite(
+        &self,
+        f: &mut fmt::Formatter,
+        depth: usize,
+        name: &str,
+        variables: &RustVariables,
+    ) -> fmt::Result {
+        if let Some(transparent_index) = variables.transparent_index() {
+            let identifier = self.identifier(name, &["transparent", "index"]);
+            let value = Value::Integer(transparent_index.into());
+            self.write_constant(f, depth, &identifier, &value)?;
+        }
+
+        let indentation = indentation(depth);
+        let sprite_type = self.sprite_type(depth);
+        writeln!(
+            f,
+            "{}{}const {}: {} = {} {{",
+            indentation,
+            self.visibility().qualifier(depth),
+            self.identifier(name, &[]),
+            sprite_type,
+            sprite_type,
+        )?;
+        write!(f, "{}    data: &", indentation)?;
+        write_bytes(f, variables.data(), "[", "]")?;
+        writeln!(f, ",")?;
+        writeln!(f, "{}    width: {},", indentation, variables.width())?;
+        writeln!(f, "{}    height: {},", indentation, variables.height())?;
+        writeln!(
+            f,
+            "{}    flags: {}, // {}",
+            indentation,
+            variables.flags().value(),
+            variables.flags().human_readable_value(),
+        )?;
+        writeln!(f, "{}}};", indentation)
+    }
+
+    fn nests_modules(&self) -> bool {
+        true
+    }
+
+    fn write_module_start(&self, f: &mut fmt::Formatter, depth: usize, name: &str) -> fmt::Result {
+        self.backend.write_module_start(f, depth, name)
+    }
+
+    fn write_module_end(&self, f: &mut fmt::Formatter, depth: usize, name: &str) -> fmt::Result {
+        self.backend.write_module_end(f, depth, name)
+    }
+
+    fn write_prologue(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.sprite_type.is_none() {
+            // The type is visible wherever the constants of nested modules are
+            let visibility = self.visibility().qualifier(1);
+            writeln!(f, "/// A WASM-4 sprite, with its data, size and flags")?;
+            writeln!(f, "#[derive(Clone, Copy, Debug, PartialEq, Eq)]")?;
+            writeln!(f, "{}struct Sprite {{", visibility)?;
+            writeln!(f, "    {}data: &'static [u8],", visibility)?;
+            writeln!(f, "    {}width: u32,", visibility)?;
+            writeln!(f, "    {}height: u32,", visibility)?;
+            writeln!(f, "    {}flags: u32,", visibility)?;
+            writeln!(f, "}}")?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Flags, Naming, Region, RustStructBackend, RustVariables, Visibility};
+
+    #[test]
+    fn variables_to_struct() {
+        let variables = RustVariables::new(
+            "some-name",
+            8,
+            4,
+            Flags::TwoBitsPerPixel,
+            vec![0x01, 0x02, 0x04, 0x1f],
+        )
+        .with_transparent_index(Some(0))
+        .with_regions(vec![Region::new("top", 0, 0, 8, 2)]);
+        let rust_code = variables
+            .to_source(RustStructBackend::default())
+            .to_string();
+
+        let expected = "/// A WASM-4 sprite, with its data, size and flags
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sprite {
+    pub data: &'static [u8],
+    pub width: u32,
+    pub height: u32,
+    pub flags: u32,
+}
+
+const SOME_NAME_TRANSPARENT_INDEX: u32 = 0;
+const SOME_NAME: Sprite = Sprite {
+    data: &[0x01, 0x02, 0x04, 0x1f],
+    width: 8,
+    height: 4,
+    flags: 1, // BLIT_2BPP
+};
+const SOME_NAME_TOP_SRC_X: u32 = 0;
+const SOME_NAME_TOP_SRC_Y: u32 = 0;
+const SOME_NAME_TOP_WIDTH: u32 = 8;
+const SOME_NAME_TOP_HEIGHT: u32 = 2;\n";
+
+        assert_eq!(rust_code, expected);
+    }
+
+    #[test]
+    fn variables_to_struct_with_visibility_and_naming() {
+        let variables = RustVariables::new("some-name", 4, 2, Flags::OneBitPerPixel, vec![0x0f]);
+        let backend = RustStructBackend::default()
+            .with_visibility(Visibility::Crate)
+            .with_naming(Naming::SnakeCase);
+        let rust_code = variables.to_source(backend).to_string();
+
+        let expected = "/// A WASM-4 sprite, with its data, size and flags
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Sprite {
+    pub(crate) data: &'static [u8],
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) flags: u32,
+}
+
+pub(crate) const some_name: Sprite = Sprite {
+    data: &[0x0f],
+    width: 4,
+    height: 2,
+    flags: 0, // BLIT_1BPP
+};\n";
+
+        assert_eq!(rust_code, expected);
+    }
+
+    #[test]
+    fn variables_to_struct_alternate() {
+        let variables = RustVariables::new("name", 4, 2, Flags::OneBitPerPixel, vec![0x0f]);
+        let backend = RustStructBackend::default().with_sprite_type("wasm4::Sprite");
+        let rust_code = format!("{:#}", variables.to_source(backend));
+
+        let expected = "const NAME: wasm4::Sprite = wasm4::Sprite {
+    data: &[0b00001111],
+    width: 4,
+    height: 2,
+    flags: 0, // BLIT_1BPP
+};\n";
+
+        assert_eq!(rust_code, expected);
+    }
+}
@@ -12,6 +12,18 @@ pub fn sanitize_variable_name(name: &str) -> String {
         .collect()
 }
 
+/// Sanitize a name for C, preserving its case
+///
+/// Unlike Rust constants, C variables generated by `w4 png2src --c` keep the
+/// case of the sprite name.
+pub fn sanitize_c_variable_name(name: &str) -> String {
+    name.replace("-", "_")
+        .chars()
+        .skip_while(|c| !c.is_ascii_alphabetic() && *c != '_')
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -57,4 +69,11 @@ mod tests {
         let expected = "SM_VRIABL";
         assert_eq!(sanitized_name, expected);
     }
+
+    #[test]
+    fn c_name_preserves_case() {
+        let sanitized_name = sanitize_c_variable_name("123some-Variable name");
+        let expected = "some_Variablename";
+        assert_eq!(sanitized_name, expected);
+    }
 }
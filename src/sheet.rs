@@ -0,0 +1,259 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use crate::PngToWasm4SrcError;
+
+/// The layout of frames in a sprite sheet
+///
+/// A sprite sheet is a grid of frames of the same size.
+/// Its width and height must be multiples of the frame width and height.
+///
+/// ```
+/// # use png2wasm4src::{FrameOrder, SpriteSheet};
+/// let sheet = SpriteSheet::new(16, 16)
+///     .with_frame_order(FrameOrder::ColumnMajor)
+///     .with_frame_names(vec!["idle", "walk", "jump", "fall"]);
+///
+/// assert_eq!(sheet.frame_width(), 16);
+/// assert_eq!(sheet.frame_names(), &["idle", "walk", "jump", "fall"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteSheet {
+    frame_width: u32,
+    frame_height: u32,
+    frame_order: FrameOrder,
+    frame_names: Vec<String>,
+}
+
+/// Order of frames in a sprite sheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameOrder {
+    /// Frames are numbered from left to right, then from top to bottom
+    RowMajor,
+
+    /// Frames are numbered from top to bottom, then from left to right
+    ColumnMajor,
+}
+
+impl Default for FrameOrder {
+    fn default() -> Self {
+        FrameOrder::RowMajor
+    }
+}
+
+impl SpriteSheet {
+    /// Create a sprite sheet layout from the frame size
+    ///
+    /// Frames are in row-major order and unnamed by default.
+    pub fn new(frame_width: u32, frame_height: u32) -> Self {
+        Self {
+            frame_width,
+            frame_height,
+            frame_order: FrameOrder::default(),
+            frame_names: Vec::default(),
+        }
+    }
+
+    /// Set the order of frames
+    pub fn with_frame_order(mut self, frame_order: FrameOrder) -> Self {
+        self.frame_order = frame_order;
+        self
+    }
+
+    /// Set the names of frames
+    ///
+    /// If any name is given, there must be one name for each frame.
+    /// A constant with the frame index is generated for each name.
+    pub fn with_frame_names<N, S>(mut self, frame_names: N) -> Self
+    where
+        N: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.frame_names = frame_names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Return the frame width
+    pub fn frame_width(&self) -> u32 {
+        self.frame_width
+    }
+
+    /// Return the frame height
+    pub fn frame_height(&self) -> u32 {
+        self.frame_height
+    }
+
+    /// Return the order of frames
+    pub fn frame_order(&self) -> FrameOrder {
+        self.frame_order
+    }
+
+    /// Return the names of frames
+    pub fn frame_names(&self) -> &[String] {
+        self.frame_names.as_ref()
+    }
+
+    /// Return the top-left corners of all frames in a sheet of a given size
+    pub(crate) fn frame_positions(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<(u32, u32)>, PngToWasm4SrcError> {
+        if self.frame_width == 0
+            || self.frame_height == 0
+            || width % self.frame_width != 0
+            || height % self.frame_height != 0
+        {
+            return Err(PngToWasm4SrcError::InvalidFrameSize {
+                frame_width: self.frame_width,
+                frame_height: self.frame_height,
+                width,
+                height,
+            });
+        }
+
+        let columns = width / self.frame_width;
+        let rows = height / self.frame_height;
+
+        let positions: Vec<(u32, u32)> = match self.frame_order {
+            FrameOrder::RowMajor => (0..rows)
+                .flat_map(|row| (0..columns).map(move |column| (column, row)))
+                .collect(),
+            FrameOrder::ColumnMajor => (0..columns)
+                .flat_map(|column| (0..rows).map(move |row| (column, row)))
+                .collect(),
+        };
+
+        if !self.frame_names.is_empty() && self.frame_names.len() != positions.len() {
+            return Err(PngToWasm4SrcError::InvalidFrameNames {
+                names: self.frame_names.len(),
+                frames: positions.len(),
+            });
+        }
+
+        Ok(positions
+            .into_iter()
+            .map(|(column, row)| (column * self.frame_width, row * self.frame_height))
+            .collect())
+    }
+}
+
+/// The position of frames in a sprite sheet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FrameLayout {
+    /// Frames are the cells of a regular grid
+    Sheet(SpriteSheet),
+
+    /// Frames are at explicit positions, such as in Aseprite data files
+    Explicit {
+        frame_width: u32,
+        frame_height: u32,
+        positions: Vec<(u32, u32)>,
+    },
+}
+
+impl FrameLayout {
+    /// Return the size of all frames
+    pub(crate) fn frame_size(&self) -> (u32, u32) {
+        match self {
+            FrameLayout::Sheet(sheet) => (sheet.frame_width, sheet.frame_height),
+            FrameLayout::Explicit {
+                frame_width,
+                frame_height,
+                ..
+            } => (*frame_width, *frame_height),
+        }
+    }
+
+    /// Return the names of frames
+    pub(crate) fn frame_names(&self) -> &[String] {
+        match self {
+            FrameLayout::Sheet(sheet) => sheet.frame_names(),
+            FrameLayout::Explicit { .. } => &[],
+        }
+    }
+
+    /// Return the top-left corners of all frames in a sheet of a given size
+    pub(crate) fn frame_positions(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<(u32, u32)>, PngToWasm4SrcError> {
+        match self {
+            FrameLayout::Sheet(sheet) => sheet.frame_positions(width, height),
+            FrameLayout::Explicit {
+                frame_width,
+                frame_height,
+                positions,
+            } => {
+                let outside = positions.iter().position(|(x, y)| {
+                    x.saturating_add(*frame_width) > width
+                        || y.saturating_add(*frame_height) > height
+                });
+                match outside {
+                    Some(frame) => Err(PngToWasm4SrcError::FrameOutOfBounds { frame }),
+                    None => Ok(positions.clone()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_positions_row_major() -> Result<(), PngToWasm4SrcError> {
+        let sheet = SpriteSheet::new(8, 4);
+
+        let positions = sheet.frame_positions(16, 8)?;
+
+        assert_eq!(positions, vec![(0, 0), (8, 0), (0, 4), (8, 4)]);
+        Ok(())
+    }
+
+    #[test]
+    fn frame_positions_column_major() -> Result<(), PngToWasm4SrcError> {
+        let sheet = SpriteSheet::new(8, 4).with_frame_order(FrameOrder::ColumnMajor);
+
+        let positions = sheet.frame_positions(16, 8)?;
+
+        assert_eq!(positions, vec![(0, 0), (0, 4), (8, 0), (8, 4)]);
+        Ok(())
+    }
+
+    #[test]
+    fn frame_positions_invalid_size() {
+        let sheet = SpriteSheet::new(8, 3);
+
+        let result = sheet.frame_positions(16, 8);
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::InvalidFrameSize {
+                frame_width: 8,
+                frame_height: 3,
+                width: 16,
+                height: 8,
+            })
+        ));
+    }
+
+    #[test]
+    fn frame_positions_invalid_names() {
+        let sheet = SpriteSheet::new(8, 8).with_frame_names(vec!["first", "second", "third"]);
+
+        let result = sheet.frame_positions(16, 8);
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::InvalidFrameNames {
+                names: 3,
+                frames: 2,
+            })
+        ));
+    }
+}
@@ -5,15 +5,29 @@
 
 #![cfg_attr(not(doctest), doc = include_str!("../Readme.md"))]
 
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::io::Cursor;
 
 use image::io::Reader as ImageReader;
 use image::{ImageFormat, Rgba, RgbaImage};
 
 use png::Decoder as PngDecoder;
+use png::{BitDepth, ColorType, Transformations};
 
-use crate::{Flags, PngToWasm4SrcError, RustVariables};
+use crate::animated::{decode_apng, decode_gif, read_gif_palette, DecodedAnimation};
+use crate::animation::millis_to_ticks;
+use crate::aseprite::AsepriteFile;
+use crate::sheet::FrameLayout;
+use crate::{
+    AtlasLayout, ColorOrder, ConversionOptions, DuplicateColors, Flags, LayerSelection,
+    MergedColor, PaletteCompaction, PngToWasm4SrcError, ReferencePalette, RustFrames, RustPalette,
+    RustVariables, SpriteSheet,
+};
+
+/// Value of all fully transparent colours, regardless of their RGB components,
+/// when the transparent colour is placed at a fixed index
+const TRANSPARENT_VALUE: u32 = 0;
 
 /// Convert a PNG image to a struct representing Rust source code
 ///
@@ -50,20 +64,869 @@ pub fn convert_png_to_rust_variables(
     name: &str,
     bytes: &[u8],
 ) -> Result<RustVariables, PngToWasm4SrcError> {
-    let palette = extract_palette(bytes)?;
-    let palette = compute_palette_mapping(&palette);
+    convert_png_to_rust_variables_with_options(name, bytes, &ConversionOptions::default())
+}
+
+/// Convert a PNG image to a struct representing Rust source code using custom options
+///
+/// Parameters
+/// ----
+///
+/// * `name` the variables prefix
+/// * `bytes` the raw PNG image
+/// * `options` the conversion options
+///
+///
+/// Grayscale images
+/// ----
+///
+/// Grayscale images with a bit depth of one or two bits are encoded directly
+/// with the same bit depth, and each gray level is used as colour index.
+/// Grayscale images with higher bit depths, as well as grayscale images with
+/// an alpha channel, are encoded like quantized images.
+/// In both cases colours are assigned according to the
+/// [colour order](ConversionOptions::with_color_order), by default from the
+/// darkest (index 0) to the lightest.
+///
+///
+/// Duplicate colours
+/// ----
+///
+/// By default, palette entries repeating an earlier colour are merged into
+/// the first entry, and trailing duplicate entries are not counted towards
+/// the palette size.
+/// They can be rejected instead by setting
+/// [duplicate colours](ConversionOptions::with_duplicate_colors) to
+/// [`DuplicateColors::Reject`].
+///
+///
+/// Oversized palettes
+/// ----
+///
+/// Many image editors save palettes with 16 or 256 entries, even when only a
+/// few of them are used.
+/// When [palette compaction](ConversionOptions::with_palette_compaction) is
+/// enabled, palettes whose size is neither two nor four are reduced to the
+/// colours actually used by the pixels.
+/// If there are at most four of them, they are assigned new indices according
+/// to the chosen [`PaletteCompaction`] rule.
+///
+///
+/// Transparency
+/// ----
+///
+/// Fully transparent pixels are treated as a single colour, regardless of
+/// their RGB components.
+/// Transparency is read from the `tRNS` chunk for indexed and grayscale
+/// images, and from the alpha channel for RGBA and grayscale+alpha images.
+/// WASM-4 draws a pixel as transparent when its entry in `DRAW_COLORS` is 0,
+/// so the transparent colour can be moved to a fixed
+/// [index](ConversionOptions::with_transparent_index), which is then reported
+/// by [`RustVariables::transparent_index()`].
+///
+///
+/// Reference palette
+/// ----
+///
+/// When a [reference palette](ConversionOptions::with_reference_palette) is
+/// set, the palette of the PNG image is ignored, and each pixel is assigned
+/// the index of the matching reference colour.
+/// Transparent pixels are assigned the
+/// [transparent index](ConversionOptions::with_transparent_index) if set, and
+/// are otherwise matched by their RGB components.
+/// Sprites only using indices 0 and 1 are encoded with one bit per pixel,
+/// other sprites with two bits per pixel.
+/// Pixels whose colour does not match any reference colour are reported as
+/// [`PngToWasm4SrcError::UnmappedColor`].
+///
+///
+/// Colour quantization
+/// ----
+///
+/// When [quantization](ConversionOptions::with_quantization) is enabled,
+/// truecolor and RGBA images are accepted as well.
+/// Their palette is built from the distinct colours in the image, sorted by
+/// luminance according to the [colour order](ConversionOptions::with_color_order).
+/// Images with up to two colours are encoded with one bit per pixel, and
+/// images with three or four colours with two bits per pixel.
+///
+/// ```no_run
+/// # use png2wasm4src::{convert_png_to_rust_variables_with_options, ConversionOptions};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let bytes = std::fs::read("sprite.png")?;
+/// let options = ConversionOptions::default().with_quantization(true);
+/// let variables = convert_png_to_rust_variables_with_options("sprite", &bytes, &options)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn convert_png_to_rust_variables_with_options(
+    name: &str,
+    bytes: &[u8],
+    options: &ConversionOptions,
+) -> Result<RustVariables, PngToWasm4SrcError> {
+    let image = convert_png_to_indexed_image(bytes, options)?;
+    Ok(image.to_rust_variables(name))
+}
 
+/// Convert a PNG sprite sheet to a struct representing Rust source code for its frames
+///
+/// Parameters
+/// ----
+///
+/// * `name` the variables prefix
+/// * `bytes` the raw PNG image
+/// * `sheet` the layout of frames in the sprite sheet
+/// * `options` the conversion options
+///
+/// The whole sheet is converted as a single image, following the same rules
+/// as [`convert_png_to_rust_variables_with_options()`], so all frames share
+/// the same palette and bit depth.
+/// It is then cut into frames of the given size, which are encoded
+/// separately in the order defined by the sheet layout.
+///
+///
+/// Generating Rust source code
+/// ----
+///
+/// The frames can be converted to actual Rust source code using the function
+/// [`std::string::ToString::to_string()`], or the macro [`format!`].
+///
+/// ```no_run
+/// # use png2wasm4src::{convert_png_to_rust_frames, ConversionOptions, SpriteSheet};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let bytes = std::fs::read("player.png")?;
+/// let sheet = SpriteSheet::new(16, 16).with_frame_names(vec!["idle", "walk"]);
+/// let frames = convert_png_to_rust_frames("player", &bytes, &sheet, &ConversionOptions::default())?;
+///
+/// // const PLAYER_WIDTH: u32 = 16;
+/// // const PLAYER_HEIGHT: u32 = 16;
+/// // const PLAYER_FLAGS: u32 = 1; // BLIT_2BPP
+/// // const PLAYER_FRAME_IDLE: usize = 0;
+/// // const PLAYER_FRAME_WALK: usize = 1;
+/// // const PLAYER_FRAMES: [[u8; 64]; 2] = [[...], [...]];
+/// println!("{}", frames);
+/// # Ok(())
+/// # }
+/// ```
+pub fn convert_png_to_rust_frames(
+    name: &str,
+    bytes: &[u8],
+    sheet: &SpriteSheet,
+    options: &ConversionOptions,
+) -> Result<RustFrames, PngToWasm4SrcError> {
+    let layout = FrameLayout::Sheet(sheet.clone());
+    convert_png_to_rust_frames_with_layout(name, bytes, &layout, options)
+}
+
+pub(crate) fn convert_png_to_rust_frames_with_layout(
+    name: &str,
+    bytes: &[u8],
+    layout: &FrameLayout,
+    options: &ConversionOptions,
+) -> Result<RustFrames, PngToWasm4SrcError> {
+    let image = convert_png_to_indexed_image(bytes, options)?;
+    let (frame_width, frame_height) = layout.frame_size();
+
+    let frames = layout
+        .frame_positions(image.width, image.height)?
+        .into_iter()
+        .map(|(x, y)| image.crop(x, y, frame_width, frame_height).encode())
+        .collect();
+
+    let rust_frames = RustFrames::new(name, frame_width, frame_height, image.flags, frames)
+        .with_frame_names(layout.frame_names())
+        .with_transparent_index(image.transparent_index)
+        .with_merged_colors(image.merged_colors);
+
+    Ok(rust_frames)
+}
+
+/// Convert a PNG sprite atlas to a struct representing Rust source code with its regions
+///
+/// Parameters
+/// ----
+///
+/// * `name` the variables prefix
+/// * `bytes` the raw PNG image
+/// * `layout` the layout of regions in the atlas
+/// * `options` the conversion options
+///
+/// The atlas is converted as a single sprite, following the same rules as
+/// [`convert_png_to_rust_variables_with_options()`].
+/// Its regions are stored in the returned variables, and generate constants
+/// for their position and size, to be used with the WASM-4 function
+/// `blit_sub`.
+/// Regions lying outside the atlas are reported as
+/// [`PngToWasm4SrcError::RegionOutOfBounds`].
+///
+/// ```no_run
+/// # use png2wasm4src::{convert_png_to_rust_atlas, AtlasLayout, ConversionOptions, Region};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let bytes = std::fs::read("items.png")?;
+/// let layout = AtlasLayout::Regions(vec![Region::new("heart", 0, 0, 8, 8)]);
+/// let variables = convert_png_to_rust_atlas("items", &bytes, &layout, &ConversionOptions::default())?;
+///
+/// // const ITEMS_WIDTH: u32 = 32;
+/// // ...
+/// // const ITEMS_HEART_SRC_X: u32 = 0;
+/// // const ITEMS_HEART_SRC_Y: u32 = 0;
+/// // const ITEMS_HEART_WIDTH: u32 = 8;
+/// // const ITEMS_HEART_HEIGHT: u32 = 8;
+/// println!("{}", variables);
+/// # Ok(())
+/// # }
+/// ```
+pub fn convert_png_to_rust_atlas(
+    name: &str,
+    bytes: &[u8],
+    layout: &AtlasLayout,
+    options: &ConversionOptions,
+) -> Result<RustVariables, PngToWasm4SrcError> {
+    let image = convert_png_to_indexed_image(bytes, options)?;
+    let regions = layout.regions(image.width, image.height)?;
+    Ok(image.to_rust_variables(name).with_regions(regions))
+}
+
+/// Convert an Aseprite image to a struct representing Rust source code
+///
+/// Parameters
+/// ----
+///
+/// * `name` the variables prefix
+/// * `bytes` the raw Aseprite file (`.ase` or `.aseprite`)
+/// * `layers` the layers to flatten
+/// * `options` the conversion options
+///
+/// Only files in indexed colour mode are accepted.
+/// The selected layers of the first frame are flattened, and the result is
+/// converted following the same rules as indexed PNG images in
+/// [`convert_png_to_rust_variables_with_options()`].
+/// Pixels using the transparent colour index of the file are transparent,
+/// unless they belong to a background layer.
+///
+/// ```no_run
+/// # use png2wasm4src::{convert_aseprite_to_rust_variables, ConversionOptions, LayerSelection};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let bytes = std::fs::read("car.aseprite")?;
+/// let layers = LayerSelection::Named(vec!["body".into()]);
+/// let variables = convert_aseprite_to_rust_variables("car", &bytes, &layers, &ConversionOptions::default())?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn convert_aseprite_to_rust_variables(
+    name: &str,
+    bytes: &[u8],
+    layers: &LayerSelection,
+    options: &ConversionOptions,
+) -> Result<RustVariables, PngToWasm4SrcError> {
+    let file = AsepriteFile::parse(bytes)?;
+    convert_aseprite_file_to_rust_variables(name, &file, layers, options)
+}
+
+pub(crate) fn convert_aseprite_file_to_rust_variables(
+    name: &str,
+    file: &AsepriteFile,
+    layers: &LayerSelection,
+    options: &ConversionOptions,
+) -> Result<RustVariables, PngToWasm4SrcError> {
+    let image = convert_aseprite_to_indexed_image(file, layers, 1, options)?;
+    Ok(image.to_rust_variables(name))
+}
+
+/// Convert an animated Aseprite image to a struct representing Rust source code for its frames
+///
+/// Parameters
+/// ----
+///
+/// * `name` the variables prefix
+/// * `bytes` the raw Aseprite file (`.ase` or `.aseprite`)
+/// * `layers` the layers to flatten
+/// * `options` the conversion options
+///
+/// Each frame is flattened like in [`convert_aseprite_to_rust_variables()`],
+/// and all frames share the same palette and bit depth.
+/// Each tag becomes an [`Animation`](crate::Animation) over its frames,
+/// playing in the tag direction, with the frame durations converted to
+/// WASM-4 ticks.
+/// Tags with a repeat count do not loop.
+pub fn convert_aseprite_to_rust_frames(
+    name: &str,
+    bytes: &[u8],
+    layers: &LayerSelection,
+    options: &ConversionOptions,
+) -> Result<RustFrames, PngToWasm4SrcError> {
+    let file = AsepriteFile::parse(bytes)?;
+    convert_aseprite_file_to_rust_frames(name, &file, layers, options)
+}
+
+pub(crate) fn convert_aseprite_file_to_rust_frames(
+    name: &str,
+    file: &AsepriteFile,
+    layers: &LayerSelection,
+    options: &ConversionOptions,
+) -> Result<RustFrames, PngToWasm4SrcError> {
+    let frame_count = file.frame_count();
+    let image = convert_aseprite_to_indexed_image(file, layers, frame_count, options)?;
+    let rust_frames = image
+        .to_rust_frames(name, frame_count)
+        .with_animations(file.animations()?);
+    Ok(rust_frames)
+}
+
+/// Convert an animated PNG image to a struct representing Rust source code for its frames
+///
+/// Parameters
+/// ----
+///
+/// * `name` the variables prefix
+/// * `bytes` the raw APNG image
+/// * `options` the conversion options
+///
+/// Each frame is composited on the full canvas, and all frames are converted
+/// together following the same rules as
+/// [`convert_png_to_rust_variables_with_options()`], so they share the same
+/// palette mapping and bit depth.
+/// All fully transparent colours are the same colour, as in
+/// [`convert_gif_to_rust_frames()`].
+/// The delay of each frame is converted to WASM-4 ticks, and reported by
+/// [`RustFrames::frame_durations()`].
+/// A PNG image that is not animated results in a single frame.
+///
+/// ```no_run
+/// # use png2wasm4src::{convert_apng_to_rust_frames, ConversionOptions};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let bytes = std::fs::read("coin.png")?;
+/// let frames = convert_apng_to_rust_frames("coin", &bytes, &ConversionOptions::default())?;
+///
+/// // const COIN_WIDTH: u32 = 8;
+/// // const COIN_HEIGHT: u32 = 8;
+/// // const COIN_FLAGS: u32 = 1; // BLIT_2BPP
+/// // const COIN_FRAMES: [[u8; 16]; 4] = [[...], [...], [...], [...]];
+/// // const COIN_FRAME_DURATIONS: [u32; 4] = [6, 6, 6, 12];
+/// println!("{}", frames);
+/// # Ok(())
+/// # }
+/// ```
+pub fn convert_apng_to_rust_frames(
+    name: &str,
+    bytes: &[u8],
+    options: &ConversionOptions,
+) -> Result<RustFrames, PngToWasm4SrcError> {
+    // Fully transparent pixels and palette entries are transparent black, like
+    // the transparent colour of GIF images and cleared areas of the canvas
+    let mut animation = decode_apng(bytes)?;
+    animation.frames = animation
+        .frames
+        .iter()
+        .map(collapse_transparent_pixels)
+        .collect();
+    let palette = match options.reference_palette() {
+        Some(_) => None,
+        None => extract_palette_for_conversion(bytes, options)?.map(|palette| {
+            palette
+                .into_iter()
+                .map(collapse_transparent_value)
+                .collect()
+        }),
+    };
+    convert_animation_to_rust_frames(name, &animation, palette, options)
+}
+
+/// Convert an animated GIF image to a struct representing Rust source code for its frames
+///
+/// Parameters
+/// ----
+///
+/// * `name` the variables prefix
+/// * `bytes` the raw GIF image
+/// * `options` the conversion options
+///
+/// Frames are converted like in [`convert_apng_to_rust_frames()`].
+/// Colours are assigned the indices of the global palette, where the
+/// transparent colour of the frames is fully transparent.
+/// Frames with a local palette must only use colours of the global palette.
+/// GIF images without a global palette are quantized.
+pub fn convert_gif_to_rust_frames(
+    name: &str,
+    bytes: &[u8],
+    options: &ConversionOptions,
+) -> Result<RustFrames, PngToWasm4SrcError> {
+    let animation = decode_gif(bytes)?;
+    let palette =
+        read_gif_palette(bytes)?.map(|palette| palette.iter().map(color_to_value).collect());
+    convert_animation_to_rust_frames(name, &animation, palette, options)
+}
+
+fn convert_animation_to_rust_frames(
+    name: &str,
+    animation: &DecodedAnimation,
+    palette: Option<Vec<u32>>,
+    options: &ConversionOptions,
+) -> Result<RustFrames, PngToWasm4SrcError> {
+    let image = convert_image(&animation.to_sheet(), palette, options)?;
+    let durations = animation
+        .durations
+        .iter()
+        .copied()
+        .map(millis_to_ticks)
+        .collect();
+    let rust_frames = image
+        .to_rust_frames(name, animation.frames.len())
+        .with_frame_durations(durations);
+    Ok(rust_frames)
+}
+
+/// Convert a PNG image to a struct representing a Rust palette constant
+///
+/// Parameters
+/// ----
+///
+/// * `name` the constant prefix
+/// * `bytes` the raw PNG image
+///
+/// Images with exactly four pixels, such as a 4x1 strip, define the palette
+/// colours in their pixels, in row-major order.
+/// Other images must be indexed, and define the palette colours in the first
+/// four entries of their palette, which must contain at least four entries.
+///
+/// Generating Rust source code
+/// ----
+///
+/// The palette can be converted to actual Rust source code using the function
+/// [`std::string::ToString::to_string()`], or the macro [`format!`].
+///
+/// ```no_run
+/// # use png2wasm4src::convert_png_to_rust_palette;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let bytes = std::fs::read("palette.png")?;
+/// let palette = convert_png_to_rust_palette("game", &bytes)?;
+///
+/// // const GAME_PALETTE: [u32; 4] = [0xe0f8cf, 0x86c06c, 0x306850, 0x071821];
+/// println!("{}", palette);
+/// # Ok(())
+/// # }
+/// ```
+pub fn convert_png_to_rust_palette(
+    name: &str,
+    bytes: &[u8],
+) -> Result<RustPalette, PngToWasm4SrcError> {
     let image = read_image(bytes)?;
 
-    let (data, flags) = match palette.len() {
-        2 => (encode_1bpp_image(&image, &palette), Flags::OneBitPerPixel),
-        4 => (encode_2bpp_image(&image, &palette), Flags::TwoBitsPerPixel),
+    let colors: Vec<u32> = if image.width() * image.height() == 4 {
+        image
+            .pixels()
+            .map(|Rgba([r, g, b, _])| triple_to_color(*r, *g, *b))
+            .collect()
+    } else {
+        let decoder = PngDecoder::new(bytes);
+        let reader = decoder.read_info()?;
+        reader
+            .info()
+            .palette
+            .as_ref()
+            .ok_or(PngToWasm4SrcError::NotIndexedPng)?
+            .chunks(3)
+            .map(|rgb| triple_to_color(rgb[0], rgb[1], rgb[2]))
+            .collect()
+    };
+
+    let colors = match colors[..] {
+        [first, second, third, fourth, ..] => [first, second, third, fourth],
+        _ => return Err(PngToWasm4SrcError::InvalidPaletteSize(colors.len())),
+    };
+
+    Ok(RustPalette::new(name, colors))
+}
+
+fn convert_png_to_indexed_image(
+    bytes: &[u8],
+    options: &ConversionOptions,
+) -> Result<IndexedImage, PngToWasm4SrcError> {
+    if let Some(reference_palette) = options.reference_palette() {
+        let image = read_image(bytes)?;
+        return convert_image_with_reference_palette(&image, reference_palette, options);
+    }
+
+    let (color_type, bit_depth) = extract_color_format(bytes)?;
+
+    if color_type == ColorType::Grayscale && matches!(bit_depth, BitDepth::One | BitDepth::Two) {
+        return convert_low_depth_grayscale_png(bytes, options);
+    }
+
+    let palette = extract_palette_for_conversion(bytes, options)?;
+    let image = read_image(bytes)?;
+    convert_image_with_palette(&image, palette, options)
+}
+
+/// Extract the palette of a PNG image, if it is indexed
+///
+/// Non-indexed images are only accepted if they are grayscale or if
+/// quantization is enabled.
+fn extract_palette_for_conversion(
+    bytes: &[u8],
+    options: &ConversionOptions,
+) -> Result<Option<Vec<u32>>, PngToWasm4SrcError> {
+    let (color_type, _) = extract_color_format(bytes)?;
+    let grayscale = matches!(color_type, ColorType::Grayscale | ColorType::GrayscaleAlpha);
+
+    match extract_palette(bytes) {
+        Ok(palette) => Ok(Some(palette)),
+        Err(PngToWasm4SrcError::NotIndexedPng) if grayscale || options.quantization() => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+fn convert_aseprite_to_indexed_image(
+    file: &AsepriteFile,
+    layers: &LayerSelection,
+    frame_count: usize,
+    options: &ConversionOptions,
+) -> Result<IndexedImage, PngToWasm4SrcError> {
+    let (image, palette) = file.render_sheet(layers, frame_count)?;
+    let palette = palette.iter().map(color_to_value).collect();
+    convert_image(&image, Some(palette), options)
+}
+
+/// Convert an image to colour indices using the reference palette if set,
+/// or its own palette otherwise
+fn convert_image(
+    image: &RgbaImage,
+    palette: Option<Vec<u32>>,
+    options: &ConversionOptions,
+) -> Result<IndexedImage, PngToWasm4SrcError> {
+    match options.reference_palette() {
+        Some(reference_palette) => {
+            convert_image_with_reference_palette(image, reference_palette, options)
+        }
+        None => convert_image_with_palette(image, palette, options),
+    }
+}
+
+/// Convert an image to colour indices using its palette
+///
+/// Images without a palette are quantized.
+fn convert_image_with_palette(
+    image: &RgbaImage,
+    palette: Option<Vec<u32>>,
+    options: &ConversionOptions,
+) -> Result<IndexedImage, PngToWasm4SrcError> {
+    // Fully transparent colours are only the same colour when it is placed at
+    // a fixed index, otherwise they are distinct palette entries
+    let collapsed_image;
+    let (image, palette) = if options.transparent_index().is_some() {
+        collapsed_image = collapse_transparent_pixels(image);
+        let palette = palette.map(|palette| {
+            palette
+                .into_iter()
+                .map(collapse_transparent_value)
+                .collect()
+        });
+        (&collapsed_image, palette)
+    } else {
+        (image, palette)
+    };
+
+    let mut duplicates = Vec::default();
+    let (mut palette, mut palette_size) = match palette {
+        Some(palette) => {
+            let PaletteMapping {
+                mapping,
+                size,
+                merged,
+            } = compute_palette_mapping(&palette, options.duplicate_colors())?;
+            duplicates = merged;
+            let compaction = options.palette_compaction();
+            if compaction != PaletteCompaction::Disabled && size != 2 && size != 4 {
+                compact_palette(image, &mapping, compaction, options.color_order())?
+            } else {
+                (mapping, size)
+            }
+        }
+        None => quantize_palette(image, options.color_order())?,
+    };
+    let has_transparent_pixels = image
+        .pixels()
+        .any(|color| color_to_value(color) == TRANSPARENT_VALUE);
+    let transparent_index = place_transparent_color(
+        &mut palette,
+        &mut palette_size,
+        TRANSPARENT_VALUE,
+        has_transparent_pixels,
+        options,
+    )?;
+
+    // Duplicates are reported with their final index, after compaction and
+    // placement of the transparent colour
+    let merged_colors = duplicates
+        .into_iter()
+        .filter_map(|(index, value)| {
+            let merged_index = *palette.get(&value)?;
+            Some(MergedColor::new(value >> 8, index, merged_index))
+        })
+        .collect();
+
+    let flags = match palette_size {
+        2 => Flags::OneBitPerPixel,
+        4 => Flags::TwoBitsPerPixel,
         n => return Err(PngToWasm4SrcError::InvalidPaletteSize(n)),
     };
 
-    let rust_variables = RustVariables::new(name, image.width(), image.height(), flags, data);
+    let indices = image
+        .enumerate_pixels()
+        .map(|(x, y, color)| {
+            let value = color_to_value(color);
+            palette
+                .get(&value)
+                .copied()
+                .ok_or(PngToWasm4SrcError::UnmappedColor {
+                    color: value >> 8,
+                    x,
+                    y,
+                })
+        })
+        .collect::<Result<_, _>>()?;
+
+    let (width, height) = image.dimensions();
+    Ok(IndexedImage {
+        width,
+        height,
+        flags,
+        indices,
+        transparent_index,
+        merged_colors,
+    })
+}
+
+fn convert_image_with_reference_palette(
+    image: &RgbaImage,
+    reference_palette: &ReferencePalette,
+    options: &ConversionOptions,
+) -> Result<IndexedImage, PngToWasm4SrcError> {
+    let reference_size = reference_palette.colors().len();
+    if reference_size == 0 || reference_size > 4 {
+        return Err(PngToWasm4SrcError::InvalidPaletteSize(reference_size));
+    }
+
+    let transparent_index = match options.transparent_index() {
+        Some(transparent_index) if transparent_index > 3 => {
+            return Err(PngToWasm4SrcError::InvalidTransparentIndex(
+                transparent_index,
+            ));
+        }
+        transparent_index => transparent_index,
+    };
+
+    let mut indices = Vec::with_capacity((image.width() * image.height()) as usize);
+    let mut has_transparent_pixels = false;
+    for (x, y, color) in image.enumerate_pixels() {
+        let Rgba([r, g, b, a]) = *color;
+        let index = match transparent_index {
+            Some(transparent_index) if a == 0 => {
+                has_transparent_pixels = true;
+                transparent_index as usize
+            }
+            _ => {
+                let color = quadruple_to_value(r, g, b, 0xff) >> 8;
+                reference_palette
+                    .find(color)
+                    .ok_or(PngToWasm4SrcError::UnmappedColor { color, x, y })?
+            }
+        };
+        indices.push(index);
+    }
+
+    let flags = if indices.iter().all(|index| *index < 2) {
+        Flags::OneBitPerPixel
+    } else {
+        Flags::TwoBitsPerPixel
+    };
+
+    let (width, height) = image.dimensions();
+    Ok(IndexedImage {
+        width,
+        height,
+        flags,
+        indices,
+        transparent_index: transparent_index.filter(|_| has_transparent_pixels),
+        merged_colors: Vec::default(),
+    })
+}
+
+fn convert_low_depth_grayscale_png(
+    bytes: &[u8],
+    options: &ConversionOptions,
+) -> Result<IndexedImage, PngToWasm4SrcError> {
+    let mut decoder = PngDecoder::new(bytes);
+    decoder.set_transformations(Transformations::IDENTITY);
+    let mut reader = decoder.read_info()?;
+    let transparent_level = reader.info().trns.as_ref().map(|trns| trns[0] as u32);
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+
+    let bits = info.bit_depth as usize;
+    let max_level = (1 << bits) - 1;
+    let mut palette = (0..=max_level)
+        .map(|level| match options.color_order() {
+            ColorOrder::DarkestFirst => (level as u32, level),
+            ColorOrder::LightestFirst => (level as u32, max_level - level),
+        })
+        .collect();
+    let mut palette_size = max_level + 1;
+
+    let level = |x: u32, y: u32| {
+        let offset = x as usize * bits;
+        let byte = buffer[y as usize * info.line_size + offset / 8];
+        let shift = 8 - bits - offset % 8;
+        (byte as u32 >> shift) & max_level as u32
+    };
+    let levels: Vec<u32> = (0..info.height)
+        .flat_map(|y| (0..info.width).map(move |x| (x, y)))
+        .map(|(x, y)| level(x, y))
+        .collect();
+
+    let transparent_index = match transparent_level {
+        Some(transparent_level) => place_transparent_color(
+            &mut palette,
+            &mut palette_size,
+            transparent_level,
+            levels.contains(&transparent_level),
+            options,
+        )?,
+        None => None,
+    };
+    let indices = levels.iter().map(|level| palette[level]).collect();
+
+    let flags = match palette_size {
+        2 => Flags::OneBitPerPixel,
+        _ => Flags::TwoBitsPerPixel,
+    };
+
+    Ok(IndexedImage {
+        width: info.width,
+        height: info.height,
+        flags,
+        indices,
+        transparent_index,
+        merged_colors: Vec::default(),
+    })
+}
+
+/// An image with a WASM-4 colour index for each pixel, not yet encoded
+pub(crate) struct IndexedImage {
+    width: u32,
+    height: u32,
+    flags: Flags,
+    indices: Vec<usize>,
+    transparent_index: Option<u8>,
+    merged_colors: Vec<MergedColor>,
+}
+
+impl IndexedImage {
+    /// Create an image with all pixels set to colour index 0
+    pub(crate) fn blank(width: u32, height: u32, flags: Flags) -> Self {
+        Self {
+            width,
+            height,
+            flags,
+            indices: vec![0; (width * height) as usize],
+            transparent_index: None,
+            merged_colors: Vec::default(),
+        }
+    }
+
+    /// Decode the image data of a sprite
+    ///
+    /// Pixels are read as WASM-4 reads them, as a contiguous sequence of one
+    /// or two bits each.
+    pub(crate) fn from_rust_variables(rust_variables: &RustVariables) -> Self {
+        let width = rust_variables.width();
+        let height = rust_variables.height();
+        let data = rust_variables.data();
+        let bits = match rust_variables.flags() {
+            Flags::OneBitPerPixel => 1,
+            Flags::TwoBitsPerPixel => 2,
+        };
+        let indices = (0..(width * height) as usize)
+            .map(|pixel| {
+                let offset = pixel * bits;
+                let byte = data.get(offset / 8).copied().unwrap_or_default();
+                let shift = 8 - bits - offset % 8;
+                ((byte >> shift) & ((1 << bits) - 1)) as usize
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            flags: rust_variables.flags(),
+            indices,
+            transparent_index: rust_variables.transparent_index(),
+            merged_colors: rust_variables.merged_colors().to_vec(),
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        self.indices[(y * self.width + x) as usize]
+    }
+
+    /// Copy another image into this one, with its top-left corner at `(x, y)`
+    pub(crate) fn paste(&mut self, other: &IndexedImage, x: u32, y: u32) {
+        for other_y in 0..other.height {
+            for other_x in 0..other.width {
+                let offset = ((y + other_y) * self.width + x + other_x) as usize;
+                self.indices[offset] = other.index(other_x, other_y);
+            }
+        }
+    }
+
+    fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        let indices = (y..y + height)
+            .flat_map(|y| (x..x + width).map(move |x| (x, y)))
+            .map(|(x, y)| self.index(x, y))
+            .collect();
+        Self {
+            width,
+            height,
+            flags: self.flags,
+            indices,
+            transparent_index: self.transparent_index,
+            merged_colors: self.merged_colors.clone(),
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let index = |x, y| self.index(x, y);
+        match self.flags {
+            Flags::OneBitPerPixel => encode_1bpp_image(self.width, self.height, index),
+            Flags::TwoBitsPerPixel => encode_2bpp_image(self.width, self.height, index),
+        }
+    }
+
+    /// Cut an image made of frames stacked vertically into frames
+    fn to_rust_frames(&self, name: &str, frame_count: usize) -> RustFrames {
+        let frame_height = self.height / frame_count.max(1) as u32;
+        let frames = (0..frame_count as u32)
+            .map(|frame| {
+                self.crop(0, frame * frame_height, self.width, frame_height)
+                    .encode()
+            })
+            .collect();
+        RustFrames::new(name, self.width, frame_height, self.flags, frames)
+            .with_transparent_index(self.transparent_index)
+            .with_merged_colors(self.merged_colors.clone())
+    }
+
+    pub(crate) fn to_rust_variables(&self, name: &str) -> RustVariables {
+        RustVariables::new(name, self.width, self.height, self.flags, self.encode())
+            .with_transparent_index(self.transparent_index)
+            .with_merged_colors(self.merged_colors.clone())
+    }
+}
 
-    Ok(rust_variables)
+fn extract_color_format(bytes: &[u8]) -> Result<(ColorType, BitDepth), PngToWasm4SrcError> {
+    let decoder = PngDecoder::new(bytes);
+    let reader = decoder.read_info()?;
+    let info = reader.info();
+    Ok((info.color_type, info.bit_depth))
 }
 
 fn extract_palette(bytes: &[u8]) -> Result<Vec<u32>, PngToWasm4SrcError> {
@@ -81,7 +944,11 @@ fn extract_palette(bytes: &[u8]) -> Result<Vec<u32>, PngToWasm4SrcError> {
                     let r = palette[i * 3];
                     let g = palette[i * 3 + 1];
                     let b = palette[i * 3 + 2];
-                    let a = 0;
+                    let a = info
+                        .trns
+                        .as_ref()
+                        .and_then(|trns| trns.get(i).copied())
+                        .unwrap_or(0xff);
                     quadruple_to_value(r, g, b, a)
                 })
                 .collect();
@@ -89,12 +956,165 @@ fn extract_palette(bytes: &[u8]) -> Result<Vec<u32>, PngToWasm4SrcError> {
         })
 }
 
-fn compute_palette_mapping(palette: &[u32]) -> HashMap<u32, usize> {
-    palette
-        .iter()
+/// WASM-4 colour indices of the colours of a palette
+struct PaletteMapping {
+    /// Index of each distinct colour
+    mapping: HashMap<u32, usize>,
+
+    /// Number of colour indices of the sprite
+    size: usize,
+
+    /// Palette index and colour of each merged duplicate entry
+    merged: Vec<(usize, u32)>,
+}
+
+/// Map the colours of a palette to WASM-4 colour indices
+///
+/// Duplicate entries are merged into the first entry with the same colour,
+/// and the remaining entries are assigned consecutive indices.
+/// Palettes with up to four distinct colours are rounded up to two or four
+/// indices, whether or not any entry was merged.
+fn compute_palette_mapping(
+    palette: &[u32],
+    duplicate_colors: DuplicateColors,
+) -> Result<PaletteMapping, PngToWasm4SrcError> {
+    let mut mapping = HashMap::default();
+    let mut merged = Vec::default();
+
+    for (index, value) in palette.iter().enumerate() {
+        let next_index = mapping.len();
+        match mapping.entry(*value) {
+            Entry::Occupied(_) => {
+                if duplicate_colors == DuplicateColors::Reject {
+                    let first_index = palette.iter().position(|v| v == value).unwrap_or(index);
+                    return Err(PngToWasm4SrcError::DuplicatePaletteColor {
+                        color: value >> 8,
+                        index,
+                        first_index,
+                    });
+                }
+                merged.push((index, *value));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(next_index);
+            }
+        }
+    }
+
+    let size = match mapping.len() {
+        0..=2 => 2,
+        3..=4 => 4,
+        n => n,
+    };
+
+    Ok(PaletteMapping {
+        mapping,
+        size,
+        merged,
+    })
+}
+
+fn place_transparent_color(
+    palette: &mut HashMap<u32, usize>,
+    palette_size: &mut usize,
+    transparent_value: u32,
+    has_transparent_pixels: bool,
+    options: &ConversionOptions,
+) -> Result<Option<u8>, PngToWasm4SrcError> {
+    let transparent_index = match options.transparent_index() {
+        Some(transparent_index) if transparent_index > 3 => {
+            return Err(PngToWasm4SrcError::InvalidTransparentIndex(
+                transparent_index,
+            ));
+        }
+        Some(transparent_index) => transparent_index,
+        None => return Ok(None),
+    };
+
+    // A palette entry that no pixel uses does not need a fixed index
+    let previous_index = match palette.get(&transparent_value) {
+        Some(previous_index) if has_transparent_pixels => *previous_index,
+        _ => return Ok(None),
+    };
+
+    for index in palette.values_mut() {
+        if *index == transparent_index as usize {
+            *index = previous_index;
+        }
+    }
+    palette.insert(transparent_value, transparent_index as usize);
+
+    if transparent_index as usize >= *palette_size {
+        *palette_size = 4;
+    }
+
+    Ok(Some(transparent_index))
+}
+
+fn compact_palette(
+    image: &RgbaImage,
+    palette: &HashMap<u32, usize>,
+    compaction: PaletteCompaction,
+    color_order: ColorOrder,
+) -> Result<(HashMap<u32, usize>, usize), PngToWasm4SrcError> {
+    let mut colors = HashSet::new();
+    for (x, y, color) in image.enumerate_pixels() {
+        let value = color_to_value(color);
+        if !palette.contains_key(&value) {
+            return Err(PngToWasm4SrcError::UnmappedColor {
+                color: value >> 8,
+                x,
+                y,
+            });
+        }
+        colors.insert(value);
+    }
+
+    let mut colors: Vec<u32> = colors.into_iter().collect();
+    match compaction {
+        PaletteCompaction::Disabled | PaletteCompaction::PaletteOrder => {
+            colors.sort_by_key(|value| palette[value]);
+        }
+        PaletteCompaction::Luminance => sort_by_luminance(&mut colors, color_order),
+    }
+
+    palette_from_colors(colors)
+}
+
+fn quantize_palette(
+    image: &RgbaImage,
+    color_order: ColorOrder,
+) -> Result<(HashMap<u32, usize>, usize), PngToWasm4SrcError> {
+    let colors: HashSet<u32> = image.pixels().map(color_to_value).collect();
+
+    let mut colors: Vec<u32> = colors.into_iter().collect();
+    sort_by_luminance(&mut colors, color_order);
+
+    palette_from_colors(colors)
+}
+
+fn sort_by_luminance(colors: &mut [u32], color_order: ColorOrder) {
+    colors.sort_by_key(|value| (value_to_luminance(*value), *value));
+    if color_order == ColorOrder::LightestFirst {
+        colors.reverse();
+    }
+}
+
+fn palette_from_colors(
+    colors: Vec<u32>,
+) -> Result<(HashMap<u32, usize>, usize), PngToWasm4SrcError> {
+    if colors.len() > 4 {
+        return Err(PngToWasm4SrcError::TooManyColors(colors.len()));
+    }
+
+    let palette_size = if colors.len() <= 2 { 2 } else { 4 };
+    let mapping = colors
+        .into_iter()
         .enumerate()
-        .map(|(index, value)| (*value, index))
-        .collect()
+        .map(|(index, value)| (value, index))
+        .collect();
+
+    Ok((mapping, palette_size))
 }
 
 fn read_image(bytes: &[u8]) -> Result<RgbaImage, PngToWasm4SrcError> {
@@ -104,40 +1124,48 @@ fn read_image(bytes: &[u8]) -> Result<RgbaImage, PngToWasm4SrcError> {
     Ok(image)
 }
 
-fn encode_1bpp_image(image: &RgbaImage, palette: &HashMap<u32, usize>) -> Vec<u8> {
+fn encode_1bpp_image<F>(width: u32, height: u32, index: F) -> Vec<u8>
+where
+    F: Fn(u32, u32) -> usize,
+{
     let encoder = |x, y| {
-        let idx = ((y * image.width() + x) as usize) >> 3;
+        let idx = ((y * width + x) as usize) >> 3;
         let shift = 7 - ((x as u8) & 0x07);
         let mask = 0x1 << shift;
         (idx, shift, mask)
     };
-    encode_image(image, palette, encoder)
+    encode_image(width, height, index, encoder)
 }
 
-fn encode_2bpp_image(image: &RgbaImage, palette: &HashMap<u32, usize>) -> Vec<u8> {
+fn encode_2bpp_image<F>(width: u32, height: u32, index: F) -> Vec<u8>
+where
+    F: Fn(u32, u32) -> usize,
+{
     let encoder = |x, y| {
-        let idx = ((y * image.width() + x) as usize) >> 2;
+        let idx = ((y * width + x) as usize) >> 2;
         let shift = 6 - (((x as u8) & 0x3) << 1);
         let mask = 0x3 << shift;
         (idx, shift, mask)
     };
-    encode_image(image, palette, encoder)
+    encode_image(width, height, index, encoder)
 }
 
-fn encode_image<F>(image: &RgbaImage, palette: &HashMap<u32, usize>, encode: F) -> Vec<u8>
+fn encode_image<F, G>(width: u32, height: u32, index: F, encode: G) -> Vec<u8>
 where
-    F: Fn(u32, u32) -> (usize, u8, u8),
+    F: Fn(u32, u32) -> usize,
+    G: Fn(u32, u32) -> (usize, u8, u8),
 {
     let mut bytes = Vec::default();
 
-    for (x, y, color) in image.enumerate_pixels() {
-        let value = color_to_value(color);
-        let index = palette.get(&value).expect("Missing pixel value in mapping");
-        let (idx, shift, mask) = encode(x, y);
-        if bytes.len() <= idx {
-            bytes.push(0);
+    for y in 0..height {
+        for x in 0..width {
+            let index = index(x, y);
+            let (idx, shift, mask) = encode(x, y);
+            if bytes.len() <= idx {
+                bytes.push(0);
+            }
+            bytes[idx] = ((index as u8) << shift) | (bytes[idx] & (!mask));
         }
-        bytes[idx] = ((*index as u8) << shift) | (bytes[idx] & (!mask));
     }
 
     bytes
@@ -148,7 +1176,41 @@ fn color_to_value(color: &Rgba<u8>) -> u32 {
     quadruple_to_value(*r, *g, *b, *a)
 }
 
-fn quadruple_to_value(r: u8, g: u8, b: u8, _a: u8) -> u32 {
-    let value = (r as u32) << 24 | (g as u32) << 16 | (b as u32) << 8;
-    value as u32
+/// Return the value of a colour, as `0xRRGGBBAA`
+///
+/// Alpha is either `0x00` for fully transparent colours or `0xff` otherwise.
+fn quadruple_to_value(r: u8, g: u8, b: u8, a: u8) -> u32 {
+    let alpha = if a == 0 { 0x00 } else { 0xff };
+    (r as u32) << 24 | (g as u32) << 16 | (b as u32) << 8 | alpha
+}
+
+/// Replace a fully transparent colour with [`TRANSPARENT_VALUE`]
+fn collapse_transparent_value(value: u32) -> u32 {
+    if value & 0xff == 0 {
+        TRANSPARENT_VALUE
+    } else {
+        value
+    }
+}
+
+/// Replace all fully transparent pixels with the same colour
+fn collapse_transparent_pixels(image: &RgbaImage) -> RgbaImage {
+    let mut image = image.clone();
+    for color in image.pixels_mut() {
+        if color[3] == 0 {
+            *color = Rgba([0, 0, 0, 0]);
+        }
+    }
+    image
+}
+
+fn triple_to_color(r: u8, g: u8, b: u8) -> u32 {
+    (r as u32) << 16 | (g as u32) << 8 | (b as u32)
+}
+
+fn value_to_luminance(value: u32) -> u32 {
+    let r = (value >> 24) & 0xff;
+    let g = (value >> 16) & 0xff;
+    let b = (value >> 8) & 0xff;
+    299 * r + 587 * g + 114 * b
 }
@@ -0,0 +1,649 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+use std::collections::BTreeMap;
+
+use crate::sanitization::sanitize_c_variable_name;
+use crate::{sanitize_variable_name, Flags, ParsedModule, PngToWasm4SrcError};
+use crate::{RustAtlas, RustFrames, RustPalette, RustVariables};
+
+/// Maximal nesting of partials, to stop partials including themselves forever
+const MAX_PARTIAL_DEPTH: usize = 64;
+
+/// A user-supplied output template
+///
+/// Templates use a subset of the [Mustache] syntax, the same as the WASM-4
+/// command `w4 png2src --template path/to/template`: variables `{{name}}`,
+/// sections `{{#sprites}}...{{/sprites}}`, inverted sections
+/// `{{^regions}}...{{/regions}}`, comments `{{! comment}}` and partials
+/// `{{> name}}`.
+/// Nothing is escaped, and lines containing only a section tag, a comment or
+/// a partial are removed from the output.
+///
+/// ```
+/// # use png2wasm4src::{Flags, RustVariables, Template};
+/// let template = Template::parse(
+///     "{{#sprites}}
+/// #[doc = \"Sprite {{name}}\"]
+/// pub const {{rustName}}: Sprite = Sprite::new({{width}}, {{height}}, {{flags}}, &[{{bytes}}]);
+/// {{/sprites}}",
+/// )?;
+/// let variables = RustVariables::new("heart", 8, 1, Flags::OneBitPerPixel, vec![0x3c]);
+///
+/// assert_eq!(
+///     template.render_variables(&variables)?,
+///     "#[doc = \"Sprite heart\"]
+/// pub const HEART: Sprite = Sprite::new(8, 1, 0, &[0x3c]);\n",
+/// );
+/// # Ok::<(), png2wasm4src::PngToWasm4SrcError>(())
+/// ```
+///
+/// Available data
+/// ----
+///
+/// Each sprite in the list `sprites` has the following variables:
+///
+/// * `name`: the sprite name, usually the file stem;
+/// * `sanitizedName`: the name as a valid identifier, keeping its case;
+/// * `rustName`: the name as a Rust constant, in upper snake case;
+/// * `width`, `height` and `length`: the sprite size, and its data length;
+/// * `flags` and `flagsHumanReadable`: the sprite flags, such as `1` and
+///   `BLIT_2BPP`;
+/// * `bytes`: the sprite data, as hexadecimal literals separated by commas;
+/// * `firstByte` and `restBytes`: the first byte, and all the others;
+/// * `transparentIndex`: the colour index of the transparent colour, if any;
+/// * `regions`: the atlas regions, each with `name`, `sanitizedName`,
+///   `rustName`, `x`, `y`, `width` and `height`.
+///
+/// When rendering a module, the template is rendered with the module data,
+/// which has the following variables:
+///
+/// * `name` and `path`: the module name and its full path, such as
+///   `sprites::characters`;
+/// * `depth` and `indent`: the module nesting level, and four spaces for
+///   each level;
+/// * `palette`: the palette, if any, with `name`, `sanitizedName`, `rustName`
+///   and `colors`;
+/// * `sprites`: the sprites in the module;
+/// * `frames`: the sprite sheets, with the same variables as sprites except
+///   for data and regions, and with `frameCount`, `frameLength`,
+///   `frameDurations` and the list `frames` with `index` and `bytes`;
+/// * `atlases`: the [packed atlases](ParsedModule::pack_atlases), with the
+///   same variables as sprites, and the list `sprites` of packed sprites,
+///   which also have `srcX`, `srcY` and `stride`;
+/// * `modules`: the submodules.
+///
+/// Nested modules are generated using a recursive partial.
+///
+/// ```
+/// # use png2wasm4src::{Flags, ParsedModule, RustVariables, Template};
+/// let template = Template::parse("{{> module}}")?.with_partial(
+///     "module",
+///     "{{indent}}mod {{name}} {
+/// {{#sprites}}
+/// {{indent}}    const {{rustName}}_WIDTH: u32 = {{width}};
+/// {{/sprites}}
+/// {{#modules}}
+/// {{> module}}
+/// {{/modules}}
+/// {{indent}}}
+/// ",
+/// )?;
+/// let module = ParsedModule::new(
+///     "sprites",
+///     Vec::default(),
+///     vec![ParsedModule::new(
+///         "tiles",
+///         vec![RustVariables::new("grass", 8, 1, Flags::OneBitPerPixel, vec![0xff])],
+///         Vec::default(),
+///     )],
+/// );
+///
+/// assert_eq!(
+///     template.render_module(&module)?,
+///     "mod sprites {
+///     mod tiles {
+///         const GRASS_WIDTH: u32 = 8;
+///     }
+/// }\n",
+/// );
+/// # Ok::<(), png2wasm4src::PngToWasm4SrcError>(())
+/// ```
+///
+/// [Mustache]: https://mustache.github.io/mustache.5.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    nodes: Vec<Node>,
+    partials: BTreeMap<String, Vec<Node>>,
+}
+
+impl Template {
+    /// Parse a template
+    pub fn parse(source: &str) -> Result<Self, PngToWasm4SrcError> {
+        Ok(Self {
+            nodes: parse_nodes(source)?,
+            partials: BTreeMap::default(),
+        })
+    }
+
+    /// Add a partial, which is included by the tag `{{> name}}`
+    pub fn with_partial(
+        mut self,
+        name: impl Into<String>,
+        source: &str,
+    ) -> Result<Self, PngToWasm4SrcError> {
+        self.partials.insert(name.into(), parse_nodes(source)?);
+        Ok(self)
+    }
+
+    /// Render the template for a single sprite
+    ///
+    /// The sprite is the only item in the list `sprites`.
+    pub fn render_variables(
+        &self,
+        variables: &RustVariables,
+    ) -> Result<String, PngToWasm4SrcError> {
+        let mut context = Context::default();
+        context.list("sprites", vec![variables_context(variables)]);
+        self.render(&context)
+    }
+
+    /// Render the template for a module
+    pub fn render_module(&self, module: &ParsedModule) -> Result<String, PngToWasm4SrcError> {
+        let context = module_context(module, None, 0);
+        self.render(&context)
+    }
+
+    fn render(&self, context: &Context) -> Result<String, PngToWasm4SrcError> {
+        let mut output = String::default();
+        let mut stack = vec![context];
+        self.render_nodes(&self.nodes, &mut stack, 0, &mut output)?;
+        Ok(output)
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        stack: &mut Vec<&Context>,
+        partial_depth: usize,
+        output: &mut String,
+    ) -> Result<(), PngToWasm4SrcError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Variable(name) => match lookup(stack, name) {
+                    Some(Value::Text(text)) => output.push_str(text),
+                    Some(Value::List(_)) | None => {}
+                },
+                Node::Section {
+                    name,
+                    inverted,
+                    children,
+                } => match lookup(stack, name) {
+                    Some(Value::List(items)) if !inverted => {
+                        for item in items {
+                            stack.push(item);
+                            self.render_nodes(children, stack, partial_depth, output)?;
+                            stack.pop();
+                        }
+                    }
+                    value => {
+                        let truthy = match value {
+                            Some(Value::Text(text)) => !text.is_empty(),
+                            Some(Value::List(items)) => !items.is_empty(),
+                            None => false,
+                        };
+                        if truthy != *inverted {
+                            self.render_nodes(children, stack, partial_depth, output)?;
+                        }
+                    }
+                },
+                Node::Partial(name) => {
+                    if partial_depth >= MAX_PARTIAL_DEPTH {
+                        return Err(PngToWasm4SrcError::InvalidTemplate(format!(
+                            "partial {} is nested too deeply",
+                            name
+                        )));
+                    }
+                    if let Some(partial) = self.partials.get(name) {
+                        self.render_nodes(partial, stack, partial_depth + 1, output)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A parsed element of a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Variable(String),
+    Section {
+        name: String,
+        inverted: bool,
+        children: Vec<Node>,
+    },
+    Partial(String),
+}
+
+/// A token of a template
+#[derive(Debug)]
+enum Token<'a> {
+    Text(&'a str),
+    Tag(TagKind, &'a str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TagKind {
+    Variable,
+    Open,
+    Inverted,
+    Close,
+    Comment,
+    Partial,
+}
+
+/// A section being parsed, with its name and whether it is inverted, or the
+/// root of the template
+type OpenSection<'a> = (Option<(&'a str, bool)>, Vec<Node>);
+
+fn parse_nodes(source: &str) -> Result<Vec<Node>, PngToWasm4SrcError> {
+    let tokens = tokenize(source)?;
+    let tokens = remove_standalone_lines(tokens);
+
+    let mut stack: Vec<OpenSection> = vec![(None, Vec::default())];
+    for token in tokens {
+        match token {
+            Token::Text(text) => push_node(&mut stack, Node::Text(text.to_string())),
+            Token::Tag(TagKind::Variable, name) => {
+                push_node(&mut stack, Node::Variable(name.to_string()))
+            }
+            Token::Tag(TagKind::Partial, name) => {
+                push_node(&mut stack, Node::Partial(name.to_string()))
+            }
+            Token::Tag(TagKind::Comment, _) => {}
+            Token::Tag(TagKind::Open, name) => stack.push((Some((name, false)), Vec::default())),
+            Token::Tag(TagKind::Inverted, name) => stack.push((Some((name, true)), Vec::default())),
+            Token::Tag(TagKind::Close, name) => match stack.pop() {
+                Some((Some((open_name, inverted)), children)) if open_name == name => {
+                    let section = Node::Section {
+                        name: name.to_string(),
+                        inverted,
+                        children,
+                    };
+                    push_node(&mut stack, section);
+                }
+                _ => {
+                    return Err(PngToWasm4SrcError::InvalidTemplate(format!(
+                        "section {} is closed without being opened",
+                        name
+                    )))
+                }
+            },
+        }
+    }
+
+    match stack.pop() {
+        Some((None, nodes)) => Ok(nodes),
+        Some((Some((name, _)), _)) => Err(PngToWasm4SrcError::InvalidTemplate(format!(
+            "section {} is not closed",
+            name
+        ))),
+        None => unreachable!("Template stack always contains the root"),
+    }
+}
+
+fn push_node(stack: &mut [OpenSection], node: Node) {
+    if let Some((_, nodes)) = stack.last_mut() {
+        nodes.push(node);
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>, PngToWasm4SrcError> {
+    let mut tokens = Vec::default();
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        let after = &rest[start + 2..];
+        let (content, length) = if let Some(after) = after.strip_prefix('{') {
+            let end = after.find("}}}").ok_or_else(unclosed_tag)?;
+            (&after[..end], end + 4)
+        } else {
+            let end = after.find("}}").ok_or_else(unclosed_tag)?;
+            (&after[..end], end + 2)
+        };
+        rest = &after[length..];
+
+        let content = content.trim();
+        let (kind, name) = match content.chars().next() {
+            Some('#') => (TagKind::Open, &content[1..]),
+            Some('^') => (TagKind::Inverted, &content[1..]),
+            Some('/') => (TagKind::Close, &content[1..]),
+            Some('!') => (TagKind::Comment, &content[1..]),
+            Some('>') => (TagKind::Partial, &content[1..]),
+            Some('&') => (TagKind::Variable, &content[1..]),
+            _ => (TagKind::Variable, content),
+        };
+        let name = name.trim();
+        if name.is_empty() && kind != TagKind::Comment {
+            return Err(PngToWasm4SrcError::InvalidTemplate(
+                "tag without a name".to_string(),
+            ));
+        }
+        tokens.push(Token::Tag(kind, name));
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    Ok(tokens)
+}
+
+fn unclosed_tag() -> PngToWasm4SrcError {
+    PngToWasm4SrcError::InvalidTemplate("tag is not closed".to_string())
+}
+
+/// Remove the lines containing only a section tag, a comment or a partial
+fn remove_standalone_lines(tokens: Vec<Token<'_>>) -> Vec<Token<'_>> {
+    let count = tokens.len();
+    let is_blank = |text: &str| text.chars().all(|c| c == ' ' || c == '\t' || c == '\r');
+    let standalone: Vec<bool> = (0..count)
+        .map(|index| {
+            match tokens[index] {
+                Token::Tag(kind, _) if kind != TagKind::Variable => {}
+                _ => return false,
+            }
+            let starts_line = match index.checked_sub(1).map(|index| &tokens[index]) {
+                None => true,
+                Some(Token::Text(text)) => match text.rfind('\n') {
+                    Some(position) => is_blank(&text[position + 1..]),
+                    None => index == 1 && is_blank(text),
+                },
+                Some(Token::Tag(..)) => false,
+            };
+            let ends_line = match tokens.get(index + 1) {
+                None => true,
+                Some(Token::Text(text)) => match text.find('\n') {
+                    Some(position) => is_blank(&text[..position]),
+                    None => index + 2 == count && is_blank(text),
+                },
+                Some(Token::Tag(..)) => false,
+            };
+            starts_line && ends_line
+        })
+        .collect();
+
+    tokens
+        .into_iter()
+        .enumerate()
+        .map(|(index, token)| match token {
+            Token::Text(text) => {
+                let start = if index > 0 && standalone[index - 1] {
+                    text.find('\n')
+                        .map(|position| position + 1)
+                        .unwrap_or(text.len())
+                } else {
+                    0
+                };
+                let end = if index + 1 < count && standalone[index + 1] {
+                    text.rfind('\n').map(|position| position + 1).unwrap_or(0)
+                } else {
+                    text.len()
+                };
+                Token::Text(&text[start..end.max(start)])
+            }
+            token => token,
+        })
+        .collect()
+}
+
+/// The data available to a template
+#[derive(Debug, Default)]
+struct Context {
+    values: BTreeMap<&'static str, Value>,
+}
+
+#[derive(Debug)]
+enum Value {
+    Text(String),
+    List(Vec<Context>),
+}
+
+impl Context {
+    fn text(&mut self, key: &'static str, value: impl ToString) {
+        self.values.insert(key, Value::Text(value.to_string()));
+    }
+
+    fn list(&mut self, key: &'static str, items: Vec<Context>) {
+        self.values.insert(key, Value::List(items));
+    }
+
+    fn names(&mut self, name: &str) {
+        self.text("name", name);
+        self.text("sanitizedName", sanitize_c_variable_name(name));
+        self.text("rustName", sanitize_variable_name(name));
+    }
+
+    fn header(&mut self, width: u32, height: u32, flags: Flags, transparent_index: Option<u8>) {
+        self.text("width", width);
+        self.text("height", height);
+        self.text("flags", flags.value());
+        self.text("flagsHumanReadable", flags.human_readable_value());
+        if let Some(transparent_index) = transparent_index {
+            self.text("transparentIndex", transparent_index);
+        }
+    }
+}
+
+/// Look up a value, from the innermost context to the outermost one
+fn lookup<'a>(stack: &[&'a Context], name: &str) -> Option<&'a Value> {
+    stack
+        .iter()
+        .rev()
+        .find_map(|context| context.values.get(name))
+}
+
+fn variables_context(variables: &RustVariables) -> Context {
+    let mut context = Context::default();
+    context.names(variables.name());
+    context.header(
+        variables.width(),
+        variables.height(),
+        variables.flags(),
+        variables.transparent_index(),
+    );
+    let data = variables.data();
+    context.text("length", data.len());
+    context.text("bytes", format_bytes(data));
+    context.text("firstByte", format_bytes(&data[..data.len().min(1)]));
+    context.text("restBytes", format_bytes(data.get(1..).unwrap_or_default()));
+    let regions = variables
+        .regions()
+        .iter()
+        .map(|region| {
+            let mut context = Context::default();
+            context.names(region.name());
+            context.text("x", region.x());
+            context.text("y", region.y());
+            context.text("width", region.width());
+            context.text("height", region.height());
+            context
+        })
+        .collect();
+    context.list("regions", regions);
+    context
+}
+
+fn frames_context(frames: &RustFrames) -> Context {
+    let mut context = Context::default();
+    context.names(frames.name());
+    context.header(
+        frames.width(),
+        frames.height(),
+        frames.flags(),
+        frames.transparent_index(),
+    );
+    let frame_length = frames.frames().first().map(Vec::len).unwrap_or_default();
+    context.text("frameCount", frames.frames().len());
+    context.text("frameLength", frame_length);
+    if !frames.frame_durations().is_empty() {
+        let durations: Vec<String> = frames
+            .frame_durations()
+            .iter()
+            .map(u32::to_string)
+            .collect();
+        context.text("frameDurations", durations.join(", "));
+    }
+    let items = frames
+        .frames()
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            let mut context = Context::default();
+            context.text("index", index);
+            context.text("bytes", format_bytes(frame));
+            context
+        })
+        .collect();
+    context.list("frames", items);
+    context
+}
+
+fn atlas_context(atlas: &RustAtlas) -> Context {
+    let mut context = variables_context(atlas.atlas());
+    let sprites = atlas
+        .sprites()
+        .iter()
+        .map(|sprite| {
+            let mut context = variables_context(sprite.variables());
+            context.text("srcX", sprite.x());
+            context.text("srcY", sprite.y());
+            context.text("stride", atlas.atlas().width());
+            context
+        })
+        .collect();
+    context.list("sprites", sprites);
+    context
+}
+
+fn palette_context(palette: &RustPalette) -> Context {
+    let mut context = Context::default();
+    context.names(palette.name());
+    let colors: Vec<String> = palette
+        .colors()
+        .iter()
+        .map(|color| format!("{:#08x}", color))
+        .collect();
+    context.text("colors", colors.join(", "));
+    context
+}
+
+fn module_context(module: &ParsedModule, parent_path: Option<&str>, depth: usize) -> Context {
+    let path = match parent_path {
+        Some(parent_path) => format!("{}::{}", parent_path, module.name()),
+        None => module.name().to_string(),
+    };
+
+    let mut context = Context::default();
+    context.text("name", module.name());
+    context.text("depth", depth);
+    context.text("indent", " ".repeat(4 * depth));
+    context.list(
+        "palette",
+        module.palette().map(palette_context).into_iter().collect(),
+    );
+    context.list(
+        "sprites",
+        module.variables().map(variables_context).collect(),
+    );
+    context.list("frames", module.frames().map(frames_context).collect());
+    context.list(
+        "atlases",
+        module.atlases().iter().map(atlas_context).collect(),
+    );
+    let submodules = module
+        .submodules()
+        .map(|submodule| module_context(submodule, Some(&path), depth + 1))
+        .collect();
+    context.list("modules", submodules);
+    context.text("path", path);
+    context
+}
+
+fn format_bytes(data: &[u8]) -> String {
+    let bytes: Vec<String> = data.iter().map(|byte| format!("{:#04x}", byte)).collect();
+    bytes.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_variables() -> Result<(), PngToWasm4SrcError> {
+        let template = Template::parse(
+            "{{#sprites}}
+// {{name}}
+#define {{sanitizedName}}Flags {{flagsHumanReadable}}
+{{#transparentIndex}}
+#define {{sanitizedName}}TransparentIndex {{transparentIndex}}
+{{/transparentIndex}}
+{{^regions}}
+// no regions
+{{/regions}}
+var {{sanitizedName}}: array[{{length}}, uint8] = [{{firstByte}}'u8, {{restBytes}}]
+{{/sprites}}",
+        )?;
+        let variables =
+            RustVariables::new("some-name", 4, 2, Flags::TwoBitsPerPixel, vec![0x01, 0x02])
+                .with_transparent_index(Some(0));
+
+        let expected = "// some-name
+#define some_nameFlags BLIT_2BPP
+#define some_nameTransparentIndex 0
+// no regions
+var some_name: array[2, uint8] = [0x01'u8, 0x02]\n";
+
+        assert_eq!(template.render_variables(&variables)?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unclosed_section() {
+        let result = Template::parse("{{#sprites}}{{name}}");
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::InvalidTemplate(reason)) if reason == "section sprites is not closed"
+        ));
+    }
+
+    #[test]
+    fn mismatched_section() {
+        let result = Template::parse("{{#sprites}}{{/modules}}");
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::InvalidTemplate(_))
+        ));
+    }
+
+    #[test]
+    fn recursive_partial() -> Result<(), PngToWasm4SrcError> {
+        let template = Template::parse("{{> loop}}")?.with_partial("loop", "{{> loop}}")?;
+        let module = ParsedModule::new("sprites", Vec::default(), Vec::default());
+
+        assert!(matches!(
+            template.render_module(&module),
+            Err(PngToWasm4SrcError::InvalidTemplate(_))
+        ));
+
+        Ok(())
+    }
+}
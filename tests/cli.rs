@@ -0,0 +1,149 @@
+// Copyright Claudio Mattera 2021.
+// Distributed under the MIT License.
+// See accompanying file License.txt, or online at
+// https://opensource.org/licenses/MIT
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, read_to_string, write};
+    use std::path::Path;
+    use std::process::{Command, Output};
+
+    use anyhow::Result;
+
+    use png2wasm4src::{build_sprite_modules_tree, Builder, ByteFormat, Visibility};
+
+    fn png2wasm4src(args: &[&str]) -> Result<Output> {
+        let output = Command::new(env!("CARGO_BIN_EXE_png2wasm4src"))
+            .args(args)
+            .output()?;
+        Ok(output)
+    }
+
+    #[test]
+    fn directory_to_stdout() -> Result<()> {
+        let output = png2wasm4src(&["tests/sprites"])?;
+
+        let module = build_sprite_modules_tree(Path::new("tests/sprites"))?.parse()?;
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout)?, module.to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn directory_with_options_matches_builder() -> Result<()> {
+        let output = png2wasm4src(&["tests/sprites", "--visibility", "crate", "--format=binary"])?;
+
+        let expected = Builder::new("tests/sprites")
+            .with_visibility(Visibility::Crate)
+            .with_format(ByteFormat::Binary)
+            .generate()?;
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn file_to_language() -> Result<()> {
+        let output = png2wasm4src(&["tests/animated/spark.gif", "-l", "zig"])?;
+
+        let expected = "const spark_width = 4;
+const spark_height = 2;
+const spark_flags = 1; // BLIT_2BPP
+const spark_frames = [2][2]u8{ .{ 0x14, 0x69 }, .{ 0x3c, 0x69 } };
+const spark_frame_durations = [2]u32{ 6, 12 };
+";
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn output_file_and_check() -> Result<()> {
+        let directory = temp_dir().join("png2wasm4src-cli-output-file-and-check");
+        create_dir_all(&directory)?;
+        let path = directory.join("tiles.rs");
+        let path_argument = path.to_str().expect("Temporary path is not UTF-8");
+
+        let output = png2wasm4src(&["tests/sprites/tiles", "-o", path_argument])?;
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty());
+        assert_eq!(
+            read_to_string(&path)?,
+            Builder::new("tests/sprites/tiles").generate()?
+        );
+
+        let output = png2wasm4src(&["tests/sprites/tiles", "-o", path_argument, "--check"])?;
+        assert!(output.status.success());
+
+        write(&path, "// Outdated\n")?;
+        let output = png2wasm4src(&["tests/sprites/tiles", "-o", path_argument, "--check"])?;
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(read_to_string(&path)?, "// Outdated\n");
+
+        let stderr = String::from_utf8(output.stderr)?;
+        assert!(stderr.contains("\ntop level\n-// Outdated\n+pub mod tiles {\n"));
+        assert!(
+            stderr.contains("\nsprite town in module tiles\n+    pub const TOWN_WIDTH: u32 = 4;\n")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_single_file() -> Result<()> {
+        let directory = temp_dir().join("png2wasm4src-cli-check-single-file");
+        create_dir_all(&directory)?;
+        let path = directory.join("town.rs");
+        let path_argument = path.to_str().expect("Temporary path is not UTF-8");
+
+        let output = png2wasm4src(&["tests/sprites/tiles/town.png", "-o", path_argument])?;
+        assert!(output.status.success());
+
+        let code = read_to_string(&path)?;
+        write(&path, code.replace("0x5a", "0x00"))?;
+        let output = png2wasm4src(&[
+            "tests/sprites/tiles/town.png",
+            "-o",
+            path_argument,
+            "--check",
+        ])?;
+        assert_eq!(output.status.code(), Some(1));
+
+        let stderr = String::from_utf8(output.stderr)?;
+        assert!(stderr.contains(
+            "\nsprite town\n-const TOWN: [u8; 4] = [0x00, 0x00, 0xf0, 0xf0];\n\
+             +const TOWN: [u8; 4] = [0x5a, 0x5a, 0xf0, 0xf0];"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_arguments() -> Result<()> {
+        let output = png2wasm4src(&["tests/sprites", "--language", "cobol"])?;
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8(output.stderr)?
+            .starts_with("error: invalid value 'cobol' for option '--language'"));
+
+        let output = png2wasm4src(&["tests/sprites", "--check"])?;
+        assert_eq!(output.status.code(), Some(2));
+
+        let output = png2wasm4src(&["tests/sprites", "-l", "c", "--naming", "snake-case"])?;
+        assert_eq!(output.status.code(), Some(2));
+
+        let output = png2wasm4src(&["tests/sprites", "-l", "c", "--format", "binary"])?;
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8(output.stderr)?
+            .starts_with("error: option '--format binary' does not apply to C"));
+
+        Ok(())
+    }
+}
@@ -10,7 +10,11 @@ mod tests {
 
     use anyhow::Result;
 
-    use png2wasm4src::{build_sprite_modules_tree, Module};
+    use png2wasm4src::{
+        build_sprite_modules_tree, convert_png_to_rust_palette, convert_png_to_rust_variables,
+        Builder, ByteFormat, Language, Module, Naming, ParsedModule, PngToWasm4SrcError,
+        RustStructBackend, Template, Visibility,
+    };
 
     #[test]
     fn sprite_modules_tree() -> Result<()> {
@@ -31,7 +35,8 @@ mod tests {
                                 PathBuf::from("tests/sprites/characters/npcs/vendor.png"),
                             ],
                             Vec::default(),
-                        ),
+                        )
+                        .with_directory("tests/sprites/characters/npcs"),
                         Module::new(
                             "bosses",
                             vec![
@@ -39,9 +44,11 @@ mod tests {
                                 PathBuf::from("tests/sprites/characters/bosses/behemoth.png"),
                             ],
                             Vec::default(),
-                        ),
+                        )
+                        .with_directory("tests/sprites/characters/bosses"),
                     ],
-                ),
+                )
+                .with_directory("tests/sprites/characters"),
                 Module::new(
                     "tiles",
                     vec![
@@ -50,9 +57,11 @@ mod tests {
                         PathBuf::from("tests/sprites/tiles/desert.png"),
                     ],
                     Vec::default(),
-                ),
+                )
+                .with_directory("tests/sprites/tiles"),
             ],
-        );
+        )
+        .with_directory("tests/sprites");
 
         assert_eq!(module, expected);
 
@@ -145,7 +154,8 @@ mod tests {
                 PathBuf::from("tests/sprites/tiles/desert.png"),
             ],
             Vec::default(),
-        );
+        )
+        .with_directory("tests/sprites");
 
         assert_eq!(module, expected);
 
@@ -202,6 +212,759 @@ mod tests {
 
 }
 
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_module_with_unmapped_color() {
+        let path = PathBuf::from("tests/unmapped-color.png");
+        let npcs = Module::new("npcs", vec![path.clone()], Vec::default());
+        let characters = Module::new("characters", Vec::default(), vec![npcs]);
+        let module = Module::new("sprites", Vec::default(), vec![characters]);
+
+        let result = module.parse();
+
+        match result {
+            Err(PngToWasm4SrcError::Sprite {
+                path: error_path,
+                module,
+                source,
+            }) => {
+                assert_eq!(error_path, path);
+                assert_eq!(module, "sprites::characters::npcs");
+                assert!(matches!(
+                    *source,
+                    PngToWasm4SrcError::UnmappedColor { x: 1, y: 1, .. }
+                ));
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_module_error_message() {
+        let path = PathBuf::from("tests/unmapped-color.png");
+        let module = Module::new("sprites", vec![path], Vec::default());
+
+        let error = module.parse().expect_err("Parsing should fail");
+
+        assert_eq!(
+            error.to_string(),
+            "could not convert sprite tests/unmapped-color.png in module sprites"
+        );
+        assert_eq!(
+            std::error::Error::source(&error).map(|source| source.to_string()),
+            Some("pixel (1, 1) has colour 0x000000, which is not in the palette".to_owned())
+        );
+    }
+
+    #[test]
+    fn parse_all_module_with_errors() -> Result<()> {
+        let tiles = Module::new(
+            "tiles",
+            vec![PathBuf::from("tests/five-colors-rgb.png")],
+            Vec::default(),
+        );
+        let module = Module::new(
+            "sprites",
+            vec![
+                PathBuf::from("tests/four-colors.png"),
+                PathBuf::from("tests/unmapped-color.png"),
+            ],
+            vec![tiles],
+        );
+
+        let errors = module.parse_all().expect_err("Parsing should fail");
+
+        assert_eq!(errors.errors().len(), 2);
+        assert_eq!(
+            errors.to_string(),
+            "could not convert 2 sprite(s)
+  could not convert sprite tests/unmapped-color.png in module sprites: pixel (1, 1) has colour 0x000000, which is not in the palette
+  could not convert sprite tests/five-colors-rgb.png in module sprites::tiles: PNG image is not indexed"
+        );
+
+        let four_colors =
+            convert_png_to_rust_variables("four-colors", include_bytes!("four-colors.png"))?;
+        let expected = ParsedModule::new(
+            "sprites",
+            vec![four_colors],
+            vec![ParsedModule::new("tiles", Vec::default(), Vec::default())],
+        );
+
+        assert_eq!(errors.into_partial_module(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_all_module_without_errors() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/sprites"))?;
+        let expected = build_sprite_modules_tree(Path::new("tests/sprites"))?.parse()?;
+
+        let parsed_module = module.parse_all()?;
+
+        assert_eq!(parsed_module, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parsed_module_with_palette_to_string() -> Result<()> {
+        let palette = convert_png_to_rust_palette("", include_bytes!("palette.png"))?;
+        let module = Module::new(
+            "sprites",
+            vec![PathBuf::from("tests/sprites/characters/player.png")],
+            Vec::default(),
+        );
+        let module = module.parse()?.with_palette(palette);
+        let code = module.to_string();
+
+        let expected = "pub mod sprites {
+    pub const PALETTE: [u32; 4] = [0xe0f8cf, 0x86c06c, 0x306850, 0x071821];
+
+    pub const PLAYER_WIDTH: u32 = 4;
+    pub const PLAYER_HEIGHT: u32 = 4;
+    pub const PLAYER_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const PLAYER: [u8; 4] = [0x5a, 0x5a, 0xf0, 0xf0];
+
+}
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn atlas_with_sidecar_metadata_to_string() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/atlases"))?;
+        let code = module.parse()?.to_string();
+
+        let expected = "pub mod atlases {
+    pub const ITEMS_WIDTH: u32 = 8;
+    pub const ITEMS_HEIGHT: u32 = 4;
+    pub const ITEMS_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const ITEMS: [u8; 8] = [0x00, 0x55, 0x00, 0x55, 0xaa, 0xff, 0xaa, 0xff];
+    pub const ITEMS_EMPTY_SRC_X: u32 = 0;
+    pub const ITEMS_EMPTY_SRC_Y: u32 = 0;
+    pub const ITEMS_EMPTY_WIDTH: u32 = 4;
+    pub const ITEMS_EMPTY_HEIGHT: u32 = 2;
+    pub const ITEMS_HEART_SRC_X: u32 = 4;
+    pub const ITEMS_HEART_SRC_Y: u32 = 0;
+    pub const ITEMS_HEART_WIDTH: u32 = 4;
+    pub const ITEMS_HEART_HEIGHT: u32 = 2;
+    pub const ITEMS_COIN_SRC_X: u32 = 0;
+    pub const ITEMS_COIN_SRC_Y: u32 = 2;
+    pub const ITEMS_COIN_WIDTH: u32 = 4;
+    pub const ITEMS_COIN_HEIGHT: u32 = 2;
+    pub const ITEMS_KEY_SRC_X: u32 = 4;
+    pub const ITEMS_KEY_SRC_Y: u32 = 2;
+    pub const ITEMS_KEY_WIDTH: u32 = 4;
+    pub const ITEMS_KEY_HEIGHT: u32 = 2;
+
+}
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn atlas_with_sidecar_metadata_cargo_build_instructions() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/atlases"))?;
+
+        let mut buffer = String::default();
+        module.generate_cargo_build_instructions(&mut buffer)?;
+
+        let expected = "cargo:rerun-if-changed=tests/atlases
+cargo:rerun-if-changed=tests/atlases/items.png
+cargo:rerun-if-changed=tests/atlases/items.toml
+";
+
+        assert_eq!(buffer, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn packed_module_to_string() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/sprites/tiles"))?;
+        let code = module.parse()?.pack_atlases().to_string();
+
+        let expected = "pub mod tiles {
+    pub const ATLAS_2BPP_WIDTH: u32 = 8;
+    pub const ATLAS_2BPP_HEIGHT: u32 = 8;
+    pub const ATLAS_2BPP_FLAGS: u32 = 1; // BLIT_2BPP
+    pub static ATLAS_2BPP: [u8; 16] = [0x5a, 0x5a, 0x5a, 0x5a, 0xf0, 0xf0, 0xf0, 0xf0, 0x5a, 0x00, 0x5a, 0x00, 0xf0, 0x00, 0xf0, 0x00];
+    pub const DESERT_WIDTH: u32 = 4;
+    pub const DESERT_HEIGHT: u32 = 4;
+    pub const DESERT_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const DESERT_SRC_X: u32 = 0;
+    pub const DESERT_SRC_Y: u32 = 0;
+    pub const DESERT_STRIDE: u32 = 8;
+    pub const FOREST_WIDTH: u32 = 4;
+    pub const FOREST_HEIGHT: u32 = 4;
+    pub const FOREST_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const FOREST_SRC_X: u32 = 4;
+    pub const FOREST_SRC_Y: u32 = 0;
+    pub const FOREST_STRIDE: u32 = 8;
+    pub const TOWN_WIDTH: u32 = 4;
+    pub const TOWN_HEIGHT: u32 = 4;
+    pub const TOWN_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const TOWN_SRC_X: u32 = 0;
+    pub const TOWN_SRC_Y: u32 = 4;
+    pub const TOWN_STRIDE: u32 = 8;
+
+}
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn packed_module_with_atlas_regions() -> Result<()> {
+        let module = Module::new(
+            "sprites",
+            vec![
+                PathBuf::from("tests/atlases/items.png"),
+                PathBuf::from("tests/sprites/characters/player.png"),
+            ],
+            Vec::default(),
+        );
+        let code = module.parse()?.pack_atlases().to_string();
+
+        assert!(code.contains("    pub const PLAYER_SRC_X: u32 = 0;\n"));
+        assert!(code.contains("    pub const PLAYER_SRC_Y: u32 = 4;\n"));
+        assert!(code.contains("    pub const ITEMS_KEY_SRC_X: u32 = 4;\n"));
+        assert!(code.contains("    pub const ITEMS_KEY_SRC_Y: u32 = 2;\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn animations_with_sidecar_metadata_to_string() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/animations"))?;
+        let code = module.parse()?.to_string();
+
+        let expected = "pub mod animations {
+    pub const HERO_WIDTH: u32 = 4;
+    pub const HERO_HEIGHT: u32 = 2;
+    pub const HERO_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const HERO_FRAME_STAND: usize = 0;
+    pub const HERO_FRAME_STEP: usize = 1;
+    pub const HERO_FRAME_JUMP: usize = 2;
+    pub const HERO_FRAME_LAND: usize = 3;
+    pub const HERO_FRAMES: [[u8; 2]; 4] = [[0x00, 0x00], [0x55, 0x55], [0xaa, 0xaa], [0xff, 0xff]];
+    pub const HERO_WALK_FRAME_COUNT: usize = 2;
+    pub const HERO_WALK_FRAMES: [usize; 2] = [0, 1];
+    pub const HERO_WALK_DURATIONS: [u32; 2] = [6, 6];
+    pub const HERO_WALK_LOOP: bool = true;
+    pub const HERO_WALK_PING_PONG: bool = false;
+    pub const HERO_JUMP_FRAME_COUNT: usize = 2;
+    pub const HERO_JUMP_FRAMES: [usize; 2] = [2, 3];
+    pub const HERO_JUMP_DURATIONS: [u32; 2] = [15, 3];
+    pub const HERO_JUMP_LOOP: bool = false;
+    pub const HERO_JUMP_PING_PONG: bool = false;
+
+    pub const SLIME_WIDTH: u32 = 8;
+    pub const SLIME_HEIGHT: u32 = 2;
+    pub const SLIME_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const SLIME_FRAMES: [[u8; 4]; 2] = [[0x00, 0x55, 0x00, 0x55], [0xaa, 0xff, 0xaa, 0xff]];
+    pub const SLIME_BOUNCE_FRAME_COUNT: usize = 2;
+    pub const SLIME_BOUNCE_FRAMES: [usize; 2] = [0, 1];
+    pub const SLIME_BOUNCE_DURATIONS: [u32; 2] = [6, 9];
+    pub const SLIME_BOUNCE_LOOP: bool = true;
+    pub const SLIME_BOUNCE_PING_PONG: bool = true;
+
+}
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn animations_with_sidecar_metadata_cargo_build_instructions() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/animations"))?;
+
+        let mut buffer = String::default();
+        module.generate_cargo_build_instructions(&mut buffer)?;
+
+        let expected = "cargo:rerun-if-changed=tests/animations
+cargo:rerun-if-changed=tests/animations/hero.png
+cargo:rerun-if-changed=tests/animations/hero.toml
+cargo:rerun-if-changed=tests/animations/slime.png
+cargo:rerun-if-changed=tests/animations/slime.json
+";
+
+        assert_eq!(buffer, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn aseprite_module_to_string() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/aseprite"))?;
+        let code = module.parse()?.to_string();
+
+        let expected = "pub mod aseprite {
+    pub const COIN_WIDTH: u32 = 4;
+    pub const COIN_HEIGHT: u32 = 2;
+    pub const COIN_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const COIN: [u8; 2] = [0x34, 0x69];
+
+    pub const WALKER_WIDTH: u32 = 4;
+    pub const WALKER_HEIGHT: u32 = 2;
+    pub const WALKER_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const WALKER_FRAMES: [[u8; 2]; 3] = [[0x3c, 0x69], [0x3c, 0x96], [0x14, 0x69]];
+    pub const WALKER_WALK_FRAME_COUNT: usize = 2;
+    pub const WALKER_WALK_FRAMES: [usize; 2] = [0, 1];
+    pub const WALKER_WALK_DURATIONS: [u32; 2] = [6, 12];
+    pub const WALKER_WALK_LOOP: bool = true;
+    pub const WALKER_WALK_PING_PONG: bool = false;
+    pub const WALKER_BLINK_FRAME_COUNT: usize = 2;
+    pub const WALKER_BLINK_FRAMES: [usize; 2] = [1, 2];
+    pub const WALKER_BLINK_DURATIONS: [u32; 2] = [12, 6];
+    pub const WALKER_BLINK_LOOP: bool = false;
+    pub const WALKER_BLINK_PING_PONG: bool = true;
+
+}
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn animated_module_to_string() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/animated"))?;
+        let code = module.parse()?.to_string();
+
+        let expected = "pub mod animated {
+    pub const BLOB_WIDTH: u32 = 4;
+    pub const BLOB_HEIGHT: u32 = 2;
+    pub const BLOB_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const BLOB_FRAMES: [[u8; 2]; 3] = [[0x14, 0x69], [0x28, 0x96], [0x38, 0x96]];
+    pub const BLOB_FRAME_DURATIONS: [u32; 3] = [6, 12, 3];
+
+    pub const SPARK_WIDTH: u32 = 4;
+    pub const SPARK_HEIGHT: u32 = 2;
+    pub const SPARK_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const SPARK_FRAMES: [[u8; 2]; 2] = [[0x14, 0x69], [0x3c, 0x69]];
+    pub const SPARK_FRAME_DURATIONS: [u32; 2] = [6, 12];
+
+}
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn module_with_uppercase_extension() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/uppercase"))?;
+        let code = module.parse()?.to_string();
+
+        let expected = "pub mod uppercase {
+    pub const WALK_WIDTH: u32 = 4;
+    pub const WALK_HEIGHT: u32 = 2;
+    pub const WALK_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const WALK_FRAMES: [[u8; 2]; 2] = [[0x14, 0x69], [0x3c, 0x69]];
+    pub const WALK_FRAME_DURATIONS: [u32; 2] = [6, 12];
+
+}
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn module_to_c_header_and_source() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/animated"))?;
+        let module = module.parse()?;
+
+        let header = module.to_c_header("ANIMATED_H").to_string();
+        let source = module.to_c_source("animated.h").to_string();
+
+        let expected_header = "#ifndef ANIMATED_H
+#define ANIMATED_H
+
+#include <stdint.h>
+
+#define animated_blobWidth 4
+#define animated_blobHeight 2
+#define animated_blobFlags BLIT_2BPP
+extern const uint8_t animated_blobFrames[3][2];
+extern const uint32_t animated_blobFrameDurations[3];
+
+#define animated_sparkWidth 4
+#define animated_sparkHeight 2
+#define animated_sparkFlags BLIT_2BPP
+extern const uint8_t animated_sparkFrames[2][2];
+extern const uint32_t animated_sparkFrameDurations[2];
+
+#endif // ANIMATED_H
+";
+
+        let expected_source = "#include \"animated.h\"
+
+const uint8_t animated_blobFrames[3][2] = { { 0x14, 0x69 }, { 0x28, 0x96 }, { 0x38, 0x96 } };
+const uint32_t animated_blobFrameDurations[3] = { 6, 12, 3 };
+
+const uint8_t animated_sparkFrames[2][2] = { { 0x14, 0x69 }, { 0x3c, 0x69 } };
+const uint32_t animated_sparkFrameDurations[2] = { 6, 12 };
+
+";
+
+        assert_eq!(header, expected_header);
+        assert_eq!(source, expected_source);
+
+        Ok(())
+    }
+
+    #[test]
+    fn packed_module_to_c() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/sprites/characters/bosses"))?;
+        let module = module.parse()?.pack_atlases();
+        let code = module.to_c().to_string();
+
+        let expected = "#define bosses_atlas_2bppWidth 8
+#define bosses_atlas_2bppHeight 4
+#define bosses_atlas_2bppFlags BLIT_2BPP
+const uint8_t bosses_atlas_2bpp[8] = { 0x5a, 0x5a, 0x5a, 0x5a, 0xf0, 0xf0, 0xf0, 0xf0 };
+#define bosses_behemothWidth 4
+#define bosses_behemothHeight 4
+#define bosses_behemothFlags BLIT_2BPP
+#define bosses_behemothSrcX 0
+#define bosses_behemothSrcY 0
+#define bosses_behemothStride 8
+#define bosses_dragonWidth 4
+#define bosses_dragonHeight 4
+#define bosses_dragonFlags BLIT_2BPP
+#define bosses_dragonSrcX 4
+#define bosses_dragonSrcY 0
+#define bosses_dragonStride 8
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn packed_module_to_zig() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/sprites/characters/bosses"))?;
+        let module = module.parse()?.pack_atlases();
+        let code = module.to_source(Language::Zig).to_string();
+
+        let expected = "const bosses_atlas_2bpp_width = 8;
+const bosses_atlas_2bpp_height = 4;
+const bosses_atlas_2bpp_flags = 1; // BLIT_2BPP
+const bosses_atlas_2bpp = [8]u8{ 0x5a, 0x5a, 0x5a, 0x5a, 0xf0, 0xf0, 0xf0, 0xf0 };
+const bosses_behemoth_width = 4;
+const bosses_behemoth_height = 4;
+const bosses_behemoth_flags = 1; // BLIT_2BPP
+const bosses_behemoth_src_x = 0;
+const bosses_behemoth_src_y = 0;
+const bosses_behemoth_stride = 8;
+const bosses_dragon_width = 4;
+const bosses_dragon_height = 4;
+const bosses_dragon_flags = 1; // BLIT_2BPP
+const bosses_dragon_src_x = 4;
+const bosses_dragon_src_y = 0;
+const bosses_dragon_stride = 8;
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn module_to_rust_structs() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/sprites/characters/npcs"))?;
+        let module = module.parse()?;
+        let code = module.to_source(RustStructBackend::default()).to_string();
+
+        let expected = "/// A WASM-4 sprite, with its data, size and flags
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sprite {
+    pub data: &'static [u8],
+    pub width: u32,
+    pub height: u32,
+    pub flags: u32,
+}
+
+pub mod npcs {
+    pub const BLACKSMITH: super::Sprite = super::Sprite {
+        data: &[0x5a, 0x5a, 0xf0, 0xf0],
+        width: 4,
+        height: 4,
+        flags: 1, // BLIT_2BPP
+    };
+
+    pub const VENDOR: super::Sprite = super::Sprite {
+        data: &[0x5a, 0x5a, 0xf0, 0xf0],
+        width: 4,
+        height: 4,
+        flags: 1, // BLIT_2BPP
+    };
+
+}
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn packed_module_to_rust_structs_with_sprite_type() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/sprites/characters/bosses"))?;
+        let module = module.parse()?.pack_atlases();
+        let backend = RustStructBackend::default().with_sprite_type("crate::Sprite");
+        let code = module.to_source(backend).to_string();
+
+        let expected = "pub mod bosses {
+    pub const ATLAS_2BPP_WIDTH: u32 = 8;
+    pub const ATLAS_2BPP_HEIGHT: u32 = 4;
+    pub const ATLAS_2BPP_FLAGS: u32 = 1; // BLIT_2BPP
+    pub static ATLAS_2BPP: [u8; 8] = [0x5a, 0x5a, 0x5a, 0x5a, 0xf0, 0xf0, 0xf0, 0xf0];
+    pub const BEHEMOTH_WIDTH: u32 = 4;
+    pub const BEHEMOTH_HEIGHT: u32 = 4;
+    pub const BEHEMOTH_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const BEHEMOTH_SRC_X: u32 = 0;
+    pub const BEHEMOTH_SRC_Y: u32 = 0;
+    pub const BEHEMOTH_STRIDE: u32 = 8;
+    pub const DRAGON_WIDTH: u32 = 4;
+    pub const DRAGON_HEIGHT: u32 = 4;
+    pub const DRAGON_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const DRAGON_SRC_X: u32 = 4;
+    pub const DRAGON_SRC_Y: u32 = 0;
+    pub const DRAGON_STRIDE: u32 = 8;
+
+}
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn builder_with_extensions() -> Result<()> {
+        let code = Builder::new("tests/animated")
+            .with_extensions(vec!["GIF"])
+            .generate()?;
+
+        let expected = "pub mod animated {
+    pub const SPARK_WIDTH: u32 = 4;
+    pub const SPARK_HEIGHT: u32 = 2;
+    pub const SPARK_FLAGS: u32 = 1; // BLIT_2BPP
+    pub const SPARK_FRAMES: [[u8; 2]; 2] = [[0x14, 0x69], [0x3c, 0x69]];
+    pub const SPARK_FRAME_DURATIONS: [u32; 2] = [6, 12];
+
+}
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn builder_with_rendering_options() -> Result<()> {
+        let code = Builder::new("tests/sprites/tiles")
+            .with_visibility(Visibility::Crate)
+            .with_naming(Naming::SnakeCase)
+            .with_format(ByteFormat::Binary)
+            .generate()?;
+
+        let expected = "pub(crate) mod tiles {
+    pub(crate) const desert_width: u32 = 4;
+    pub(crate) const desert_height: u32 = 4;
+    pub(crate) const desert_flags: u32 = 1; // BLIT_2BPP
+    pub(crate) const desert: [u8; 4] = [0b01011010, 0b01011010, 0b11110000, 0b11110000];
+
+    pub(crate) const forest_width: u32 = 4;
+    pub(crate) const forest_height: u32 = 4;
+    pub(crate) const forest_flags: u32 = 1; // BLIT_2BPP
+    pub(crate) const forest: [u8; 4] = [0b01011010, 0b01011010, 0b11110000, 0b11110000];
+
+    pub(crate) const town_width: u32 = 4;
+    pub(crate) const town_height: u32 = 4;
+    pub(crate) const town_flags: u32 = 1; // BLIT_2BPP
+    pub(crate) const town: [u8; 4] = [0b01011010, 0b01011010, 0b11110000, 0b11110000];
+
+}
+
+";
+
+        assert_eq!(code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn builder_default_matches_module() -> Result<()> {
+        let code = Builder::new("tests/sprites").generate()?;
+
+        let module = build_sprite_modules_tree(Path::new("tests/sprites"))?.parse()?;
+
+        assert_eq!(code, module.to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn builder_write_to_out_dir() -> Result<()> {
+        let out_dir = std::env::temp_dir().join("png2wasm4src-builder-write-to-out-dir");
+        std::fs::create_dir_all(&out_dir)?;
+        let builder = Builder::new("tests/sprites/tiles");
+
+        std::env::remove_var("OUT_DIR");
+        assert!(matches!(
+            builder.write_to_out_dir("sprites.rs"),
+            Err(PngToWasm4SrcError::MissingOutDir),
+        ));
+
+        // A previous longer output must not leave stale bytes behind
+        let path = out_dir.join("sprites.rs");
+        std::fs::write(&path, "x".repeat(10_000))?;
+
+        std::env::set_var("OUT_DIR", &out_dir);
+        let first_write = builder.write_to_out_dir("sprites.rs")?;
+        let second_write = builder.write_to_out_dir("sprites.rs")?;
+        std::env::remove_var("OUT_DIR");
+
+        assert!(first_write);
+        assert!(!second_write);
+        assert_eq!(std::fs::read_to_string(&path)?, builder.generate()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn builder_check_committed_file() -> Result<()> {
+        let directory = std::env::temp_dir().join("png2wasm4src-builder-check-committed-file");
+        std::fs::create_dir_all(&directory)?;
+        let path = directory.join("sprites.rs");
+        let builder = Builder::new("tests/sprites");
+
+        builder.write_to(&path)?;
+        builder.check(&path)?;
+
+        let code = std::fs::read_to_string(&path)?
+            .replace(
+                "pub const TOWN: [u8; 4] = [0x5a",
+                "pub const TOWN: [u8; 4] = [0x5b",
+            )
+            .replace(
+                "            pub const VENDOR_FLAGS: u32 = 1; // BLIT_2BPP\n",
+                "",
+            );
+        std::fs::write(&path, code)?;
+
+        let error = builder
+            .check(&path)
+            .expect_err("File should be out of date");
+
+        let expected = format!(
+            "file {} is out of date
+sprite vendor in module sprites::characters::npcs
++            pub const VENDOR_FLAGS: u32 = 1; // BLIT_2BPP
+sprite town in module sprites::tiles
+-        pub const TOWN: [u8; 4] = [0x5b, 0x5a, 0xf0, 0xf0];
++        pub const TOWN: [u8; 4] = [0x5a, 0x5a, 0xf0, 0xf0];",
+            path.display()
+        );
+
+        assert!(matches!(error, PngToWasm4SrcError::OutdatedFile { .. }));
+        assert_eq!(error.to_string(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn module_check_file_with_missing_module() -> Result<()> {
+        let directory = std::env::temp_dir().join("png2wasm4src-module-check-missing-module");
+        std::fs::create_dir_all(&directory)?;
+        let path = directory.join("sprites.rs");
+
+        let module = build_sprite_modules_tree(Path::new("tests/sprites/characters"))?.parse()?;
+        let code = module.to_string();
+        let start = code
+            .find("    pub mod npcs {")
+            .expect("Module npcs is missing");
+        let end = code.find("\n\n}").expect("Module characters is not closed");
+        std::fs::write(&path, format!("{}{}", &code[..start], &code[end + 1..]))?;
+
+        let error = module
+            .check_file(&path)
+            .expect_err("File should be out of date");
+        let message = error.to_string();
+
+        assert!(message.contains("\nmodule characters\n+    pub mod npcs {\n"));
+        assert!(message.contains("\nsprite blacksmith in module characters::npcs\n"));
+        assert!(message.contains("\nsprite vendor in module characters::npcs\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn module_to_template() -> Result<()> {
+        let module = build_sprite_modules_tree(Path::new("tests/animated"))?;
+        let module = module.parse()?;
+
+        let template = Template::parse(
+            "// Module {{path}}
+{{#frames}}
+pub static {{rustName}}: Animation<{{frameCount}}> = Animation::new(
+    {{width}}, {{height}}, {{flagsHumanReadable}},
+    [{{#frames}}&[{{bytes}}], {{/frames}}],
+    [{{frameDurations}}],
+);
+{{/frames}}
+",
+        )?;
+        let code = template.render_module(&module)?;
+
+        let expected = "// Module animated
+pub static BLOB: Animation<3> = Animation::new(
+    4, 2, BLIT_2BPP,
+    [&[0x14, 0x69], &[0x28, 0x96], &[0x38, 0x96], ],
+    [6, 12, 3],
+);
+pub static SPARK: Animation<2> = Animation::new(
+    4, 2, BLIT_2BPP,
+    [&[0x14, 0x69], &[0x3c, 0x69], ],
+    [6, 12],
+);
 ";
 
         assert_eq!(code, expected);
@@ -7,7 +7,15 @@
 mod tests {
     use anyhow::Result;
 
-    use png2wasm4src::{convert_png_to_rust_variables, Flags};
+    use png2wasm4src::{
+        convert_apng_to_rust_frames, convert_aseprite_to_rust_frames,
+        convert_aseprite_to_rust_variables, convert_gif_to_rust_frames, convert_png_to_rust_atlas,
+        convert_png_to_rust_frames, convert_png_to_rust_palette, convert_png_to_rust_variables,
+        convert_png_to_rust_variables_with_options, Animation, AtlasLayout, ColorMatching,
+        ColorOrder, ConversionOptions, DuplicateColors, Flags, FrameOrder, LayerSelection,
+        MergedColor, PaletteCompaction, PngToWasm4SrcError, ReferencePalette, Region,
+        RustVariables, SpriteSheet,
+    };
 
     const ONE_COLOR_BYTES: &[u8] = include_bytes!("one-color.png");
     const TWO_COLORS_BYTES: &[u8] = include_bytes!("two-colors.png");
@@ -17,6 +25,35 @@ mod tests {
     // https://publicdomainvectors.org/en/free-clipart/Vector-illustration-of-side-view-of-green-car-pixel-art/27161.html
     const CAR_BYTES: &[u8] = include_bytes!("car.png");
 
+    const TWO_COLORS_RGBA_BYTES: &[u8] = include_bytes!("two-colors-rgba.png");
+    const FOUR_COLORS_RGBA_BYTES: &[u8] = include_bytes!("four-colors-rgba.png");
+    const FIVE_COLORS_RGB_BYTES: &[u8] = include_bytes!("five-colors-rgb.png");
+    const CAR_RGB_BYTES: &[u8] = include_bytes!("car-rgb.png");
+
+    const TWO_COLORS_GRAY_1BIT_BYTES: &[u8] = include_bytes!("two-colors-gray-1bit.png");
+    const FOUR_COLORS_GRAY_2BIT_BYTES: &[u8] = include_bytes!("four-colors-gray-2bit.png");
+    const FOUR_COLORS_GRAY_8BIT_BYTES: &[u8] = include_bytes!("four-colors-gray-8bit.png");
+    const FOUR_COLORS_GRAY_ALPHA_BYTES: &[u8] = include_bytes!("four-colors-gray-alpha.png");
+
+    const UNMAPPED_COLOR_BYTES: &[u8] = include_bytes!("unmapped-color.png");
+
+    const TWO_COLORS_PADDED_BYTES: &[u8] = include_bytes!("two-colors-padded.png");
+    const TWO_COLORS_INTERIOR_DUPLICATES_BYTES: &[u8] =
+        include_bytes!("two-colors-interior-duplicates.png");
+    const FOUR_COLORS_FIVE_ENTRIES_BYTES: &[u8] = include_bytes!("four-colors-five-entries.png");
+    const THREE_ENTRIES_BYTES: &[u8] = include_bytes!("three-entries.png");
+    const THREE_COLORS_DUPLICATE_BYTES: &[u8] = include_bytes!("three-colors-duplicate.png");
+
+    const TWO_COLORS_OVERSIZED_BYTES: &[u8] = include_bytes!("two-colors-oversized.png");
+    const FOUR_COLORS_OVERSIZED_BYTES: &[u8] = include_bytes!("four-colors-oversized.png");
+
+    const FOUR_COLORS_GRAY_2BIT_TRNS_BYTES: &[u8] =
+        include_bytes!("four-colors-gray-2bit-trns.png");
+    const FOUR_COLORS_TWO_TRANSPARENT_BYTES: &[u8] =
+        include_bytes!("four-colors-two-transparent.png");
+    const ONE_COLOR_UNUSED_TRANSPARENT_BYTES: &[u8] =
+        include_bytes!("one-color-unused-transparent.png");
+
     #[test]
     fn encode_one_color() -> Result<()> {
         let name = "one-color";
@@ -220,4 +257,1128 @@ const CAR: [u8; 299] = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x55, 0x00, 0x
 
         Ok(())
     }
+
+    #[test]
+    fn encode_rgba_without_quantization() {
+        let name = "four-colors";
+
+        let result = convert_png_to_rust_variables(name, FOUR_COLORS_RGBA_BYTES);
+
+        assert!(matches!(result, Err(PngToWasm4SrcError::NotIndexedPng)));
+    }
+
+    #[test]
+    fn encode_two_colors_rgba_quantized() -> Result<()> {
+        let name = "two-colors";
+        let options = ConversionOptions::default().with_quantization(true);
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, TWO_COLORS_RGBA_BYTES, &options)?;
+        let expected = convert_png_to_rust_variables(name, TWO_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables.flags(), Flags::OneBitPerPixel);
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_rgba_quantized() -> Result<()> {
+        let name = "four-colors";
+        let options = ConversionOptions::default().with_quantization(true);
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, FOUR_COLORS_RGBA_BYTES, &options)?;
+        let expected = convert_png_to_rust_variables(name, FOUR_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables.flags(), Flags::TwoBitsPerPixel);
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_car_rgb_quantized() -> Result<()> {
+        let name = "car";
+        let options = ConversionOptions::default().with_quantization(true);
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, CAR_RGB_BYTES, &options)?;
+        let expected = convert_png_to_rust_variables(name, CAR_BYTES)?;
+
+        assert_eq!(rust_variables.to_string(), expected.to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_five_colors_rgb_quantized() {
+        let name = "five-colors";
+        let options = ConversionOptions::default().with_quantization(true);
+
+        let result =
+            convert_png_to_rust_variables_with_options(name, FIVE_COLORS_RGB_BYTES, &options);
+
+        assert!(matches!(result, Err(PngToWasm4SrcError::TooManyColors(5))));
+    }
+
+    #[test]
+    fn encode_two_colors_gray_1bit() -> Result<()> {
+        let name = "two-colors";
+
+        let rust_variables = convert_png_to_rust_variables(name, TWO_COLORS_GRAY_1BIT_BYTES)?;
+        let expected = convert_png_to_rust_variables(name, TWO_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_two_colors_gray_1bit_lightest_first() -> Result<()> {
+        let name = "two-colors";
+        let options = ConversionOptions::default().with_color_order(ColorOrder::LightestFirst);
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, TWO_COLORS_GRAY_1BIT_BYTES, &options)?;
+
+        let expected_flags: Flags = Flags::OneBitPerPixel;
+        let expected_data: [u8; 8] = [0x0f, 0x0f, 0x0f, 0x0f, 0x0f, 0x0f, 0x0f, 0x0f];
+
+        assert_eq!(rust_variables.flags(), expected_flags);
+        assert_eq!(rust_variables.data(), expected_data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_gray_2bit() -> Result<()> {
+        let name = "four-colors";
+
+        let rust_variables = convert_png_to_rust_variables(name, FOUR_COLORS_GRAY_2BIT_BYTES)?;
+        let expected = convert_png_to_rust_variables(name, FOUR_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_gray_2bit_lightest_first() -> Result<()> {
+        let name = "four-colors";
+        let options = ConversionOptions::default().with_color_order(ColorOrder::LightestFirst);
+
+        let rust_variables = convert_png_to_rust_variables_with_options(
+            name,
+            FOUR_COLORS_GRAY_2BIT_BYTES,
+            &options,
+        )?;
+
+        let expected_flags: Flags = Flags::TwoBitsPerPixel;
+        let expected_data: [u8; 4] = [0xa5, 0xa5, 0x0f, 0x0f];
+
+        assert_eq!(rust_variables.flags(), expected_flags);
+        assert_eq!(rust_variables.data(), expected_data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_gray_8bit() -> Result<()> {
+        let name = "four-colors";
+
+        let rust_variables = convert_png_to_rust_variables(name, FOUR_COLORS_GRAY_8BIT_BYTES)?;
+        let expected = convert_png_to_rust_variables(name, FOUR_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_gray_alpha() -> Result<()> {
+        let name = "four-colors";
+
+        let rust_variables = convert_png_to_rust_variables(name, FOUR_COLORS_GRAY_ALPHA_BYTES)?;
+        let expected = convert_png_to_rust_variables(name, FOUR_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_gray_8bit_lightest_first() -> Result<()> {
+        let name = "four-colors";
+        let options = ConversionOptions::default().with_color_order(ColorOrder::LightestFirst);
+
+        let rust_variables = convert_png_to_rust_variables_with_options(
+            name,
+            FOUR_COLORS_GRAY_8BIT_BYTES,
+            &options,
+        )?;
+        let expected = convert_png_to_rust_variables_with_options(
+            name,
+            FOUR_COLORS_GRAY_2BIT_BYTES,
+            &options,
+        )?;
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_unmapped_color() {
+        let name = "unmapped-color";
+
+        let result = convert_png_to_rust_variables(name, UNMAPPED_COLOR_BYTES);
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::UnmappedColor {
+                color: 0x000000,
+                x: 1,
+                y: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn encode_two_colors_padded() -> Result<()> {
+        let name = "two-colors";
+
+        let rust_variables = convert_png_to_rust_variables(name, TWO_COLORS_PADDED_BYTES)?;
+        let expected =
+            convert_png_to_rust_variables(name, TWO_COLORS_BYTES)?.with_merged_colors(vec![
+                MergedColor::new(0x306950, 2, 1),
+                MergedColor::new(0x306950, 3, 1),
+            ]);
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_two_colors_interior_duplicates() -> Result<()> {
+        let name = "two-colors";
+
+        let rust_variables =
+            convert_png_to_rust_variables(name, TWO_COLORS_INTERIOR_DUPLICATES_BYTES)?;
+
+        assert_eq!(rust_variables.flags(), Flags::OneBitPerPixel);
+        assert_eq!(rust_variables.data(), &[0b00110011]);
+        assert_eq!(
+            rust_variables.merged_colors(),
+            &[
+                MergedColor::new(0x000000, 1, 0),
+                MergedColor::new(0xffffff, 3, 1),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_three_entries() -> Result<()> {
+        let name = "three-entries";
+
+        let rust_variables = convert_png_to_rust_variables(name, THREE_ENTRIES_BYTES)?;
+
+        assert_eq!(rust_variables.flags(), Flags::TwoBitsPerPixel);
+        assert_eq!(rust_variables.data(), &[0b00011001]);
+        assert_eq!(rust_variables.merged_colors(), &[]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_three_colors_duplicate() -> Result<()> {
+        let name = "three-colors";
+
+        let rust_variables = convert_png_to_rust_variables(name, THREE_COLORS_DUPLICATE_BYTES)?;
+
+        assert_eq!(rust_variables.flags(), Flags::TwoBitsPerPixel);
+        assert_eq!(rust_variables.data(), &[0b00011000]);
+        assert_eq!(
+            rust_variables.merged_colors(),
+            &[MergedColor::new(0x071821, 2, 0)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_five_entries() -> Result<()> {
+        let name = "four-colors";
+
+        let rust_variables = convert_png_to_rust_variables(name, FOUR_COLORS_FIVE_ENTRIES_BYTES)?;
+
+        assert_eq!(rust_variables.flags(), Flags::TwoBitsPerPixel);
+        assert_eq!(rust_variables.data(), &[0b00010110, 0b11000101]);
+        assert_eq!(
+            rust_variables.merged_colors(),
+            &[MergedColor::new(0x306950, 2, 1)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_five_entries_rejecting_duplicates() {
+        let name = "four-colors";
+        let options = ConversionOptions::default().with_duplicate_colors(DuplicateColors::Reject);
+
+        let result = convert_png_to_rust_variables_with_options(
+            name,
+            FOUR_COLORS_FIVE_ENTRIES_BYTES,
+            &options,
+        );
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::DuplicatePaletteColor {
+                color: 0x306950,
+                index: 2,
+                first_index: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn encode_two_colors_padded_rejecting_duplicates() {
+        let name = "two-colors";
+        let options = ConversionOptions::default().with_duplicate_colors(DuplicateColors::Reject);
+
+        let result =
+            convert_png_to_rust_variables_with_options(name, TWO_COLORS_PADDED_BYTES, &options);
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::DuplicatePaletteColor {
+                color: 0x306950,
+                index: 2,
+                first_index: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn encode_four_colors_rejecting_duplicates() -> Result<()> {
+        let name = "four-colors";
+        let options = ConversionOptions::default().with_duplicate_colors(DuplicateColors::Reject);
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, FOUR_COLORS_BYTES, &options)?;
+        let expected = convert_png_to_rust_variables(name, FOUR_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_oversized_palette_without_compaction() {
+        let name = "two-colors";
+
+        let result = convert_png_to_rust_variables(name, TWO_COLORS_OVERSIZED_BYTES);
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::InvalidPaletteSize(256))
+        ));
+    }
+
+    #[test]
+    fn encode_two_colors_oversized_palette_order() -> Result<()> {
+        let name = "two-colors";
+        let options =
+            ConversionOptions::default().with_palette_compaction(PaletteCompaction::PaletteOrder);
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, TWO_COLORS_OVERSIZED_BYTES, &options)?;
+        let expected = convert_png_to_rust_variables(name, TWO_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_oversized_palette_order() -> Result<()> {
+        let name = "four-colors";
+        let options =
+            ConversionOptions::default().with_palette_compaction(PaletteCompaction::PaletteOrder);
+
+        let rust_variables = convert_png_to_rust_variables_with_options(
+            name,
+            FOUR_COLORS_OVERSIZED_BYTES,
+            &options,
+        )?;
+
+        let expected_flags: Flags = Flags::TwoBitsPerPixel;
+        let expected_data: [u8; 4] = [0xa5, 0xa5, 0x0f, 0x0f];
+
+        assert_eq!(rust_variables.flags(), expected_flags);
+        assert_eq!(rust_variables.data(), expected_data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_oversized_luminance() -> Result<()> {
+        let name = "four-colors";
+        let options =
+            ConversionOptions::default().with_palette_compaction(PaletteCompaction::Luminance);
+
+        let rust_variables = convert_png_to_rust_variables_with_options(
+            name,
+            FOUR_COLORS_OVERSIZED_BYTES,
+            &options,
+        )?;
+        let expected = convert_png_to_rust_variables(name, FOUR_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_without_transparent_index() -> Result<()> {
+        let name = "four-colors";
+
+        let rust_variables = convert_png_to_rust_variables(name, FOUR_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables.transparent_index(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_two_transparent_colors_without_transparent_index() -> Result<()> {
+        let name = "four-colors";
+        let options = ConversionOptions::default().with_duplicate_colors(DuplicateColors::Reject);
+
+        let rust_variables =
+            convert_png_to_rust_variables(name, FOUR_COLORS_TWO_TRANSPARENT_BYTES)?;
+        let rejecting_duplicates = convert_png_to_rust_variables_with_options(
+            name,
+            FOUR_COLORS_TWO_TRANSPARENT_BYTES,
+            &options,
+        )?;
+
+        let expected = RustVariables::new(name, 4, 1, Flags::TwoBitsPerPixel, vec![0b00011011]);
+
+        assert_eq!(rust_variables, expected);
+        assert_eq!(rejecting_duplicates, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_two_transparent_colors_transparent_index() -> Result<()> {
+        let name = "four-colors";
+        let options = ConversionOptions::default().with_transparent_index(Some(0));
+
+        let rust_variables = convert_png_to_rust_variables_with_options(
+            name,
+            FOUR_COLORS_TWO_TRANSPARENT_BYTES,
+            &options,
+        )?;
+
+        let expected = RustVariables::new(name, 4, 1, Flags::TwoBitsPerPixel, vec![0b00000110])
+            .with_transparent_index(Some(0))
+            .with_merged_colors(vec![MergedColor::new(0x000000, 1, 0)]);
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_unused_transparent_color_transparent_index() -> Result<()> {
+        let name = "one-color";
+        let options = ConversionOptions::default().with_transparent_index(Some(3));
+
+        let rust_variables = convert_png_to_rust_variables_with_options(
+            name,
+            ONE_COLOR_UNUSED_TRANSPARENT_BYTES,
+            &options,
+        )?;
+
+        let expected = RustVariables::new(name, 8, 1, Flags::OneBitPerPixel, vec![0x00]);
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_transparent_index() -> Result<()> {
+        let name = "four-colors";
+        let options = ConversionOptions::default().with_transparent_index(Some(3));
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, FOUR_COLORS_BYTES, &options)?;
+
+        let expected_flags: Flags = Flags::TwoBitsPerPixel;
+        let expected_data: [u8; 4] = [0x5a, 0x5a, 0x0f, 0x0f];
+
+        assert_eq!(rust_variables.flags(), expected_flags);
+        assert_eq!(rust_variables.data(), expected_data);
+        assert_eq!(rust_variables.transparent_index(), Some(3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_rgba_transparent_index() -> Result<()> {
+        let name = "four-colors";
+        let options = ConversionOptions::default()
+            .with_quantization(true)
+            .with_transparent_index(Some(3));
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, FOUR_COLORS_RGBA_BYTES, &options)?;
+        let expected =
+            convert_png_to_rust_variables_with_options(name, FOUR_COLORS_BYTES, &options)?;
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_two_colors_transparent_index_string() -> Result<()> {
+        let name = "two-colors";
+        let options = ConversionOptions::default().with_transparent_index(Some(0));
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, TWO_COLORS_BYTES, &options)?;
+        let rust_code = rust_variables.to_string();
+
+        let expected = "const TWO_COLORS_WIDTH: u32 = 8;
+const TWO_COLORS_HEIGHT: u32 = 8;
+const TWO_COLORS_FLAGS: u32 = 0; // BLIT_1BPP
+const TWO_COLORS_TRANSPARENT_INDEX: u32 = 0;
+const TWO_COLORS: [u8; 8] = [0xf0, 0xf0, 0xf0, 0xf0, 0xf0, 0xf0, 0xf0, 0xf0];\n";
+
+        assert_eq!(rust_code, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_two_colors_transparent_index_two_bits_per_pixel() -> Result<()> {
+        let name = "two-colors";
+        let options = ConversionOptions::default().with_transparent_index(Some(2));
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, TWO_COLORS_BYTES, &options)?;
+
+        let expected_flags: Flags = Flags::TwoBitsPerPixel;
+        let expected_data: [u8; 16] = [
+            0x55, 0xaa, 0x55, 0xaa, 0x55, 0xaa, 0x55, 0xaa, 0x55, 0xaa, 0x55, 0xaa, 0x55, 0xaa,
+            0x55, 0xaa,
+        ];
+
+        assert_eq!(rust_variables.flags(), expected_flags);
+        assert_eq!(rust_variables.data(), expected_data);
+        assert_eq!(rust_variables.transparent_index(), Some(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_gray_transparent_index() -> Result<()> {
+        let name = "four-colors";
+        let options = ConversionOptions::default().with_transparent_index(Some(0));
+
+        let rust_variables = convert_png_to_rust_variables_with_options(
+            name,
+            FOUR_COLORS_GRAY_2BIT_TRNS_BYTES,
+            &options,
+        )?;
+
+        let expected_data: [u8; 4] = [0x5a, 0x5a, 0x0f, 0x0f];
+
+        assert_eq!(rust_variables.data(), expected_data);
+        assert_eq!(rust_variables.transparent_index(), Some(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_invalid_transparent_index() {
+        let name = "four-colors";
+        let options = ConversionOptions::default().with_transparent_index(Some(4));
+
+        let result = convert_png_to_rust_variables_with_options(name, FOUR_COLORS_BYTES, &options);
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::InvalidTransparentIndex(4))
+        ));
+    }
+
+    const FOUR_COLORS_PALETTE: [u32; 4] = [0x071821, 0x306950, 0x86bf6b, 0xdef7cd];
+
+    #[test]
+    fn encode_four_colors_reference_palette() -> Result<()> {
+        let name = "four-colors";
+        let reference_palette = ReferencePalette::new(FOUR_COLORS_PALETTE.iter().copied());
+        let options = ConversionOptions::default().with_reference_palette(Some(reference_palette));
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, FOUR_COLORS_BYTES, &options)?;
+        let expected = convert_png_to_rust_variables(name, FOUR_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_reversed_reference_palette() -> Result<()> {
+        let name = "four-colors";
+        let reference_palette = ReferencePalette::new(FOUR_COLORS_PALETTE.iter().rev().copied());
+        let options = ConversionOptions::default().with_reference_palette(Some(reference_palette));
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, FOUR_COLORS_RGBA_BYTES, &options)?;
+
+        let expected_flags: Flags = Flags::TwoBitsPerPixel;
+        let expected_data: [u8; 4] = [0xa5, 0xa5, 0x0f, 0x0f];
+
+        assert_eq!(rust_variables.flags(), expected_flags);
+        assert_eq!(rust_variables.data(), expected_data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_two_colors_reference_palette() -> Result<()> {
+        let name = "two-colors";
+        let reference_palette = ReferencePalette::new(FOUR_COLORS_PALETTE.iter().copied());
+        let options = ConversionOptions::default().with_reference_palette(Some(reference_palette));
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, TWO_COLORS_OVERSIZED_BYTES, &options)?;
+        let expected = convert_png_to_rust_variables(name, TWO_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_nearest_reference_palette() -> Result<()> {
+        let name = "four-colors";
+        let reference_palette = ReferencePalette::new(vec![0x081820, 0x346856, 0x88c070, 0xe0f8d0])
+            .with_matching(ColorMatching::Nearest { max_distance: 8 });
+        let options = ConversionOptions::default().with_reference_palette(Some(reference_palette));
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, FOUR_COLORS_BYTES, &options)?;
+        let expected = convert_png_to_rust_variables(name, FOUR_COLORS_BYTES)?;
+
+        assert_eq!(rust_variables, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_four_colors_exact_reference_palette_mismatch() {
+        let name = "four-colors";
+        let reference_palette = ReferencePalette::new(vec![0x081820, 0x346856, 0x88c070, 0xe0f8d0]);
+        let options = ConversionOptions::default().with_reference_palette(Some(reference_palette));
+
+        let result = convert_png_to_rust_variables_with_options(name, FOUR_COLORS_BYTES, &options);
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::UnmappedColor {
+                color: 0x306950,
+                x: 0,
+                y: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn encode_four_colors_reference_palette_transparent_index() -> Result<()> {
+        let name = "four-colors";
+        let reference_palette = ReferencePalette::new(FOUR_COLORS_PALETTE.iter().rev().copied());
+        let options = ConversionOptions::default()
+            .with_reference_palette(Some(reference_palette))
+            .with_transparent_index(Some(0));
+
+        let rust_variables =
+            convert_png_to_rust_variables_with_options(name, FOUR_COLORS_BYTES, &options)?;
+
+        let expected_data: [u8; 4] = [0xa5, 0xa5, 0x00, 0x00];
+
+        assert_eq!(rust_variables.data(), expected_data);
+        assert_eq!(rust_variables.transparent_index(), Some(0));
+
+        Ok(())
+    }
+
+    const PALETTE_BYTES: &[u8] = include_bytes!("palette.png");
+    const PALETTE_SIXTEEN_ENTRIES_BYTES: &[u8] = include_bytes!("palette-sixteen-entries.png");
+
+    #[test]
+    fn palette_from_pixels() -> Result<()> {
+        let name = "game";
+
+        let palette = convert_png_to_rust_palette(name, PALETTE_BYTES)?;
+
+        assert_eq!(palette.colors(), [0xe0f8cf, 0x86c06c, 0x306850, 0x071821]);
+        assert_eq!(
+            palette.to_string(),
+            "const GAME_PALETTE: [u32; 4] = [0xe0f8cf, 0x86c06c, 0x306850, 0x071821];\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn palette_from_indexed_palette() -> Result<()> {
+        let name = "four-colors";
+
+        let palette = convert_png_to_rust_palette(name, FOUR_COLORS_BYTES)?;
+
+        assert_eq!(palette.colors(), FOUR_COLORS_PALETTE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn palette_from_indexed_palette_of_invalid_size() {
+        let name = "two-colors";
+
+        let result = convert_png_to_rust_palette(name, TWO_COLORS_BYTES);
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::InvalidPaletteSize(2))
+        ));
+    }
+
+    #[test]
+    fn palette_from_indexed_palette_of_sixteen_entries() -> Result<()> {
+        let name = "sixteen-entries";
+
+        let palette = convert_png_to_rust_palette(name, PALETTE_SIXTEEN_ENTRIES_BYTES)?;
+
+        assert_eq!(palette.colors(), [0xfff6d3, 0xf9a875, 0xeb6b6f, 0x7c3f58]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn palette_from_non_indexed_image() {
+        let name = "car";
+
+        let result = convert_png_to_rust_palette(name, CAR_RGB_BYTES);
+
+        assert!(matches!(result, Err(PngToWasm4SrcError::NotIndexedPng)));
+    }
+
+    const FOUR_FRAMES_BYTES: &[u8] = include_bytes!("four-frames.png");
+
+    #[test]
+    fn frames_in_row_major_order() -> Result<()> {
+        let name = "four-frames";
+        let sheet = SpriteSheet::new(4, 2);
+
+        let rust_frames = convert_png_to_rust_frames(
+            name,
+            FOUR_FRAMES_BYTES,
+            &sheet,
+            &ConversionOptions::default(),
+        )?;
+
+        let expected_frames = vec![
+            vec![0x00, 0x00],
+            vec![0x55, 0x55],
+            vec![0xaa, 0xaa],
+            vec![0xff, 0xff],
+        ];
+
+        assert_eq!(rust_frames.width(), 4);
+        assert_eq!(rust_frames.height(), 2);
+        assert_eq!(rust_frames.flags(), Flags::TwoBitsPerPixel);
+        assert_eq!(rust_frames.frames(), expected_frames);
+
+        Ok(())
+    }
+
+    #[test]
+    fn frames_in_column_major_order() -> Result<()> {
+        let name = "four-frames";
+        let sheet = SpriteSheet::new(4, 2).with_frame_order(FrameOrder::ColumnMajor);
+
+        let rust_frames = convert_png_to_rust_frames(
+            name,
+            FOUR_FRAMES_BYTES,
+            &sheet,
+            &ConversionOptions::default(),
+        )?;
+
+        let expected_frames = vec![
+            vec![0x00, 0x00],
+            vec![0xaa, 0xaa],
+            vec![0x55, 0x55],
+            vec![0xff, 0xff],
+        ];
+
+        assert_eq!(rust_frames.frames(), expected_frames);
+
+        Ok(())
+    }
+
+    #[test]
+    fn frames_with_names_to_string() -> Result<()> {
+        let name = "four-frames";
+        let sheet = SpriteSheet::new(8, 2).with_frame_names(vec!["top", "bottom"]);
+
+        let rust_frames = convert_png_to_rust_frames(
+            name,
+            FOUR_FRAMES_BYTES,
+            &sheet,
+            &ConversionOptions::default(),
+        )?;
+
+        let expected = "const FOUR_FRAMES_WIDTH: u32 = 8;
+const FOUR_FRAMES_HEIGHT: u32 = 2;
+const FOUR_FRAMES_FLAGS: u32 = 1; // BLIT_2BPP
+const FOUR_FRAMES_FRAME_TOP: usize = 0;
+const FOUR_FRAMES_FRAME_BOTTOM: usize = 1;
+const FOUR_FRAMES_FRAMES: [[u8; 4]; 2] = [[0x00, 0x55, 0x00, 0x55], [0xaa, 0xff, 0xaa, 0xff]];\n";
+
+        assert_eq!(rust_frames.to_string(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn frames_with_merged_colors() -> Result<()> {
+        let name = "two-colors";
+        let sheet = SpriteSheet::new(8, 4);
+
+        let rust_frames = convert_png_to_rust_frames(
+            name,
+            TWO_COLORS_PADDED_BYTES,
+            &sheet,
+            &ConversionOptions::default(),
+        )?;
+
+        assert_eq!(rust_frames.frames().len(), 2);
+        assert_eq!(
+            rust_frames.merged_colors(),
+            &[
+                MergedColor::new(0x306950, 2, 1),
+                MergedColor::new(0x306950, 3, 1),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn frames_of_invalid_size() {
+        let name = "four-frames";
+        let sheet = SpriteSheet::new(3, 2);
+
+        let result = convert_png_to_rust_frames(
+            name,
+            FOUR_FRAMES_BYTES,
+            &sheet,
+            &ConversionOptions::default(),
+        );
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::InvalidFrameSize {
+                frame_width: 3,
+                frame_height: 2,
+                width: 8,
+                height: 4,
+            })
+        ));
+    }
+
+    #[test]
+    fn atlas_with_regions() -> Result<()> {
+        let name = "four-frames";
+        let regions = vec![
+            Region::new("left", 0, 0, 4, 4),
+            Region::new("bottom-right", 4, 2, 4, 2),
+        ];
+        let layout = AtlasLayout::Regions(regions.clone());
+
+        let rust_variables = convert_png_to_rust_atlas(
+            name,
+            FOUR_FRAMES_BYTES,
+            &layout,
+            &ConversionOptions::default(),
+        )?;
+
+        let expected_data: [u8; 8] = [0x00, 0x55, 0x00, 0x55, 0xaa, 0xff, 0xaa, 0xff];
+
+        assert_eq!(rust_variables.data(), expected_data);
+        assert_eq!(rust_variables.regions(), regions);
+
+        Ok(())
+    }
+
+    #[test]
+    fn atlas_with_region_out_of_bounds() {
+        let name = "four-frames";
+        let layout = AtlasLayout::Regions(vec![Region::new("outside", 4, 2, 8, 2)]);
+
+        let result = convert_png_to_rust_atlas(
+            name,
+            FOUR_FRAMES_BYTES,
+            &layout,
+            &ConversionOptions::default(),
+        );
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::RegionOutOfBounds { name }) if name == "outside"
+        ));
+    }
+
+    const TWO_COLORS_ASEPRITE_BYTES: &[u8] = include_bytes!("two-colors.aseprite");
+    const FOUR_COLORS_ASEPRITE_BYTES: &[u8] = include_bytes!("four-colors.aseprite");
+    const CAR_ASEPRITE_BYTES: &[u8] = include_bytes!("car.aseprite");
+    const COIN_ASEPRITE_BYTES: &[u8] = include_bytes!("aseprite/coin.aseprite");
+    const WALKER_ASEPRITE_BYTES: &[u8] = include_bytes!("aseprite/walker.aseprite");
+
+    #[test]
+    fn aseprite_matches_png() -> Result<()> {
+        let cases = [
+            ("two-colors", TWO_COLORS_ASEPRITE_BYTES, TWO_COLORS_BYTES),
+            ("four-colors", FOUR_COLORS_ASEPRITE_BYTES, FOUR_COLORS_BYTES),
+            ("car", CAR_ASEPRITE_BYTES, CAR_BYTES),
+        ];
+
+        for (name, aseprite_bytes, png_bytes) in cases {
+            let rust_variables = convert_aseprite_to_rust_variables(
+                name,
+                aseprite_bytes,
+                &LayerSelection::Visible,
+                &ConversionOptions::default(),
+            )?;
+            let expected = convert_png_to_rust_variables(name, png_bytes)?;
+
+            assert_eq!(rust_variables, expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn aseprite_visible_layers() -> Result<()> {
+        let name = "coin";
+
+        let rust_variables = convert_aseprite_to_rust_variables(
+            name,
+            COIN_ASEPRITE_BYTES,
+            &LayerSelection::Visible,
+            &ConversionOptions::default(),
+        )?;
+
+        let expected_data: [u8; 2] = [0x14, 0x69];
+
+        assert_eq!(rust_variables.flags(), Flags::TwoBitsPerPixel);
+        assert_eq!(rust_variables.data(), expected_data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn aseprite_named_layers() -> Result<()> {
+        let name = "coin";
+        let layers = LayerSelection::Named(vec!["base".into(), "shine".into()]);
+
+        let rust_variables = convert_aseprite_to_rust_variables(
+            name,
+            COIN_ASEPRITE_BYTES,
+            &layers,
+            &ConversionOptions::default(),
+        )?;
+
+        let expected_data: [u8; 2] = [0x34, 0x69];
+
+        assert_eq!(rust_variables.data(), expected_data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn aseprite_unknown_layer() {
+        let name = "coin";
+        let layers = LayerSelection::Named(vec!["outline".into()]);
+
+        let result = convert_aseprite_to_rust_variables(
+            name,
+            COIN_ASEPRITE_BYTES,
+            &layers,
+            &ConversionOptions::default(),
+        );
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::UnknownLayer(name)) if name == "outline"
+        ));
+    }
+
+    #[test]
+    fn aseprite_from_png() {
+        let name = "car";
+
+        let result = convert_aseprite_to_rust_variables(
+            name,
+            CAR_BYTES,
+            &LayerSelection::Visible,
+            &ConversionOptions::default(),
+        );
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::InvalidAseprite(_))
+        ));
+    }
+
+    #[test]
+    fn aseprite_without_frames() {
+        let mut header = vec![0; 128];
+        header[0..4].copy_from_slice(&128u32.to_le_bytes());
+        header[4..6].copy_from_slice(&0xa5e0u16.to_le_bytes());
+        header[8..10].copy_from_slice(&8u16.to_le_bytes());
+        header[10..12].copy_from_slice(&8u16.to_le_bytes());
+        header[12..14].copy_from_slice(&8u16.to_le_bytes());
+
+        let result = convert_aseprite_to_rust_frames(
+            "empty",
+            &header,
+            &LayerSelection::Visible,
+            &ConversionOptions::default(),
+        );
+
+        assert!(matches!(
+            result,
+            Err(PngToWasm4SrcError::InvalidAseprite("file has no frames"))
+        ));
+    }
+
+    #[test]
+    fn aseprite_frames_and_tags() -> Result<()> {
+        let name = "walker";
+
+        let rust_frames = convert_aseprite_to_rust_frames(
+            name,
+            WALKER_ASEPRITE_BYTES,
+            &LayerSelection::Visible,
+            &ConversionOptions::default(),
+        )?;
+
+        let expected_frames = vec![vec![0x3c, 0x69], vec![0x3c, 0x96], vec![0x14, 0x69]];
+        let expected_animations = vec![
+            Animation::new("walk", vec![0, 1], vec![6, 12]),
+            Animation::new("blink", vec![1, 2], vec![12, 6])
+                .with_looping(false)
+                .with_ping_pong(true),
+        ];
+
+        assert_eq!(rust_frames.width(), 4);
+        assert_eq!(rust_frames.height(), 2);
+        assert_eq!(rust_frames.frames(), expected_frames);
+        assert_eq!(rust_frames.animations(), expected_animations);
+
+        Ok(())
+    }
+
+    const BLOB_APNG_BYTES: &[u8] = include_bytes!("animated/blob.png");
+    const TRANSPARENT_APNG_BYTES: &[u8] = include_bytes!("transparent-animation.png");
+    const SPARK_GIF_BYTES: &[u8] = include_bytes!("animated/spark.gif");
+
+    #[test]
+    fn apng_frames_and_durations() -> Result<()> {
+        let name = "blob";
+
+        let rust_frames =
+            convert_apng_to_rust_frames(name, BLOB_APNG_BYTES, &ConversionOptions::default())?;
+
+        let expected_frames = vec![vec![0x14, 0x69], vec![0x28, 0x96], vec![0x38, 0x96]];
+        let expected_durations = [6, 12, 3];
+
+        assert_eq!(rust_frames.width(), 4);
+        assert_eq!(rust_frames.height(), 2);
+        assert_eq!(rust_frames.flags(), Flags::TwoBitsPerPixel);
+        assert_eq!(rust_frames.frames(), expected_frames);
+        assert_eq!(rust_frames.frame_durations(), expected_durations);
+
+        Ok(())
+    }
+
+    #[test]
+    fn apng_transparent_color() -> Result<()> {
+        let name = "transparent";
+        let options = ConversionOptions::default().with_transparent_index(Some(0));
+
+        let rust_frames = convert_apng_to_rust_frames(
+            name,
+            TRANSPARENT_APNG_BYTES,
+            &ConversionOptions::default(),
+        )?;
+        let with_transparent_index =
+            convert_apng_to_rust_frames(name, TRANSPARENT_APNG_BYTES, &options)?;
+
+        let expected_frames = vec![vec![0b00011011], vec![0b00001101]];
+
+        assert_eq!(rust_frames.flags(), Flags::TwoBitsPerPixel);
+        assert_eq!(rust_frames.frames(), expected_frames);
+        assert_eq!(rust_frames.transparent_index(), None);
+        assert_eq!(with_transparent_index.frames(), expected_frames);
+        assert_eq!(with_transparent_index.transparent_index(), Some(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn apng_from_still_png() -> Result<()> {
+        let name = "four-colors";
+
+        let rust_frames =
+            convert_apng_to_rust_frames(name, FOUR_COLORS_BYTES, &ConversionOptions::default())?;
+
+        let expected_frames = vec![vec![0x5a, 0x5a, 0xf0, 0xf0]];
+
+        assert_eq!(rust_frames.frames(), expected_frames);
+        assert!(rust_frames.frame_durations().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn gif_frames_and_durations() -> Result<()> {
+        let name = "spark";
+
+        let rust_frames =
+            convert_gif_to_rust_frames(name, SPARK_GIF_BYTES, &ConversionOptions::default())?;
+
+        let expected_frames = vec![vec![0x14, 0x69], vec![0x3c, 0x69]];
+        let expected_durations = [6, 12];
+
+        assert_eq!(rust_frames.flags(), Flags::TwoBitsPerPixel);
+        assert_eq!(rust_frames.frames(), expected_frames);
+        assert_eq!(rust_frames.frame_durations(), expected_durations);
+
+        Ok(())
+    }
 }